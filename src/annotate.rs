@@ -0,0 +1,600 @@
+use crate::canvas::{polygon_spans, Canvas, Color};
+use crate::error::Error;
+#[cfg(feature = "ndarray")]
+use crate::frame::Frame;
+use crate::frame::{RawFrame, VideoFrame};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Padding between the text of a label and the edges of its box.
+const LABEL_PADDING: u32 = 2;
+
+/// Geometry of an annotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Shape {
+    Rect {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    Polyline(Vec<(i32, i32)>),
+    Polygon(Vec<(i32, i32)>),
+    Point {
+        x: i32,
+        y: i32,
+    },
+    Label {
+        x: i32,
+        y: i32,
+    },
+}
+
+/// A shape to draw onto a frame, such as the bounding box of a detection, with an optional label.
+///
+/// Lines are drawn with the annotation color and thickness. Labels are drawn in a box with the
+/// annotation color, just above the top left corner of the shape (or just inside it if the shape
+/// touches the top of the frame).
+///
+/// # Example
+///
+/// ```ignore
+/// let annotation = Annotation::rect(120, 80, 64, 128)
+///     .with_color(Color::RED)
+///     .with_label("person 0.93");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    shape: Shape,
+    color: Color,
+    thickness: u32,
+    fill: Option<Color>,
+    label: Option<String>,
+    font_size: u32,
+}
+
+impl Annotation {
+    /// Create a rectangle. The outline is drawn on the inside of the rectangle.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position of the left edge.
+    /// * `y` - Vertical position of the top edge.
+    /// * `width` - Width of rectangle.
+    /// * `height` - Height of rectangle.
+    pub fn rect(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self::new(Shape::Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Create an open line through a list of points.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - Points as `(x, y)` coordinates.
+    pub fn polyline(points: impl IntoIterator<Item = (i32, i32)>) -> Self {
+        Self::new(Shape::Polyline(points.into_iter().collect()))
+    }
+
+    /// Create a closed polygon through a list of points.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - Corners as `(x, y)` coordinates.
+    pub fn polygon(points: impl IntoIterator<Item = (i32, i32)>) -> Self {
+        Self::new(Shape::Polygon(points.into_iter().collect()))
+    }
+
+    /// Create a point, such as a keypoint. Points are drawn as a disc with the thickness as radius.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position.
+    /// * `y` - Vertical position.
+    pub fn point(x: i32, y: i32) -> Self {
+        Self::new(Shape::Point { x, y })
+    }
+
+    /// Create a label on its own, with the top left corner of its box at the given position.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position of the left edge.
+    /// * `y` - Vertical position of the top edge.
+    /// * `text` - Label text.
+    pub fn label(x: i32, y: i32, text: impl Into<String>) -> Self {
+        Self::new(Shape::Label { x, y }).with_label(text)
+    }
+
+    /// Set the color of lines, points and the label box. Defaults to green.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - Color.
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the line thickness in pixels. Defaults to 2.
+    ///
+    /// # Arguments
+    ///
+    /// * `thickness` - Line thickness.
+    pub fn with_thickness(mut self, thickness: u32) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    /// Fill the inside of a rectangle or polygon. Use a color with alpha to keep what is behind
+    /// the shape visible.
+    ///
+    /// # Arguments
+    ///
+    /// * `fill` - Fill color.
+    pub fn with_fill(mut self, fill: Color) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    /// Attach a label to the shape.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Label text.
+    pub fn with_label(mut self, text: impl Into<String>) -> Self {
+        self.label = Some(text.into());
+        self
+    }
+
+    /// Set the font size of the label in pixels. The built-in font is 8 pixels high and is scaled
+    /// by whole numbers. Defaults to 8.
+    ///
+    /// # Arguments
+    ///
+    /// * `font_size` - Font size in pixels.
+    pub fn with_font_size(mut self, font_size: u32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// Draw the annotation onto a canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to draw onto.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        match &self.shape {
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => self.draw_rect(canvas, *x, *y, *width, *height),
+            Shape::Polyline(points) => {
+                self.draw_path(canvas, points.windows(2).map(|line| (line[0], line[1])));
+            }
+            Shape::Polygon(points) => {
+                if let Some(fill) = self.fill {
+                    canvas.fill_spans(polygon_spans(points, canvas.height()), fill);
+                }
+                let closing = points.last().zip(points.first());
+                self.draw_path(
+                    canvas,
+                    points
+                        .windows(2)
+                        .map(|line| (line[0], line[1]))
+                        .chain(closing.map(|(&last, &first)| (last, first))),
+                );
+            }
+            Shape::Point { x, y } => {
+                canvas.fill_spans(disc_spans(*x, *y, self.thickness), self.color);
+            }
+            Shape::Label { .. } => {}
+        }
+
+        if let Some(label) = &self.label {
+            self.draw_label(canvas, label);
+        }
+    }
+
+    /// Create an annotation with default style.
+    ///
+    /// # Arguments
+    ///
+    /// * `shape` - Geometry of the annotation.
+    fn new(shape: Shape) -> Self {
+        Self {
+            shape,
+            color: Color::GREEN,
+            thickness: 2,
+            fill: None,
+            label: None,
+            font_size: 8,
+        }
+    }
+
+    /// Draw a rectangle, with the outline on the inside.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to draw onto.
+    /// * `x` - Horizontal position of the left edge.
+    /// * `y` - Vertical position of the top edge.
+    /// * `width` - Width of rectangle.
+    /// * `height` - Height of rectangle.
+    fn draw_rect(&self, canvas: &mut Canvas, x: i32, y: i32, width: u32, height: u32) {
+        // The outline is drawn at once as spans that do not overlap, so that colors with alpha are
+        // blended only once, also where pixels share chroma.
+        let top = self.thickness.min(height);
+        let bottom = self.thickness.min(height - top);
+        let left = self.thickness.min(width);
+        let right = self.thickness.min(width - left);
+        let inner_width = width - left - right;
+        let inner_height = height - top - bottom;
+
+        if let Some(fill) = self.fill {
+            canvas.fill_rect(
+                x + left as i32,
+                y + top as i32,
+                inner_width,
+                inner_height,
+                fill,
+            );
+        }
+        let right_edge = x + width as i32;
+        let mut outline = Vec::new();
+        for row in y..y + height as i32 {
+            if row < y + top as i32 || row >= y + (height - bottom) as i32 {
+                outline.push((row, x, right_edge));
+            } else {
+                outline.push((row, x, x + left as i32));
+                outline.push((row, right_edge - right as i32, right_edge));
+            }
+        }
+        canvas.fill_spans(outline, self.color);
+    }
+
+    /// Draw a set of connected lines. Every pixel is blended once, also where lines meet.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to draw onto.
+    /// * `lines` - Lines as start and end points.
+    fn draw_path(
+        &self,
+        canvas: &mut Canvas,
+        lines: impl Iterator<Item = ((i32, i32), (i32, i32))>,
+    ) {
+        let mut pixels = Vec::new();
+        for (from, to) in lines {
+            line_pixels(from, to, self.thickness, &mut pixels);
+        }
+        pixels.sort_unstable_by_key(|&(x, y)| (y, x));
+        pixels.dedup();
+        canvas.blend_pixels(pixels.into_iter().map(|(x, y)| (x, y, self.color)));
+    }
+
+    /// Draw the label box with text in a color that contrasts with the box.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to draw onto.
+    /// * `text` - Label text.
+    fn draw_label(&self, canvas: &mut Canvas, text: &str) {
+        let scale = (self.font_size / 8).max(1);
+        let (text_width, text_height) = Canvas::text_size(text, scale);
+        let (box_width, box_height) = (
+            text_width + 2 * LABEL_PADDING,
+            text_height + 2 * LABEL_PADDING,
+        );
+        let (x, y) = match (&self.shape, self.label_anchor()) {
+            (Shape::Label { x, y }, _) => (*x, *y),
+            (_, Some((x, y))) if y >= box_height as i32 => (x, y - box_height as i32),
+            (_, Some((x, y))) => (x, y.max(0)),
+            (_, None) => return,
+        };
+
+        let text_color = if luminance(self.color) > 140 {
+            Color::BLACK
+        } else {
+            Color::WHITE
+        };
+        canvas.fill_rect(x, y, box_width, box_height, self.color);
+        canvas.draw_text(
+            x + LABEL_PADDING as i32,
+            y + LABEL_PADDING as i32,
+            text,
+            scale,
+            text_color,
+        );
+    }
+
+    /// Top left corner of the shape, which the label is placed above.
+    fn label_anchor(&self) -> Option<(i32, i32)> {
+        match &self.shape {
+            Shape::Rect { x, y, .. } => Some((*x, *y)),
+            Shape::Polyline(points) | Shape::Polygon(points) => {
+                let left = points.iter().map(|(x, _)| *x).min()?;
+                let top = points.iter().map(|(_, y)| *y).min()?;
+                Some((left, top))
+            }
+            Shape::Point { x, y } => {
+                let radius = self.thickness as i32;
+                Some((x - radius, y - radius))
+            }
+            Shape::Label { x, y } => Some((*x, *y)),
+        }
+    }
+}
+
+/// A set of annotations to draw onto frames, for example the detections for one frame.
+///
+/// # Example
+///
+/// ```ignore
+/// let annotations = detections
+///     .iter()
+///     .map(|detection| {
+///         Annotation::rect(detection.x, detection.y, detection.width, detection.height)
+///             .with_label(&detection.class)
+///     })
+///     .collect::<Annotations>();
+/// annotations.apply(&mut frame)?;
+/// encoder.encode_frame(frame)?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotations {
+    annotations: Vec<Annotation>,
+}
+
+impl Annotations {
+    /// Create an empty set of annotations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an annotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `annotation` - Annotation to add.
+    pub fn with(mut self, annotation: Annotation) -> Self {
+        self.push(annotation);
+        self
+    }
+
+    /// Add an annotation.
+    ///
+    /// # Arguments
+    ///
+    /// * `annotation` - Annotation to add.
+    pub fn push(&mut self, annotation: Annotation) {
+        self.annotations.push(annotation);
+    }
+
+    /// Remove all annotations, for example to reuse the set for the next frame.
+    pub fn clear(&mut self) {
+        self.annotations.clear();
+    }
+
+    /// Get the number of annotations.
+    pub fn len(&self) -> usize {
+        self.annotations.len()
+    }
+
+    /// Whether there are no annotations.
+    pub fn is_empty(&self) -> bool {
+        self.annotations.is_empty()
+    }
+
+    /// Draw the annotations onto a canvas, in the order in which they were added.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to draw onto.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        for annotation in &self.annotations {
+            annotation.draw(canvas);
+        }
+    }
+
+    /// Draw the annotations onto a [`VideoFrame`].
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to draw onto. See [`Canvas`] for the supported pixel formats.
+    pub fn apply(&self, frame: &mut VideoFrame) -> Result<()> {
+        self.draw(&mut Canvas::from_frame(frame)?);
+        Ok(())
+    }
+
+    /// Draw the annotations onto a raw frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to draw onto. See [`Canvas`] for the supported pixel formats.
+    pub fn apply_raw(&self, frame: &mut RawFrame) -> Result<()> {
+        self.draw(&mut Canvas::from_raw_frame(frame)?);
+        Ok(())
+    }
+
+    /// Draw the annotations onto an RGB24 `ndarray` frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame in `HWC` format and standard layout.
+    #[cfg(feature = "ndarray")]
+    pub fn apply_ndarray(&self, frame: &mut Frame) -> Result<()> {
+        self.draw(&mut Canvas::from_ndarray(frame)?);
+        Ok(())
+    }
+}
+
+impl FromIterator<Annotation> for Annotations {
+    fn from_iter<T: IntoIterator<Item = Annotation>>(iter: T) -> Self {
+        Self {
+            annotations: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Annotation> for Annotations {
+    fn extend<T: IntoIterator<Item = Annotation>>(&mut self, iter: T) {
+        self.annotations.extend(iter);
+    }
+}
+
+/// Compute the pixels of a thick line with Bresenham's algorithm. Thickness is added across the
+/// major axis of the line so that every pixel of the line is produced once.
+///
+/// # Arguments
+///
+/// * `from` - Start point.
+/// * `to` - End point.
+/// * `thickness` - Line thickness.
+/// * `pixels` - Output pixels.
+fn line_pixels(from: (i32, i32), to: (i32, i32), thickness: u32, pixels: &mut Vec<(i32, i32)>) {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - from.0).abs();
+    let dy = -(to.1 - from.1).abs();
+    let step_x = if from.0 < to.0 { 1 } else { -1 };
+    let step_y = if from.1 < to.1 { 1 } else { -1 };
+    let steep = -dy > dx;
+    let thickness = thickness.max(1) as i32;
+    let first_offset = -(thickness - 1) / 2;
+
+    let mut error = dx + dy;
+    loop {
+        for offset in first_offset..first_offset + thickness {
+            pixels.push(if steep {
+                (x + offset, y)
+            } else {
+                (x, y + offset)
+            });
+        }
+        if (x, y) == to {
+            break;
+        }
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Compute the horizontal spans of pixels in a disc.
+///
+/// # Arguments
+///
+/// * `x` - Horizontal position of the center.
+/// * `y` - Vertical position of the center.
+/// * `radius` - Radius of the disc.
+///
+/// # Return value
+///
+/// Spans as row, start column (inclusive) and end column (exclusive).
+fn disc_spans(x: i32, y: i32, radius: u32) -> Vec<(i32, i32, i32)> {
+    let radius = radius as i32;
+    (-radius..=radius)
+        .map(|row| {
+            let half_width = (((radius * radius - row * row) as f64).sqrt()) as i32;
+            (y + row, x - half_width, x + half_width + 1)
+        })
+        .collect()
+}
+
+/// Approximate perceived brightness of a color, from 0 to 255.
+///
+/// # Arguments
+///
+/// * `color` - Color.
+fn luminance(color: Color) -> u32 {
+    (299 * color.r as u32 + 587 * color.g as u32 + 114 * color.b as u32) / 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_thin_lines() {
+        let mut pixels = Vec::new();
+        line_pixels((0, 0), (4, 2), 1, &mut pixels);
+        assert_eq!(pixels, vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+        pixels.clear();
+        line_pixels((2, 3), (2, 0), 1, &mut pixels);
+        assert_eq!(pixels, vec![(2, 3), (2, 2), (2, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn thickens_lines_across_major_axis() {
+        let mut pixels = Vec::new();
+        line_pixels((0, 0), (2, 0), 3, &mut pixels);
+        assert_eq!(
+            pixels,
+            vec![
+                (0, -1),
+                (0, 0),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+                (2, -1),
+                (2, 0),
+                (2, 1)
+            ]
+        );
+        pixels.clear();
+        line_pixels((0, 0), (0, 1), 2, &mut pixels);
+        assert_eq!(pixels, vec![(0, 0), (1, 0), (0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn fills_discs() {
+        assert_eq!(disc_spans(5, 5, 0), vec![(5, 5, 6)]);
+        assert_eq!(disc_spans(5, 5, 1), vec![(4, 5, 6), (5, 4, 7), (6, 5, 6)]);
+    }
+
+    #[test]
+    fn anchors_labels_at_top_left() {
+        let polygon = Annotation::polygon([(10, 20), (30, 5), (40, 25)]);
+        assert_eq!(polygon.label_anchor(), Some((10, 5)));
+        assert_eq!(Annotation::polyline([]).label_anchor(), None);
+        assert_eq!(
+            Annotation::point(10, 10).with_thickness(3).label_anchor(),
+            Some((7, 7))
+        );
+        assert_eq!(
+            Annotation::label(10, 10, "car").label_anchor(),
+            Some((10, 10))
+        );
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn draws_rect_outlines_inside() {
+        let mut frame = Frame::zeros((6, 6, 3));
+        let annotations = Annotations::new().with(
+            Annotation::rect(1, 1, 4, 4)
+                .with_thickness(1)
+                .with_color(Color::RED),
+        );
+        annotations.apply_ndarray(&mut frame).unwrap();
+        assert_eq!(frame[[1, 1, 0]], 255);
+        assert_eq!(frame[[4, 4, 0]], 255);
+        assert_eq!(frame[[1, 4, 0]], 255);
+        assert_eq!(frame[[2, 2, 0]], 0);
+        assert_eq!(frame[[0, 0, 0]], 0);
+        assert_eq!(frame[[5, 5, 0]], 0);
+    }
+}
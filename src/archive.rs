@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+
+use crate::decode::Decoder;
+use crate::encode::{Encoder, EncoderSplit, Pass, Settings};
+use crate::error::Error;
+use crate::ffi;
+use crate::frame::{RawFrame, VideoFrame};
+use crate::location::Location;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Encode a video in two passes. The source is decoded twice: the first pass only analyzes the
+/// video and writes statistics to the stats file, the second pass uses those statistics to
+/// distribute the bitrate and writes the destination.
+///
+/// Use this together with [`RateControl::Abr`](crate::encode::RateControl::Abr) to hit a target
+/// file size as closely as possible.
+///
+/// # Arguments
+///
+/// * `source` - Video to encode.
+/// * `destination` - Where to encode to.
+/// * `settings` - Encoding settings.
+/// * `stats_path` - Path of the stats file that is shared between the passes. It is left in place
+///   after encoding.
+///
+/// # Example
+///
+/// ```ignore
+/// let settings = Settings::preset_h264_yuv420p(1280, 720, false)
+///     .with_rate_control(RateControl::Abr(2_000_000));
+/// encode_two_pass(
+///     Path::new("input.mp4"),
+///     Path::new("output.mp4"),
+///     settings,
+///     "/tmp/output.stats",
+/// )?;
+/// ```
+pub fn encode_two_pass(
+    source: impl Into<Location>,
+    destination: impl Into<Location>,
+    settings: Settings,
+    stats_path: impl Into<PathBuf>,
+) -> Result<()> {
+    let source = source.into();
+    let stats_path = stats_path.into();
+
+    // The first pass only produces statistics, so the encoded packets can be discarded.
+    let mut decoder = Decoder::new(source.clone())?;
+    let mut encoder = EncoderSplit::new(
+        settings
+            .clone()
+            .with_two_pass(Pass::First, stats_path.clone()),
+        true,
+    )?;
+    while let Some(frame) = decode_next_frame(&mut decoder)? {
+        encoder.encode_frame(frame)?;
+    }
+    encoder.drain()?;
+
+    let mut decoder = Decoder::new(source)?;
+    let mut encoder = Encoder::new(
+        destination,
+        settings.with_two_pass(Pass::Second, stats_path),
+    )?;
+    while let Some(frame) = decode_next_frame(&mut decoder)? {
+        encoder.encode_frame(frame)?;
+    }
+    encoder.finish()
+}
+
+/// Verify that a losslessly encoded video decodes bit-exactly back to the frames of the original.
+/// Both videos are decoded to RGB24, which is also the pixel format the encoder receives frames
+/// in by default.
+///
+/// This is meant for videos encoded with lossless settings such as
+/// [`Settings::preset_lossless_ffv1`] or [`Settings::preset_lossless_h264`].
+///
+/// # Arguments
+///
+/// * `original` - Original video.
+/// * `encoded` - Losslessly encoded video.
+///
+/// # Return value
+///
+/// `true` if both videos have the same number of frames and all frames are equal.
+pub fn verify_lossless(
+    original: impl Into<Location>,
+    encoded: impl Into<Location>,
+) -> Result<bool> {
+    let mut original = Decoder::new(original)?;
+    let mut encoded = Decoder::new(encoded)?;
+    loop {
+        match (original.decode_raw(), encoded.decode_raw()) {
+            (Ok(original_frame), Ok(encoded_frame)) => {
+                if !frames_equal(&original_frame, &encoded_frame)? {
+                    return Ok(false);
+                }
+            }
+            (Err(Error::DecodeExhausted), Err(Error::DecodeExhausted)) => return Ok(true),
+            (Err(Error::DecodeExhausted), Ok(_)) | (Ok(_), Err(Error::DecodeExhausted)) => {
+                return Ok(false)
+            }
+            (Err(err), _) | (_, Err(err)) => return Err(err),
+        }
+    }
+}
+
+/// Decode the next frame, or `None` if the decoder is exhausted.
+///
+/// # Arguments
+///
+/// * `decoder` - Decoder to decode from.
+fn decode_next_frame(decoder: &mut Decoder) -> Result<Option<VideoFrame>> {
+    match decoder.decode_frame() {
+        Ok(frame) => Ok(Some(frame)),
+        Err(Error::DecodeExhausted) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Compare the pixel data of two frames. Row padding is ignored.
+///
+/// # Arguments
+///
+/// * `a` - First frame.
+/// * `b` - Second frame.
+fn frames_equal(a: &RawFrame, b: &RawFrame) -> Result<bool> {
+    if a.format() != b.format() || a.width() != b.width() || a.height() != b.height() {
+        return Ok(false);
+    }
+
+    let row_sizes = ffi::image_plane_row_sizes(a.format(), a.width())?;
+    for (plane, row_size) in row_sizes.into_iter().enumerate().take(a.planes()) {
+        let rows_a = a.data(plane).chunks(a.stride(plane));
+        let rows_b = b.data(plane).chunks(b.stride(plane));
+        let rows = a.plane_height(plane) as usize;
+        if rows_a
+            .zip(rows_b)
+            .take(rows)
+            .any(|(row_a, row_b)| row_a[..row_size] != row_b[..row_size])
+        {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn verifies_lossless_copies() {
+        let (width, height) = testing::SIZE;
+        let original = testing::temp_path("verify-lossless-original.mkv");
+        let copy = testing::temp_path("verify-lossless-copy.mkv");
+        testing::write_video(&original, Settings::preset_lossless_ffv1(width, height), 5);
+
+        let mut decoder = Decoder::new(original.as_path()).unwrap();
+        let mut encoder = Encoder::new(
+            copy.as_path(),
+            Settings::preset_lossless_ffv1(width, height),
+        )
+        .unwrap();
+        while let Some(frame) = decode_next_frame(&mut decoder).unwrap() {
+            encoder.encode_frame(frame).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let verified = verify_lossless(original.as_path(), copy.as_path());
+        std::fs::remove_file(&original).unwrap();
+        std::fs::remove_file(&copy).unwrap();
+        assert!(matches!(verified, Ok(true)));
+    }
+}
@@ -0,0 +1,827 @@
+use crate::error::Error;
+use crate::ffi;
+use crate::font;
+#[cfg(feature = "ndarray")]
+use crate::frame::Frame;
+use crate::frame::{ColorRange, ColorSpace, PixelFormat, RawFrame, VideoFrame};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// An RGB color with alpha. An alpha of 255 is opaque, an alpha of 0 is fully transparent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const RED: Color = Color::rgb(255, 0, 0);
+    pub const GREEN: Color = Color::rgb(0, 255, 0);
+    pub const BLUE: Color = Color::rgb(0, 0, 255);
+    pub const YELLOW: Color = Color::rgb(255, 255, 0);
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+
+    /// Create an opaque color.
+    ///
+    /// # Arguments
+    ///
+    /// * `r` - Red component.
+    /// * `g` - Green component.
+    /// * `b` - Blue component.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    /// Create a color with alpha.
+    ///
+    /// # Arguments
+    ///
+    /// * `r` - Red component.
+    /// * `g` - Green component.
+    /// * `b` - Blue component.
+    /// * `a` - Alpha component.
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Get the same color with another alpha.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - Alpha component.
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self { a, ..self }
+    }
+
+    /// Convert the color to Y, Cb and Cr components.
+    ///
+    /// # Arguments
+    ///
+    /// * `matrix` - Conversion matrix.
+    /// * `full_range` - Whether to use the full range of 0 to 255 instead of the limited (video)
+    ///   range of 16 to 235 for luma and 16 to 240 for chroma.
+    fn to_yuv(self, matrix: YuvMatrix, full_range: bool) -> (u8, u8, u8) {
+        let (kr, kb) = match matrix {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+        };
+        let (r, g, b) = (self.r as f32, self.g as f32, self.b as f32);
+        let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+        let cb = (b - y) / (2.0 * (1.0 - kb));
+        let cr = (r - y) / (2.0 * (1.0 - kr));
+        let (y, cb, cr) = if full_range {
+            (y, 128.0 + cb, 128.0 + cr)
+        } else {
+            (
+                16.0 + y * 219.0 / 255.0,
+                128.0 + cb * 224.0 / 255.0,
+                128.0 + cr * 224.0 / 255.0,
+            )
+        };
+        (
+            y.round().clamp(0.0, 255.0) as u8,
+            cb.round().clamp(0.0, 255.0) as u8,
+            cr.round().clamp(0.0, 255.0) as u8,
+        )
+    }
+}
+
+/// Matrix for converting RGB to YUV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum YuvMatrix {
+    Bt601,
+    Bt709,
+}
+
+/// Memory layout of the pixels of a canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// Packed RGB with the byte offsets of the red, green and blue components in a pixel. Any other
+    /// component, such as alpha, is left alone.
+    Packed {
+        bytes_per_pixel: usize,
+        offsets: [usize; 3],
+    },
+    /// A single luma plane.
+    Gray,
+    /// A luma plane and either two chroma planes or one plane with interleaved chroma, with the
+    /// chroma planes subsampled by `chroma_shift` (as a power of two).
+    Yuv {
+        chroma_shift: (u32, u32),
+        interleaved_chroma: bool,
+        matrix: YuvMatrix,
+        full_range: bool,
+    },
+}
+
+/// Position of a single color component in the planes of a canvas, such as the red component of
+/// packed RGB or the blue-difference chroma of NV12.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Component {
+    /// Index of the plane that holds the component.
+    pub plane: usize,
+    /// Byte offset of the component in a pixel.
+    pub offset: usize,
+    /// Number of bytes from one pixel to the next.
+    pub step: usize,
+    /// Horizontal and vertical subsampling of the component (as a power of two).
+    pub shift: (u32, u32),
+}
+
+/// Something to draw on: a frame in one of the common 8-bit RGB, grayscale and YUV pixel formats,
+/// or an RGB24 `ndarray` frame. Everything that draws onto frames, such as the
+/// [`Overlay`](crate::overlay::Overlay), draws onto a canvas.
+///
+/// Colors are blended with the pixels that are already there according to their alpha. For
+/// subsampled YUV formats, the pixels that share chroma are blended with that chroma once, in
+/// proportion to how much of their block is drawn.
+///
+/// Supported pixel formats are RGB24, BGR24, RGBA, BGRA, ARGB, ABGR, GRAY8, YUV420P, YUV422P,
+/// YUV444P (and their full range YUVJ variants) and NV12.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut frame = decoder.decode_frame()?;
+/// let mut canvas = Canvas::from_frame(&mut frame)?;
+/// canvas.fill_rect(10, 10, 100, 20, Color::BLACK.with_alpha(128));
+/// canvas.draw_text(14, 14, "CAM 1", 1, Color::WHITE);
+/// ```
+pub struct Canvas<'a> {
+    planes: Vec<(&'a mut [u8], usize)>,
+    layout: Layout,
+    width: u32,
+    height: u32,
+}
+
+impl<'a> Canvas<'a> {
+    /// Create a canvas that draws onto a [`VideoFrame`].
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to draw onto.
+    pub fn from_frame(frame: &'a mut VideoFrame) -> Result<Self> {
+        Self::from_raw_frame(frame.as_raw_mut())
+    }
+
+    /// Create a canvas that draws onto a raw frame. If the frame data is shared with another
+    /// frame, it is copied first so that drawing does not affect the other frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to draw onto.
+    pub fn from_raw_frame(frame: &'a mut RawFrame) -> Result<Self> {
+        let full_range = frame.color_range() == ColorRange::JPEG;
+        let matrix = if frame.color_space() == ColorSpace::BT709 {
+            YuvMatrix::Bt709
+        } else {
+            YuvMatrix::Bt601
+        };
+        let layout = Self::layout_of(frame.format(), matrix, full_range)?;
+        let (width, height) = (frame.width(), frame.height());
+        let planes = ffi::frame_planes_mut(frame)?;
+
+        Ok(Self {
+            planes,
+            layout,
+            width,
+            height,
+        })
+    }
+
+    /// Create a canvas that draws onto an RGB24 `ndarray` frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame in `HWC` format and standard layout.
+    #[cfg(feature = "ndarray")]
+    pub fn from_ndarray(frame: &'a mut Frame) -> Result<Self> {
+        let (height, width, channels) = frame.dim();
+        if channels != 3 {
+            return Err(Error::InvalidFrameFormat);
+        }
+        let data = frame.as_slice_mut().ok_or(Error::InvalidFrameFormat)?;
+
+        Ok(Self {
+            planes: vec![(data, width * 3)],
+            layout: Layout::Packed {
+                bytes_per_pixel: 3,
+                offsets: [0, 1, 2],
+            },
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+
+    /// Get canvas width.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get canvas height.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get canvas size: width and height.
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Blend a single pixel. Pixels outside the canvas are ignored.
+    ///
+    /// For subsampled YUV formats, the chroma that the pixel shares with its neighbours is blended
+    /// in proportion to the part of the chroma block that the pixel covers. To draw shapes, use
+    /// [`Canvas::fill_rect`] and the other methods that draw many pixels at once, so that chroma
+    /// is blended once for every chroma block.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position.
+    /// * `y` - Vertical position.
+    /// * `color` - Color to blend.
+    pub fn blend_pixel(&mut self, x: i32, y: i32, color: Color) {
+        self.blend_pixels([(x, y, color)]);
+    }
+
+    /// Fill a rectangle. The parts of the rectangle that lie outside the canvas are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position of the left edge.
+    /// * `y` - Vertical position of the top edge.
+    /// * `width` - Width of rectangle.
+    /// * `height` - Height of rectangle.
+    /// * `color` - Fill color.
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Color) {
+        let (left, right) = self.clip(x, width, self.width);
+        let (top, bottom) = self.clip(y, height, self.height);
+        self.fill_spans((top..bottom).map(|row| (row, left, right)), color);
+    }
+
+    /// Draw text with the built-in 8x8 pixel font. Only printable ASCII characters are in the font,
+    /// other characters are drawn as `?`. Lines are separated by `\n`.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position of the left edge of the text.
+    /// * `y` - Vertical position of the top edge of the text.
+    /// * `text` - Text to draw.
+    /// * `scale` - Scale of the font. At scale 1, characters are 8 by 8 pixels.
+    /// * `color` - Text color.
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, scale: u32, color: Color) {
+        let scale = scale.max(1);
+        let glyph_size = (font::GLYPH_SIZE * scale) as i32;
+        // The text is drawn row by row across all glyphs of a line, so that glyphs that share
+        // chroma with each other are blended together.
+        let mut spans = Vec::new();
+        for (line_index, line) in text.split('\n').enumerate() {
+            let glyphs = line.chars().map(font::glyph).collect::<Vec<_>>();
+            for offset in 0..glyph_size {
+                let row = y + line_index as i32 * glyph_size + offset;
+                let glyph_y = offset as u32 / scale;
+                for (column, glyph) in glyphs.iter().enumerate() {
+                    for glyph_x in 0..font::GLYPH_SIZE {
+                        if font::glyph_pixel(glyph, glyph_x, glyph_y) {
+                            let start = x + column as i32 * glyph_size + (glyph_x * scale) as i32;
+                            spans.push((row, start, start + scale as i32));
+                        }
+                    }
+                }
+            }
+        }
+        self.fill_spans(spans, color);
+    }
+
+    /// Get the size of text drawn with [`Canvas::draw_text`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to measure.
+    /// * `scale` - Scale of the font.
+    pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+        let (width, height) = font::text_size(text);
+        (width * scale.max(1), height * scale.max(1))
+    }
+
+    /// Draw an RGBA image, scaled to the given size with nearest neighbour sampling. The alpha of
+    /// the image is used for blending.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position of the left edge.
+    /// * `y` - Vertical position of the top edge.
+    /// * `image_size` - Width and height of the image.
+    /// * `image` - Image pixels in RGBA order, row by row without padding.
+    /// * `size` - Width and height to draw the image at.
+    pub fn draw_rgba(
+        &mut self,
+        x: i32,
+        y: i32,
+        image_size: (u32, u32),
+        image: &[u8],
+        size: (u32, u32),
+    ) -> Result<()> {
+        let (image_width, image_height) = image_size;
+        if image.len() < image_width as usize * image_height as usize * 4 {
+            return Err(Error::InvalidFrameFormat);
+        }
+        let (width, height) = size;
+        if image_width == 0 || image_height == 0 || width == 0 || height == 0 {
+            return Ok(());
+        }
+        let (left, right) = self.clip(x, width, self.width);
+        let (top, bottom) = self.clip(y, height, self.height);
+        let pixels = (top..bottom).flat_map(|target_y| {
+            let image_y = ((target_y - y) as u64 * image_height as u64 / height as u64) as usize;
+            (left..right).map(move |target_x| {
+                let image_x = ((target_x - x) as u64 * image_width as u64 / width as u64) as usize;
+                let offset = (image_y * image_width as usize + image_x) * 4;
+                let pixel = &image[offset..offset + 4];
+                (
+                    target_x,
+                    target_y,
+                    Color::rgba(pixel[0], pixel[1], pixel[2], pixel[3]),
+                )
+            })
+        });
+        self.blend_pixels(pixels);
+
+        Ok(())
+    }
+
+    /// Get the color components of the canvas. Filters that need to read pixels, such as blurring,
+    /// work on each component separately.
+    pub(crate) fn components(&self) -> Vec<Component> {
+        let component = |plane, offset, step, shift| Component {
+            plane,
+            offset,
+            step,
+            shift,
+        };
+        match self.layout {
+            Layout::Packed {
+                bytes_per_pixel,
+                offsets,
+            } => offsets
+                .into_iter()
+                .map(|offset| component(0, offset, bytes_per_pixel, (0, 0)))
+                .collect(),
+            Layout::Gray => vec![component(0, 0, 1, (0, 0))],
+            Layout::Yuv {
+                chroma_shift,
+                interleaved_chroma: true,
+                ..
+            } => vec![
+                component(0, 0, 1, (0, 0)),
+                component(1, 0, 2, chroma_shift),
+                component(1, 1, 2, chroma_shift),
+            ],
+            Layout::Yuv { chroma_shift, .. } => vec![
+                component(0, 0, 1, (0, 0)),
+                component(1, 0, 1, chroma_shift),
+                component(2, 0, 1, chroma_shift),
+            ],
+        }
+    }
+
+    /// Get the data and stride of a plane.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of plane, see [`Component::plane`].
+    pub(crate) fn plane_mut(&mut self, index: usize) -> (&mut [u8], usize) {
+        let (data, stride) = &mut self.planes[index];
+        (data, *stride)
+    }
+
+    /// Fill horizontal spans of pixels, such as the spans that make up a polygon. The parts of the
+    /// spans that lie outside the canvas are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `spans` - Spans as row, start (inclusive) and end (exclusive), in order of their rows.
+    /// * `color` - Fill color.
+    pub(crate) fn fill_spans(
+        &mut self,
+        spans: impl IntoIterator<Item = (i32, i32, i32)>,
+        color: Color,
+    ) {
+        let width = self.width as i32;
+        let pixels = spans.into_iter().flat_map(move |(row, start, end)| {
+            (start.max(0)..end.min(width)).map(move |x| (x, row, color))
+        });
+        self.blend_pixels(pixels);
+    }
+
+    /// Blend pixels. Pixels outside the canvas are ignored.
+    ///
+    /// For subsampled YUV formats, the chroma of the pixels that share a chroma sample is
+    /// collected first and blended once, with the alpha weighted by how much of the chroma block
+    /// the pixels cover. A block that is drawn in full thus gets the same chroma as it would
+    /// without subsampling. Pixels should be given in order of their rows, as chroma is collected
+    /// for one row of chroma samples at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixels` - Positions and colors of the pixels.
+    pub(crate) fn blend_pixels(&mut self, pixels: impl IntoIterator<Item = (i32, i32, Color)>) {
+        let mut chroma = ChromaRow::new(self.layout, self.width);
+        for (x, y, color) in pixels {
+            if color.a == 0 || x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+                continue;
+            }
+            let (x, y) = (x as usize, y as usize);
+            let alpha = color.a;
+
+            match self.layout {
+                Layout::Packed {
+                    bytes_per_pixel,
+                    offsets,
+                } => {
+                    let (data, stride) = &mut self.planes[0];
+                    let offset = y * *stride + x * bytes_per_pixel;
+                    for (component, value) in offsets.into_iter().zip([color.r, color.g, color.b]) {
+                        blend(&mut data[offset + component], value, alpha);
+                    }
+                }
+                Layout::Gray => {
+                    let (luma, _, _) = color.to_yuv(YuvMatrix::Bt601, true);
+                    let (data, stride) = &mut self.planes[0];
+                    blend(&mut data[y * *stride + x], luma, alpha);
+                }
+                Layout::Yuv {
+                    chroma_shift: (shift_x, shift_y),
+                    matrix,
+                    full_range,
+                    ..
+                } => {
+                    let (luma, cb, cr) = color.to_yuv(matrix, full_range);
+                    let (data, stride) = &mut self.planes[0];
+                    blend(&mut data[y * *stride + x], luma, alpha);
+
+                    if (shift_x, shift_y) == (0, 0) {
+                        self.blend_chroma(x, y, cb, cr, alpha);
+                    } else {
+                        let chroma_y = y >> shift_y;
+                        if chroma.row != Some(chroma_y) {
+                            self.flush_chroma(&mut chroma);
+                            chroma.row = Some(chroma_y);
+                        }
+                        chroma.add(x >> shift_x, cb, cr, alpha);
+                    }
+                }
+            }
+        }
+        self.flush_chroma(&mut chroma);
+    }
+
+    /// Blend the chroma collected for a row of chroma samples, and clear it.
+    ///
+    /// # Arguments
+    ///
+    /// * `chroma` - Collected chroma.
+    fn flush_chroma(&mut self, chroma: &mut ChromaRow) {
+        let Layout::Yuv {
+            chroma_shift: (shift_x, shift_y),
+            ..
+        } = self.layout
+        else {
+            return;
+        };
+        let Some(chroma_y) = chroma.row.take() else {
+            return;
+        };
+        // Blocks at the right and bottom edges of frames with odd sizes hold fewer pixels.
+        let block_height = (1 << shift_y).min(self.height as usize - (chroma_y << shift_y));
+        let (start, end) = std::mem::replace(&mut chroma.dirty, (usize::MAX, 0));
+        for chroma_x in start..end {
+            let [alpha, cb, cr] = std::mem::take(&mut chroma.samples[chroma_x]);
+            if alpha == 0 {
+                continue;
+            }
+            let block_width = (1 << shift_x).min(self.width as usize - (chroma_x << shift_x));
+            let block_size = (block_width * block_height) as u32;
+            let coverage = ((alpha + block_size / 2) / block_size).min(255) as u8;
+            let average = |sum: u32| ((sum + alpha / 2) / alpha) as u8;
+            self.blend_chroma(chroma_x, chroma_y, average(cb), average(cr), coverage);
+        }
+    }
+
+    /// Blend a single chroma sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `chroma_x` - Horizontal position in the chroma plane.
+    /// * `chroma_y` - Vertical position in the chroma plane.
+    /// * `cb` - Blue-difference chroma of the color.
+    /// * `cr` - Red-difference chroma of the color.
+    /// * `alpha` - Alpha to blend with.
+    fn blend_chroma(&mut self, chroma_x: usize, chroma_y: usize, cb: u8, cr: u8, alpha: u8) {
+        if alpha == 0 {
+            return;
+        }
+        if matches!(
+            self.layout,
+            Layout::Yuv {
+                interleaved_chroma: true,
+                ..
+            }
+        ) {
+            let (data, stride) = &mut self.planes[1];
+            let offset = chroma_y * *stride + chroma_x * 2;
+            blend(&mut data[offset], cb, alpha);
+            blend(&mut data[offset + 1], cr, alpha);
+        } else {
+            for (plane, value) in [(1, cb), (2, cr)] {
+                let (data, stride) = &mut self.planes[plane];
+                blend(&mut data[chroma_y * *stride + chroma_x], value, alpha);
+            }
+        }
+    }
+
+    /// Clip a span to the canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Start of span.
+    /// * `length` - Length of span.
+    /// * `limit` - Size of the canvas in the direction of the span.
+    ///
+    /// # Return value
+    ///
+    /// Start (inclusive) and end (exclusive) of the part of the span that lies on the canvas.
+    fn clip(&self, start: i32, length: u32, limit: u32) -> (i32, i32) {
+        let end = (start as i64 + length as i64).min(limit as i64);
+        (start.max(0), end.max(0) as i32)
+    }
+
+    /// Determine the memory layout for a pixel format.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Pixel format.
+    /// * `matrix` - Conversion matrix for YUV formats.
+    /// * `full_range` - Whether YUV formats use the full range.
+    fn layout_of(format: PixelFormat, matrix: YuvMatrix, full_range: bool) -> Result<Layout> {
+        let packed = |bytes_per_pixel, offsets| Layout::Packed {
+            bytes_per_pixel,
+            offsets,
+        };
+        let yuv = |chroma_shift, interleaved_chroma, full_range| Layout::Yuv {
+            chroma_shift,
+            interleaved_chroma,
+            matrix,
+            full_range,
+        };
+        Ok(match format {
+            PixelFormat::RGB24 => packed(3, [0, 1, 2]),
+            PixelFormat::BGR24 => packed(3, [2, 1, 0]),
+            PixelFormat::RGBA => packed(4, [0, 1, 2]),
+            PixelFormat::BGRA => packed(4, [2, 1, 0]),
+            PixelFormat::ARGB => packed(4, [1, 2, 3]),
+            PixelFormat::ABGR => packed(4, [3, 2, 1]),
+            PixelFormat::GRAY8 => Layout::Gray,
+            PixelFormat::YUV420P => yuv((1, 1), false, full_range),
+            PixelFormat::YUV422P => yuv((1, 0), false, full_range),
+            PixelFormat::YUV444P => yuv((0, 0), false, full_range),
+            PixelFormat::YUVJ420P => yuv((1, 1), false, true),
+            PixelFormat::YUVJ422P => yuv((1, 0), false, true),
+            PixelFormat::YUVJ444P => yuv((0, 0), false, true),
+            PixelFormat::NV12 => yuv((1, 1), true, full_range),
+            _ => return Err(Error::InvalidFrameFormat),
+        })
+    }
+}
+
+/// Chroma of the pixels drawn in one row of chroma samples of a subsampled YUV canvas, collected so
+/// that every chroma sample is blended only once.
+struct ChromaRow {
+    /// Row of chroma samples that is being collected.
+    row: Option<usize>,
+    /// Sum of the alpha, and of the Cb and Cr components weighted by alpha, of the pixels drawn
+    /// for each chroma sample in the row.
+    samples: Vec<[u32; 3]>,
+    /// Start (inclusive) and end (exclusive) of the chroma samples that pixels were drawn for.
+    dirty: (usize, usize),
+}
+
+impl ChromaRow {
+    /// Create an empty row for a canvas. The row holds no samples if the canvas does not have
+    /// subsampled chroma.
+    ///
+    /// # Arguments
+    ///
+    /// * `layout` - Layout of the canvas.
+    /// * `width` - Width of the canvas.
+    fn new(layout: Layout, width: u32) -> Self {
+        let samples = match layout {
+            Layout::Yuv {
+                chroma_shift: (shift_x, _),
+                ..
+            } => (width as usize + (1 << shift_x) - 1) >> shift_x,
+            _ => 0,
+        };
+        Self {
+            row: None,
+            samples: vec![[0; 3]; samples],
+            dirty: (usize::MAX, 0),
+        }
+    }
+
+    /// Add the chroma of a pixel.
+    ///
+    /// # Arguments
+    ///
+    /// * `chroma_x` - Horizontal position of the chroma sample of the pixel.
+    /// * `cb` - Blue-difference chroma of the pixel color.
+    /// * `cr` - Red-difference chroma of the pixel color.
+    /// * `alpha` - Alpha of the pixel color.
+    fn add(&mut self, chroma_x: usize, cb: u8, cr: u8, alpha: u8) {
+        let alpha = alpha as u32;
+        let sample = &mut self.samples[chroma_x];
+        sample[0] += alpha;
+        sample[1] += cb as u32 * alpha;
+        sample[2] += cr as u32 * alpha;
+        self.dirty = (self.dirty.0.min(chroma_x), self.dirty.1.max(chroma_x + 1));
+    }
+}
+
+/// Compute the horizontal spans of pixels inside a polygon, using the even-odd rule. A pixel is
+/// inside if its center is.
+///
+/// # Arguments
+///
+/// * `points` - Corners of the polygon.
+/// * `height` - Height of the canvas. Rows outside the canvas are skipped.
+///
+/// # Return value
+///
+/// Spans as row, start column (inclusive) and end column (exclusive).
+pub(crate) fn polygon_spans(points: &[(i32, i32)], height: u32) -> Vec<(i32, i32, i32)> {
+    let mut spans = Vec::new();
+    let (Some(top), Some(bottom)) = (
+        points.iter().map(|(_, y)| *y).min(),
+        points.iter().map(|(_, y)| *y).max(),
+    ) else {
+        return spans;
+    };
+
+    let mut crossings = Vec::new();
+    for row in top.max(0)..bottom.min(height as i32) {
+        let center = row as f64 + 0.5;
+        crossings.clear();
+        for (index, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(index + 1) % points.len()];
+            let (y0, y1) = (y0 as f64, y1 as f64);
+            if (y0 <= center) != (y1 <= center) {
+                crossings.push(x0 as f64 + (center - y0) * (x1 - x0) as f64 / (y1 - y0));
+            }
+        }
+        crossings.sort_by(f64::total_cmp);
+        for pair in crossings.chunks_exact(2) {
+            let start = (pair[0] - 0.5).ceil() as i32;
+            let end = (pair[1] - 0.5).ceil() as i32;
+            if start < end {
+                spans.push((row, start, end));
+            }
+        }
+    }
+    spans
+}
+
+/// Blend a color component onto a pixel component.
+///
+/// # Arguments
+///
+/// * `destination` - Pixel component to blend onto.
+/// * `value` - Color component.
+/// * `alpha` - Alpha of the color.
+#[inline]
+fn blend(destination: &mut u8, value: u8, alpha: u8) {
+    let alpha = alpha as u32;
+    *destination = ((value as u32 * alpha + *destination as u32 * (255 - alpha) + 127) / 255) as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_colors_to_limited_range_yuv() {
+        assert_eq!(
+            Color::WHITE.to_yuv(YuvMatrix::Bt601, false),
+            (235, 128, 128)
+        );
+        assert_eq!(Color::BLACK.to_yuv(YuvMatrix::Bt601, false), (16, 128, 128));
+        assert_eq!(Color::RED.to_yuv(YuvMatrix::Bt601, false), (81, 90, 240));
+        assert_eq!(Color::RED.to_yuv(YuvMatrix::Bt709, false), (63, 102, 240));
+    }
+
+    #[test]
+    fn converts_colors_to_full_range_yuv() {
+        assert_eq!(Color::WHITE.to_yuv(YuvMatrix::Bt601, true), (255, 128, 128));
+        assert_eq!(Color::BLACK.to_yuv(YuvMatrix::Bt601, true), (0, 128, 128));
+    }
+
+    #[test]
+    fn blends_by_alpha() {
+        let mut value = 0;
+        blend(&mut value, 255, 255);
+        assert_eq!(value, 255);
+        blend(&mut value, 0, 0);
+        assert_eq!(value, 255);
+        blend(&mut value, 0, 128);
+        assert_eq!(value, 127);
+    }
+
+    #[test]
+    fn blends_subsampled_chroma_once_per_sample() {
+        let (mut luma, mut cb, mut cr) = ([100; 8], [200; 2], [50; 2]);
+        let mut canvas = Canvas {
+            planes: vec![(&mut luma[..], 4), (&mut cb[..], 2), (&mut cr[..], 2)],
+            layout: Layout::Yuv {
+                chroma_shift: (1, 1),
+                interleaved_chroma: false,
+                matrix: YuvMatrix::Bt601,
+                full_range: false,
+            },
+            width: 4,
+            height: 2,
+        };
+        canvas.fill_rect(0, 0, 4, 2, Color::BLACK.with_alpha(160));
+        // Half of the first chroma block is covered, so its chroma is blended at half the alpha.
+        canvas.fill_rect(0, 0, 1, 2, Color::BLACK);
+
+        let blended = |mut value, color, alpha| {
+            blend(&mut value, color, alpha);
+            value
+        };
+        assert_eq!(luma[1], blended(100, 16, 160));
+        assert_eq!(luma[4], 16);
+        assert_eq!(
+            cb,
+            [
+                blended(blended(200, 128, 160), 128, 128),
+                blended(200, 128, 160)
+            ]
+        );
+        assert_eq!(
+            cr,
+            [
+                blended(blended(50, 128, 160), 128, 128),
+                blended(50, 128, 160)
+            ]
+        );
+    }
+
+    #[test]
+    fn fills_polygons() {
+        let square = [(1, 1), (4, 1), (4, 3), (1, 3)];
+        assert_eq!(polygon_spans(&square, 10), vec![(1, 1, 4), (2, 1, 4)]);
+        assert_eq!(polygon_spans(&square, 2), vec![(1, 1, 4)]);
+
+        let triangle = [(0, 0), (4, 4), (0, 4)];
+        assert_eq!(
+            polygon_spans(&triangle, 10),
+            vec![(1, 0, 1), (2, 0, 2), (3, 0, 3)]
+        );
+        assert!(polygon_spans(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn measures_text() {
+        assert_eq!(Canvas::text_size("CAM 1", 1), (40, 8));
+        assert_eq!(Canvas::text_size("a\nlonger line", 2), (176, 32));
+        assert_eq!(Canvas::text_size("", 3), (0, 24));
+    }
+
+    #[test]
+    fn unknown_characters_use_replacement_glyph() {
+        assert_eq!(font::glyph('é'), font::glyph('?'));
+        assert_ne!(font::glyph('A'), font::glyph('?'));
+        assert!(font::glyph_pixel(font::glyph('_'), 0, 7));
+        assert!(!font::glyph_pixel(font::glyph(' '), 3, 3));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn draws_on_ndarray_frames() {
+        let mut frame = Frame::zeros((4, 4, 3));
+        let mut canvas = Canvas::from_ndarray(&mut frame).unwrap();
+        canvas.fill_rect(-1, 1, 2, 2, Color::RED);
+        canvas.blend_pixel(10, 10, Color::WHITE);
+        assert_eq!(frame[[1, 0, 0]], 255);
+        assert_eq!(frame[[2, 0, 0]], 255);
+        assert_eq!(frame[[1, 1, 0]], 0);
+        assert_eq!(frame[[0, 0, 0]], 0);
+        assert_eq!(frame[[1, 0, 1]], 0);
+    }
+}
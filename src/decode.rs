@@ -1,5 +1,8 @@
 extern crate ffmpeg_next as ffmpeg;
 
+#[cfg(feature = "ndarray")]
+use ndarray::{ArrayView3, ArrayViewMut3};
+
 use ffmpeg::codec::decoder::Video as AvDecoder;
 use ffmpeg::codec::Context as AvContext;
 use ffmpeg::format::pixel::Pixel as AvPixel;
@@ -10,15 +13,19 @@ use ffmpeg::{Error as AvError, Rational as AvRational};
 use crate::error::Error;
 use crate::ffi;
 use crate::ffi_hwaccel;
+#[cfg(feature = "filter")]
+use crate::filter::{BufferSource, Deinterlace, FilterGraph};
 #[cfg(feature = "ndarray")]
 use crate::frame::Frame;
-use crate::frame::{RawFrame, FRAME_PIXEL_FORMAT};
+use crate::frame::{FramePool, PixelFormat, RawFrame, VideoFrame, FRAME_PIXEL_FORMAT};
 use crate::hwaccel::{HardwareAccelerationContext, HardwareAccelerationDeviceType};
 use crate::io::{Reader, ReaderBuilder};
 use crate::location::Location;
 use crate::options::Options;
 use crate::packet::Packet;
 use crate::resize::Resize;
+#[cfg(feature = "ndarray")]
+use crate::tensor::{convert_frame_to_tensor, FrameBatch, TensorFrame, TensorSettings};
 use crate::time::Time;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -32,6 +39,12 @@ pub struct DecoderBuilder<'a> {
     options: Option<&'a Options>,
     resize: Option<Resize>,
     hardware_acceleration_device_type: Option<HardwareAccelerationDeviceType>,
+    hardware_acceleration_fallback: Vec<HardwareAccelerationDeviceType>,
+    pixel_format: Option<PixelFormat>,
+    #[cfg(feature = "filter")]
+    filter: Option<String>,
+    #[cfg(feature = "filter")]
+    deinterlace: Option<Deinterlace>,
 }
 
 impl<'a> DecoderBuilder<'a> {
@@ -44,6 +57,12 @@ impl<'a> DecoderBuilder<'a> {
             options: None,
             resize: None,
             hardware_acceleration_device_type: None,
+            hardware_acceleration_fallback: Vec::new(),
+            pixel_format: None,
+            #[cfg(feature = "filter")]
+            filter: None,
+            #[cfg(feature = "filter")]
+            deinterlace: None,
         }
     }
 
@@ -74,6 +93,72 @@ impl<'a> DecoderBuilder<'a> {
         self
     }
 
+    /// Try hardware acceleration with each of the specified device types in order, and fall back
+    /// to software decoding if none of them can be used for the source, for example because there
+    /// is no GPU. Unlike [`DecoderBuilder::with_hardware_acceleration`], building the decoder does
+    /// not fail if hardware acceleration is not possible. Use
+    /// [`Decoder::is_hardware_accelerated`] to find out which device type ended up being used.
+    ///
+    /// * `device_types` - Devices to try for hardware acceleration, in order of preference.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let decoder = DecoderBuilder::new(Path::new("video.mp4"))
+    ///     .with_hardware_acceleration_fallback([
+    ///         HardwareAccelerationDeviceType::Cuda,
+    ///         HardwareAccelerationDeviceType::VaApi,
+    ///     ])
+    ///     .build()?;
+    /// println!("hardware accelerated: {}", decoder.is_hardware_accelerated());
+    /// ```
+    pub fn with_hardware_acceleration_fallback(
+        mut self,
+        device_types: impl IntoIterator<Item = HardwareAccelerationDeviceType>,
+    ) -> Self {
+        self.hardware_acceleration_fallback = device_types.into_iter().collect();
+        self
+    }
+
+    /// Set the pixel format of decoded frames. By default, frames are converted to RGB24.
+    ///
+    /// Use a planar YUV format such as YUV420P to skip the conversion to RGB when the source is
+    /// already in that format, or GRAY8 to only get the luma plane. Note that the `ndarray`-based
+    /// decode functions (such as [`Decoder::decode`]) require RGB24 and will return
+    /// [`Error::InvalidFrameFormat`] otherwise. Use [`Decoder::decode_frame`] and
+    /// [`VideoFrame::plane_view`] to access planar frames.
+    ///
+    /// * `pixel_format` - Pixel format of decoded frames.
+    pub fn with_pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+        self.pixel_format = Some(pixel_format);
+        self
+    }
+
+    /// Apply an ffmpeg filter graph to decoded frames, such as `yadif,hqdn3d`. See
+    /// [`FilterGraph`]. Filters run before resizing and pixel format conversion, so
+    /// [`DecoderBuilder::with_resize`] and [`DecoderBuilder::with_pixel_format`] apply to the
+    /// filtered frames.
+    ///
+    /// * `description` - Filter description, in the same syntax as the `-vf` argument of the
+    ///   ffmpeg command line tool.
+    #[cfg(feature = "filter")]
+    pub fn with_filter(mut self, description: &str) -> Self {
+        self.filter = Some(description.to_string());
+        self
+    }
+
+    /// Deinterlace frames that are flagged as interlaced. Deinterlacing runs before the filter
+    /// graph set with [`DecoderBuilder::with_filter`]. Note that with
+    /// [`DeinterlaceMode::Bob`](crate::filter::DeinterlaceMode::Bob), interlaced frames produce
+    /// two frames each, with timestamps at the field rate.
+    ///
+    /// * `deinterlace` - Deinterlacing settings.
+    #[cfg(feature = "filter")]
+    pub fn with_deinterlace(mut self, deinterlace: Deinterlace) -> Self {
+        self.deinterlace = Some(deinterlace);
+        self
+    }
+
     /// Build [`Decoder`].
     pub fn build(self) -> Result<Decoder> {
         let mut reader_builder = ReaderBuilder::new(self.source);
@@ -82,16 +167,22 @@ impl<'a> DecoderBuilder<'a> {
         }
         let reader = reader_builder.build()?;
         let reader_stream_index = reader.best_video_stream_index()?;
+        let settings = DecoderSettings {
+            resize: self.resize,
+            hardware_acceleration_device_type: self.hardware_acceleration_device_type,
+            hardware_acceleration_fallback: self.hardware_acceleration_fallback,
+            pixel_format: self.pixel_format,
+            #[cfg(feature = "filter")]
+            filter: self.filter,
+            #[cfg(feature = "filter")]
+            deinterlace: self.deinterlace,
+        };
         Ok(Decoder {
-            decoder: DecoderSplit::new(
-                &reader,
-                reader_stream_index,
-                self.resize,
-                self.hardware_acceleration_device_type,
-            )?,
+            decoder: DecoderSplit::from_settings(&reader, reader_stream_index, settings)?,
             reader,
             reader_stream_index,
             draining: false,
+            frame: None,
         })
     }
 }
@@ -113,6 +204,7 @@ pub struct Decoder {
     reader: Reader,
     reader_stream_index: usize,
     draining: bool,
+    frame: Option<RawFrame>,
 }
 
 impl Decoder {
@@ -195,6 +287,11 @@ impl Decoder {
     pub fn decode(&mut self) -> Result<(Time, Frame)> {
         Ok(loop {
             if !self.draining {
+                // Filters may produce more than one frame per packet, so frames that are still
+                // queued come first.
+                if let Some(frame) = self.decoder.receive_raw()? {
+                    break frame;
+                }
                 let packet_result = self.reader.read(self.reader_stream_index);
                 if matches!(packet_result, Err(Error::ReadExhausted)) {
                     self.draining = true;
@@ -218,6 +315,157 @@ impl Decoder {
         })
     }
 
+    /// Decode a single frame into a caller-provided buffer. This avoids allocating a new array for
+    /// every frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Buffer to write the frame to, with dimensions `(H, W, C)`. The dimensions must
+    ///   match [`Decoder::size_out`].
+    ///
+    /// # Return value
+    ///
+    /// The frame timestamp (relative to the stream).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let (width, height) = decoder.size_out();
+    /// let mut frame = Array3::<u8>::zeros((height as usize, width as usize, 3));
+    /// loop {
+    ///     let ts = decoder.decode_into(frame.view_mut())?;
+    ///     // Do something with frame...
+    /// }
+    /// ```
+    #[cfg(feature = "ndarray")]
+    pub fn decode_into(&mut self, mut frame: ArrayViewMut3<u8>) -> Result<Time> {
+        let raw_frame = self.decode_raw()?;
+        if raw_frame.format() != FRAME_PIXEL_FORMAT {
+            return Err(Error::InvalidFrameFormat);
+        }
+        let timestamp = self.decoder.frame_timestamp(&raw_frame);
+        let result = {
+            let view = ffi::convert_frame_to_ndarray_view_rgb24(&raw_frame);
+            if view.dim() == frame.dim() {
+                frame.assign(&view);
+                Ok(timestamp)
+            } else {
+                Err(Error::InvalidFrameFormat)
+            }
+        };
+        self.decoder.recycle(raw_frame);
+        result
+    }
+
+    /// Decode a single frame and borrow it without copying.
+    ///
+    /// The returned view borrows the decoder, and stays valid until the next call. Note that rows
+    /// in the frame may be padded, in which case the view is not in standard layout.
+    ///
+    /// # Return value
+    ///
+    /// A tuple of the frame timestamp (relative to the stream) and a view of the frame with
+    /// dimensions `(H, W, C)`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// loop {
+    ///     let (ts, frame) = decoder.decode_view()?;
+    ///     // Do something with frame...
+    /// }
+    /// ```
+    #[cfg(feature = "ndarray")]
+    pub fn decode_view(&mut self) -> Result<(Time, ArrayView3<'_, u8>)> {
+        if let Some(raw_frame) = self.frame.take() {
+            self.decoder.recycle(raw_frame);
+        }
+        let raw_frame = self.decode_raw()?;
+        if raw_frame.format() != FRAME_PIXEL_FORMAT {
+            return Err(Error::InvalidFrameFormat);
+        }
+        let timestamp = self.decoder.frame_timestamp(&raw_frame);
+        let raw_frame = self.frame.insert(raw_frame);
+        Ok((
+            timestamp,
+            ffi::convert_frame_to_ndarray_view_rgb24(raw_frame),
+        ))
+    }
+
+    /// Decode a single frame and convert it to a normalized floating-point tensor.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Layout and normalization of the tensor.
+    ///
+    /// # Return value
+    ///
+    /// A tuple of the frame timestamp (relative to the stream) and the tensor frame.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let settings = TensorSettings::new().with_normalization(Normalization::imagenet());
+    /// let (ts, tensor) = decoder.decode_tensor(&settings)?;
+    /// ```
+    #[cfg(feature = "ndarray")]
+    pub fn decode_tensor(&mut self, settings: &TensorSettings) -> Result<(Time, TensorFrame)> {
+        let (timestamp, frame) = self.decode_view()?;
+        Ok((timestamp, convert_frame_to_tensor(frame, settings)?))
+    }
+
+    /// Decode frames into a batch of tensor frames. The batch is cleared first and then filled
+    /// until it is full or the decoder is exhausted.
+    ///
+    /// The batch reuses its backing buffer, so the caller should keep the same batch around for
+    /// the whole stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch` - Batch to decode into. Its frame size must match [`Decoder::size_out`].
+    ///
+    /// # Return value
+    ///
+    /// The number of frames in the batch. This is only less than the batch capacity for the last
+    /// batch in the stream. After that, [`Error::DecodeExhausted`] is returned.
+    #[cfg(feature = "ndarray")]
+    pub fn decode_batch(&mut self, batch: &mut FrameBatch) -> Result<usize> {
+        batch.clear();
+        while !batch.is_full() {
+            match self.decode_view() {
+                Ok((timestamp, frame)) => batch.push(timestamp, frame)?,
+                Err(Error::DecodeExhausted) if !batch.is_empty() => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(batch.len())
+    }
+
+    /// Decode frames through iterator interface. This is similar to `decode_frame` but it returns
+    /// frames through an infinite iterator.
+    pub fn decode_frame_iter(&mut self) -> impl Iterator<Item = Result<VideoFrame>> + '_ {
+        std::iter::from_fn(move || Some(self.decode_frame()))
+    }
+
+    /// Decode a single frame and return it along with its metadata.
+    ///
+    /// # Return value
+    ///
+    /// The decoded frame as [`VideoFrame`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// loop {
+    ///     let frame = decoder.decode_frame()?;
+    ///     println!("frame at {} is key: {}", frame.pts(), frame.is_key());
+    /// }
+    /// ```
+    pub fn decode_frame(&mut self) -> Result<VideoFrame> {
+        let frame = self.decode_raw()?;
+        Ok(VideoFrame::new(frame, self.decoder.frame_time_base()))
+    }
+
     /// Decode frames through iterator interface. This is similar to `decode_raw` but it returns
     /// frames through an infinite iterator.
     pub fn decode_raw_iter(&mut self) -> impl Iterator<Item = Result<RawFrame>> + '_ {
@@ -232,6 +480,11 @@ impl Decoder {
     pub fn decode_raw(&mut self) -> Result<RawFrame> {
         Ok(loop {
             if !self.draining {
+                // Filters may produce more than one frame per packet, so frames that are still
+                // queued come first.
+                if let Some(frame) = self.decoder.receive_raw()? {
+                    break frame;
+                }
                 let packet_result = self.reader.read(self.reader_stream_index);
                 if matches!(packet_result, Err(Error::ReadExhausted)) {
                     self.draining = true;
@@ -241,8 +494,6 @@ impl Decoder {
                 if let Some(frame) = self.decoder.decode_raw(packet)? {
                     break frame;
                 }
-            } else if let Some(frame) = self.decoder.drain_raw()? {
-                break frame;
             } else {
                 match self.decoder.drain_raw() {
                     Ok(Some(frame)) => break frame,
@@ -264,7 +515,7 @@ impl Decoder {
     pub fn seek(&mut self, timestamp_milliseconds: i64) -> Result<()> {
         self.reader
             .seek(timestamp_milliseconds)
-            .inspect(|_| self.decoder.decoder.flush())
+            .inspect(|_| self.decoder.flush())
     }
 
     /// Seek to specific frame in reader.
@@ -274,7 +525,7 @@ impl Decoder {
     pub fn seek_to_frame(&mut self, frame_number: i64) -> Result<()> {
         self.reader
             .seek_to_frame(frame_number)
-            .inspect(|_| self.decoder.decoder.flush())
+            .inspect(|_| self.decoder.flush())
     }
 
     /// Seek to start of reader.
@@ -284,7 +535,7 @@ impl Decoder {
     pub fn seek_to_start(&mut self) -> Result<()> {
         self.reader
             .seek_to_start()
-            .inspect(|_| self.decoder.decoder.flush())
+            .inspect(|_| self.decoder.flush())
     }
 
     /// Split the decoder into a decoder (of type [`DecoderSplit`]) and a [`Reader`].
@@ -313,6 +564,19 @@ impl Decoder {
         self.decoder.size_out
     }
 
+    /// Whether or not the decoder ended up using hardware acceleration. See
+    /// [`DecoderBuilder::with_hardware_acceleration_fallback`].
+    #[inline]
+    pub fn is_hardware_accelerated(&self) -> bool {
+        self.decoder.is_hardware_accelerated()
+    }
+
+    /// Get the device type used for hardware acceleration, if any.
+    #[inline]
+    pub fn hardware_acceleration_device_type(&self) -> Option<HardwareAccelerationDeviceType> {
+        self.decoder.hardware_acceleration_device_type()
+    }
+
     /// Get the decoders input frame rate as floating-point value.
     pub fn frame_rate(&self) -> f32 {
         let frame_rate = self
@@ -340,8 +604,12 @@ impl Decoder {
 pub struct DecoderSplit {
     decoder: AvDecoder,
     decoder_time_base: AvRational,
+    frame_time_base: AvRational,
     hwaccel_context: Option<HardwareAccelerationContext>,
+    #[cfg(feature = "filter")]
+    filter: Option<FilterGraph>,
     scaler: Option<AvScaler>,
+    frame_pool: FramePool,
     size: (u32, u32),
     size_out: (u32, u32),
     draining: bool,
@@ -359,6 +627,49 @@ impl DecoderSplit {
         reader_stream_index: usize,
         resize: Option<Resize>,
         hwaccel_device_type: Option<HardwareAccelerationDeviceType>,
+    ) -> Result<Self> {
+        let settings = DecoderSettings {
+            resize,
+            hardware_acceleration_device_type: hwaccel_device_type,
+            ..Default::default()
+        };
+        Self::from_settings(reader, reader_stream_index, settings)
+    }
+
+    /// Create a new [`DecoderSplit`] that produces frames in the pixel format of the stream,
+    /// without converting them.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - [`Reader`] to initialize decoder from.
+    /// * `reader_stream_index` - Index of stream in reader to decode.
+    pub(crate) fn new_native(reader: &Reader, reader_stream_index: usize) -> Result<Self> {
+        let reader_stream = reader
+            .input
+            .stream(reader_stream_index)
+            .ok_or(AvError::StreamNotFound)?;
+        let pixel_format = AvContext::from_parameters(reader_stream.parameters())?
+            .decoder()
+            .video()?
+            .format();
+        let settings = DecoderSettings {
+            pixel_format: Some(pixel_format),
+            ..Default::default()
+        };
+        Self::from_settings(reader, reader_stream_index, settings)
+    }
+
+    /// Create a new [`DecoderSplit`] with the settings collected by [`DecoderBuilder`].
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - [`Reader`] to initialize decoder from.
+    /// * `reader_stream_index` - Index of stream in reader to decode.
+    /// * `settings` - Decoder settings.
+    fn from_settings(
+        reader: &Reader,
+        reader_stream_index: usize,
+        settings: DecoderSettings,
     ) -> Result<Self> {
         let reader_stream = reader
             .input
@@ -369,9 +680,14 @@ impl DecoderSplit {
         ffi::set_decoder_context_time_base(&mut decoder, reader_stream.time_base());
         decoder.set_parameters(reader_stream.parameters())?;
 
-        let hwaccel_context = match hwaccel_device_type {
+        let hwaccel_context = match settings.hardware_acceleration_device_type {
             Some(device_type) => Some(HardwareAccelerationContext::new(&mut decoder, device_type)?),
-            None => None,
+            None => settings
+                .hardware_acceleration_fallback
+                .iter()
+                .find_map(|&device_type| {
+                    HardwareAccelerationContext::new(&mut decoder, device_type).ok()
+                }),
         };
 
         let decoder = decoder.decoder().video()?;
@@ -381,29 +697,70 @@ impl DecoderSplit {
             return Err(Error::MissingCodecParameters);
         }
 
-        let (resize_width, resize_height) = match resize {
-            Some(resize) => resize
-                .compute_for((decoder.width(), decoder.height()))
-                .ok_or(Error::InvalidResizeParameters)?,
-            None => (decoder.width(), decoder.height()),
-        };
-
-        let scaler_input_format = if hwaccel_context.is_some() {
+        let decoded_format = if hwaccel_context.is_some() {
             HWACCEL_PIXEL_FORMAT
         } else {
             decoder.format()
         };
 
-        let is_scaler_needed = !(scaler_input_format == FRAME_PIXEL_FORMAT
-            && decoder.width() == resize_width
-            && decoder.height() == resize_height);
+        // The filter graph sits between the decoder and the scaler, so the scaler works on
+        // filtered frames. Deinterlacing goes first, so that the other filters get progressive
+        // frames.
+        #[cfg(feature = "filter")]
+        let filter_descriptions = settings
+            .deinterlace
+            .map(|deinterlace| deinterlace.description())
+            .into_iter()
+            .chain(settings.filter.clone())
+            .collect::<Vec<_>>();
+        #[cfg(feature = "filter")]
+        let filter = if filter_descriptions.is_empty() {
+            None
+        } else {
+            let source = BufferSource::new(
+                decoder.width(),
+                decoder.height(),
+                decoded_format,
+                decoder_time_base,
+            )
+            .with_sample_aspect_ratio(decoder.aspect_ratio());
+            Some(FilterGraph::new(&filter_descriptions.join(","), source)?)
+        };
+        // Filters may change the time base, for example to fit the fields of bob deinterlacing
+        // between the frames, so filtered frames keep the time base of the filter graph.
+        #[cfg(feature = "filter")]
+        let frame_time_base = filter
+            .as_ref()
+            .map_or(decoder_time_base, |filter| filter.time_base());
+        #[cfg(not(feature = "filter"))]
+        let frame_time_base = decoder_time_base;
+        #[cfg(feature = "filter")]
+        let (scaler_input_format, (scaler_input_width, scaler_input_height)) = match &filter {
+            Some(filter) => (filter.output_format(), filter.output_size()),
+            None => (decoded_format, (decoder.width(), decoder.height())),
+        };
+        #[cfg(not(feature = "filter"))]
+        let (scaler_input_format, (scaler_input_width, scaler_input_height)) =
+            (decoded_format, (decoder.width(), decoder.height()));
+
+        let (resize_width, resize_height) = match settings.resize {
+            Some(resize) => resize
+                .compute_for((scaler_input_width, scaler_input_height))
+                .ok_or(Error::InvalidResizeParameters)?,
+            None => (scaler_input_width, scaler_input_height),
+        };
+
+        let scaler_output_format = settings.pixel_format.unwrap_or(FRAME_PIXEL_FORMAT);
+        let is_scaler_needed = !(scaler_input_format == scaler_output_format
+            && scaler_input_width == resize_width
+            && scaler_input_height == resize_height);
         let scaler = if is_scaler_needed {
             Some(
                 AvScaler::get(
                     scaler_input_format,
-                    decoder.width(),
-                    decoder.height(),
-                    FRAME_PIXEL_FORMAT,
+                    scaler_input_width,
+                    scaler_input_height,
+                    scaler_output_format,
                     resize_width,
                     resize_height,
                     AvScalerFlags::AREA,
@@ -420,8 +777,12 @@ impl DecoderSplit {
         Ok(Self {
             decoder,
             decoder_time_base,
+            frame_time_base,
             hwaccel_context,
+            #[cfg(feature = "filter")]
+            filter,
             scaler,
+            frame_pool: FramePool::new(scaler_output_format, resize_width, resize_height),
             size,
             size_out,
             draining: false,
@@ -434,6 +795,13 @@ impl DecoderSplit {
         self.decoder_time_base
     }
 
+    /// Get the time base of the frames produced by this decoder. This is the decoder time base,
+    /// unless filters change it.
+    #[inline]
+    pub fn frame_time_base(&self) -> AvRational {
+        self.frame_time_base
+    }
+
     /// Decode a [`Packet`].
     ///
     /// Feeds the packet to the decoder and returns a frame if there is one available. The caller
@@ -450,7 +818,11 @@ impl DecoderSplit {
     #[cfg(feature = "ndarray")]
     pub fn decode(&mut self, packet: Packet) -> Result<Option<(Time, Frame)>> {
         match self.decode_raw(packet)? {
-            Some(mut frame) => Ok(Some(self.raw_frame_to_time_and_frame(&mut frame)?)),
+            Some(mut frame) => {
+                let time_and_frame = self.raw_frame_to_time_and_frame(&mut frame);
+                self.recycle(frame);
+                Ok(Some(time_and_frame?))
+            }
             None => Ok(None),
         }
     }
@@ -473,6 +845,55 @@ impl DecoderSplit {
         self.receive_frame_from_decoder()
     }
 
+    /// Decode a [`Packet`].
+    ///
+    /// Feeds the packet to the decoder and returns a frame if there is one available. The caller
+    /// should keep feeding packets until the decoder returns a frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if in draining mode.
+    ///
+    /// # Return value
+    ///
+    /// The decoded frame as [`VideoFrame`] if the decoder has a frame available, [`None`] if not.
+    pub fn decode_frame(&mut self, packet: Packet) -> Result<Option<VideoFrame>> {
+        Ok(self
+            .decode_raw(packet)?
+            .map(|frame| VideoFrame::new(frame, self.frame_time_base)))
+    }
+
+    /// Receive a frame without feeding a packet first. Filters such as deinterlacers in bob mode
+    /// produce more than one frame per packet, so call this until it returns [`None`] before
+    /// feeding the next packet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if in draining mode.
+    ///
+    /// # Return value
+    ///
+    /// The decoded raw frame as [`RawFrame`] if the decoder has a frame available, [`None`] if not.
+    pub fn receive_raw(&mut self) -> Result<Option<RawFrame>> {
+        assert!(!self.draining);
+        self.receive_frame_from_decoder()
+    }
+
+    /// Receive a frame without feeding a packet first. See [`DecoderSplit::receive_raw`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if in draining mode.
+    ///
+    /// # Return value
+    ///
+    /// The decoded frame as [`VideoFrame`] if the decoder has a frame available, [`None`] if not.
+    pub fn receive_frame(&mut self) -> Result<Option<VideoFrame>> {
+        Ok(self
+            .receive_raw()?
+            .map(|frame| VideoFrame::new(frame, self.frame_time_base)))
+    }
+
     /// Drain one frame from the decoder.
     ///
     /// After calling drain once the decoder is in draining mode and the caller may not use normal
@@ -485,7 +906,11 @@ impl DecoderSplit {
     #[cfg(feature = "ndarray")]
     pub fn drain(&mut self) -> Result<Option<(Time, Frame)>> {
         match self.drain_raw()? {
-            Some(mut frame) => Ok(Some(self.raw_frame_to_time_and_frame(&mut frame)?)),
+            Some(mut frame) => {
+                let time_and_frame = self.raw_frame_to_time_and_frame(&mut frame);
+                self.recycle(frame);
+                Ok(Some(time_and_frame?))
+            }
             None => Ok(None),
         }
     }
@@ -506,9 +931,54 @@ impl DecoderSplit {
         self.receive_frame_from_decoder()
     }
 
+    /// Drain one frame from the decoder.
+    ///
+    /// After calling drain once the decoder is in draining mode and the caller may not use normal
+    /// decode anymore or it will panic.
+    ///
+    /// # Return value
+    ///
+    /// The decoded frame as [`VideoFrame`] if the decoder has a frame available, [`None`] if not.
+    pub fn drain_frame(&mut self) -> Result<Option<VideoFrame>> {
+        Ok(self
+            .drain_raw()?
+            .map(|frame| VideoFrame::new(frame, self.frame_time_base)))
+    }
+
+    /// Return a frame produced by this decoder so that its buffer can be reused for later frames.
+    /// This avoids allocating a new frame for every decoded frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame previously returned by `decode_raw` or `drain_raw`.
+    pub fn recycle(&mut self, frame: RawFrame) {
+        // Only frames produced by the scaler are our own, other frames come straight from the
+        // decoder and are better left alone.
+        if self.scaler.is_some() {
+            self.frame_pool.put(frame);
+        }
+    }
+
+    /// Get the timestamp (relative to the stream) of a frame produced by this decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame previously returned by `decode_raw` or `drain_raw`.
+    pub fn frame_timestamp(&self, frame: &RawFrame) -> Time {
+        // Filters such as `fps` and deinterlacers in bob mode produce frames of their own, and
+        // only set their PTS, which is in the time base of the filter graph.
+        #[cfg(feature = "filter")]
+        if self.filter.is_some() {
+            return Time::new(frame.pts(), self.frame_time_base);
+        }
+        // Without filters, we use the packet DTS here (which is `frame->pkt_dts`) because that is what the
+        // encoder will use when encoding for the `PTS` field.
+        Time::new(Some(frame.packet().dts), self.decoder_time_base)
+    }
+
     /// Reset the decoder to be used again after draining.
     pub fn reset(&mut self) {
-        self.decoder.flush();
+        self.flush();
         self.draining = false;
     }
 
@@ -525,6 +995,20 @@ impl DecoderSplit {
         self.size_out
     }
 
+    /// Whether or not the decoder uses hardware acceleration.
+    #[inline]
+    pub fn is_hardware_accelerated(&self) -> bool {
+        self.hwaccel_context.is_some()
+    }
+
+    /// Get the device type used for hardware acceleration, if any.
+    #[inline]
+    pub fn hardware_acceleration_device_type(&self) -> Option<HardwareAccelerationDeviceType> {
+        self.hwaccel_context
+            .as_ref()
+            .map(|hwaccel_context| hwaccel_context.device_type())
+    }
+
     /// Send packet to decoder. Includes rescaling timestamps accordingly.
     fn send_packet_to_decoder(&mut self, packet: Packet) -> Result<()> {
         let (mut packet, packet_time_base) = packet.into_inner_parts();
@@ -537,33 +1021,102 @@ impl DecoderSplit {
         Ok(())
     }
 
-    /// Receive packet from decoder. Will handle hwaccel conversions and scaling as well.
+    /// Flush the buffers of the decoder, and of the filter graph if there is one. This is
+    /// necessary after seeking.
+    fn flush(&mut self) {
+        self.decoder.flush();
+        #[cfg(feature = "filter")]
+        if let Some(filter) = self.filter.as_mut() {
+            filter.reset();
+        }
+    }
+
+    /// Receive packet from decoder. Will handle hwaccel conversions, filtering and scaling as
+    /// well.
     fn receive_frame_from_decoder(&mut self) -> Result<Option<RawFrame>> {
-        match self.decoder_receive_frame()? {
-            Some(frame) => {
-                let frame = match self.hwaccel_context.as_ref() {
-                    Some(hwaccel_context) if hwaccel_context.format() == frame.format() => {
-                        Self::download_frame(&frame)?
-                    }
-                    _ => frame,
-                };
+        #[cfg(feature = "filter")]
+        if self.filter.is_some() {
+            return match self.receive_frame_from_filter()? {
+                Some(frame) => Ok(Some(self.scale_frame(frame)?)),
+                None => Ok(None),
+            };
+        }
 
-                let frame = match self.scaler.as_mut() {
-                    Some(scaler) => Self::rescale_frame(&frame, scaler)?,
-                    _ => frame,
-                };
+        match self.receive_downloaded_frame_from_decoder()? {
+            Some(frame) => Ok(Some(self.scale_frame(frame)?)),
+            None => Ok(None),
+        }
+    }
 
-                Ok(Some(frame))
-            }
+    /// Receive a filtered frame, passing decoded frames through the filter graph as needed. The
+    /// timestamps of filtered frames are in the frame time base. Once the decoder is exhausted, the
+    /// filter graph is drained before the decoder reports that.
+    #[cfg(feature = "filter")]
+    fn receive_frame_from_filter(&mut self) -> Result<Option<RawFrame>> {
+        let Some(filter) = self.filter.as_mut() else {
+            return self.receive_downloaded_frame_from_decoder();
+        };
+        let decoder = &mut self.decoder;
+        let hwaccel_context = self.hwaccel_context.as_ref();
+        receive_filtered_frame(filter, || {
+            Self::receive_downloaded_frame(decoder, hwaccel_context)
+        })
+    }
+
+    /// Receive frame from decoder, and download it from the hardware acceleration device if
+    /// necessary.
+    fn receive_downloaded_frame_from_decoder(&mut self) -> Result<Option<RawFrame>> {
+        Self::receive_downloaded_frame(&mut self.decoder, self.hwaccel_context.as_ref())
+    }
+
+    /// Receive frame from a decoder, and download it from the hardware acceleration device if
+    /// necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder` - Decoder to receive frame from.
+    /// * `hwaccel_context` - Hardware acceleration context of the decoder, if any.
+    fn receive_downloaded_frame(
+        decoder: &mut AvDecoder,
+        hwaccel_context: Option<&HardwareAccelerationContext>,
+    ) -> Result<Option<RawFrame>> {
+        match Self::receive_decoded_frame(decoder)? {
+            Some(frame) => match hwaccel_context {
+                Some(hwaccel_context) if hwaccel_context.format() == frame.format() => {
+                    Ok(Some(Self::download_frame(&frame)?))
+                }
+                _ => Ok(Some(frame)),
+            },
             None => Ok(None),
         }
     }
 
+    /// Rescale frame to the output size and pixel format, if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to rescale.
+    fn scale_frame(&mut self, frame: RawFrame) -> Result<RawFrame> {
+        match self.scaler.as_mut() {
+            Some(scaler) => Self::rescale_frame(&frame, scaler, self.frame_pool.take()),
+            None => Ok(frame),
+        }
+    }
+
     /// Pull a decoded frame from the decoder. This function also implements retry mechanism in case
     /// the decoder signals `EAGAIN`.
     fn decoder_receive_frame(&mut self) -> Result<Option<RawFrame>> {
+        Self::receive_decoded_frame(&mut self.decoder)
+    }
+
+    /// Pull a decoded frame from a decoder, or `None` if the decoder signals `EAGAIN`.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder` - Decoder to receive frame from.
+    fn receive_decoded_frame(decoder: &mut AvDecoder) -> Result<Option<RawFrame>> {
         let mut frame = RawFrame::empty();
-        let decode_result = self.decoder.receive_frame(&mut frame);
+        let decode_result = decoder.receive_frame(&mut frame);
         match decode_result {
             Ok(()) => Ok(Some(frame)),
             Err(AvError::Eof) => Err(Error::ReadExhausted),
@@ -582,8 +1135,17 @@ impl DecoderSplit {
     }
 
     /// Rescale frame with the scaler.
-    fn rescale_frame(frame: &RawFrame, scaler: &mut AvScaler) -> Result<RawFrame> {
-        let mut frame_scaled = RawFrame::empty();
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to rescale.
+    /// * `scaler` - Scaler to use.
+    /// * `frame_scaled` - Frame to write to. May be empty, in which case it is allocated.
+    fn rescale_frame(
+        frame: &RawFrame,
+        scaler: &mut AvScaler,
+        mut frame_scaled: RawFrame,
+    ) -> Result<RawFrame> {
         scaler
             .run(frame, &mut frame_scaled)
             .map_err(Error::BackendError)?;
@@ -593,15 +1155,71 @@ impl DecoderSplit {
 
     #[cfg(feature = "ndarray")]
     fn raw_frame_to_time_and_frame(&self, frame: &mut RawFrame) -> Result<(Time, Frame)> {
-        // We use the packet DTS here (which is `frame->pkt_dts`) because that is what the
-        // encoder will use when encoding for the `PTS` field.
-        let timestamp = Time::new(Some(frame.packet().dts), self.decoder_time_base);
+        if frame.format() != FRAME_PIXEL_FORMAT {
+            return Err(Error::InvalidFrameFormat);
+        }
+        let timestamp = self.frame_timestamp(frame);
         let frame = ffi::convert_frame_to_ndarray_rgb24(frame).map_err(Error::BackendError)?;
 
         Ok((timestamp, frame))
     }
 }
 
+/// Receive a frame from a filter graph. Frames that are already in the graph come out first, so
+/// that filters that produce more frames than they get do not pile them up. Only when the graph
+/// needs more input is the next decoded frame sent into it. Timestamps of filtered frames are in
+/// the time base of the filter graph.
+///
+/// # Arguments
+///
+/// * `filter` - Filter graph to receive frame from.
+/// * `receive_decoded` - Receives the next decoded frame, `None` if the decoder needs more input,
+///   or [`Error::ReadExhausted`] if the decoder is exhausted.
+///
+/// # Return value
+///
+/// A filtered frame, `None` if more input is needed, or [`Error::ReadExhausted`] once both the
+/// decoder and the filter graph are exhausted.
+#[cfg(feature = "filter")]
+fn receive_filtered_frame(
+    filter: &mut FilterGraph,
+    mut receive_decoded: impl FnMut() -> Result<Option<RawFrame>>,
+) -> Result<Option<RawFrame>> {
+    loop {
+        if let Some(frame) = filter.receive()? {
+            return Ok(Some(frame));
+        }
+
+        match receive_decoded() {
+            Ok(Some(frame)) => filter.send(frame)?,
+            Ok(None) => return Ok(None),
+            Err(Error::ReadExhausted) => {
+                // Sending EOF more than once has no effect, so once the graph is drained as well,
+                // the next attempt ends up here again without a frame.
+                filter.send_eof()?;
+                return match filter.receive()? {
+                    Some(frame) => Ok(Some(frame)),
+                    None => Err(Error::ReadExhausted),
+                };
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Settings for a [`DecoderSplit`], as collected by [`DecoderBuilder`].
+#[derive(Default)]
+struct DecoderSettings {
+    resize: Option<Resize>,
+    hardware_acceleration_device_type: Option<HardwareAccelerationDeviceType>,
+    hardware_acceleration_fallback: Vec<HardwareAccelerationDeviceType>,
+    pixel_format: Option<PixelFormat>,
+    #[cfg(feature = "filter")]
+    filter: Option<String>,
+    #[cfg(feature = "filter")]
+    deinterlace: Option<Deinterlace>,
+}
+
 impl Drop for DecoderSplit {
     fn drop(&mut self) {
         // Maximum number of invocations to `decoder_receive_frame` to drain the items still on the
@@ -621,3 +1239,125 @@ impl Drop for DecoderSplit {
 
 unsafe impl Send for DecoderSplit {}
 unsafe impl Sync for DecoderSplit {}
+
+#[cfg(all(test, feature = "filter"))]
+mod tests {
+    use super::*;
+    use crate::encode::Settings;
+    use crate::testing;
+
+    /// Time base of the test frames, with frames at 25 fps.
+    const TIME_BASE: AvRational = AvRational(1, 90_000);
+    const FRAME_INTERVAL: i64 = 3600;
+
+    fn test_frames(count: usize, frame_interval: i64) -> Vec<RawFrame> {
+        (0..count)
+            .map(|index| {
+                let mut frame = RawFrame::new(AvPixel::YUV420P, 16, 16);
+                for plane in 0..frame.planes() {
+                    frame.data_mut(plane).fill(128);
+                }
+                frame.set_pts(Some(index as i64 * frame_interval));
+                frame
+            })
+            .collect()
+    }
+
+    /// Feed frames through a filter graph like a decoder would, receiving filtered frames after
+    /// each decoded frame.
+    ///
+    /// # Return value
+    ///
+    /// The timestamps of the frames received before the end of the input, and of all frames.
+    fn filter_frames(
+        description: &str,
+        time_base: AvRational,
+        frames: Vec<RawFrame>,
+    ) -> (Vec<Time>, Vec<Time>) {
+        let source = BufferSource::new(16, 16, AvPixel::YUV420P, time_base);
+        let mut filter = FilterGraph::new(description, source).unwrap();
+        let mut timestamps = Vec::new();
+        for frame in frames {
+            let mut frame = Some(frame);
+            while let Some(filtered) =
+                receive_filtered_frame(&mut filter, || Ok(frame.take())).unwrap()
+            {
+                timestamps.push(Time::new(filtered.pts(), filter.time_base()));
+            }
+        }
+        let received_before_end = timestamps.clone();
+        loop {
+            match receive_filtered_frame(&mut filter, || Err(Error::ReadExhausted)) {
+                Ok(Some(filtered)) => {
+                    timestamps.push(Time::new(filtered.pts(), filter.time_base()))
+                }
+                Err(Error::ReadExhausted) => break,
+                result => panic!("unexpected result: {:?}", result.map(|_| ())),
+            }
+        }
+        (received_before_end, timestamps)
+    }
+
+    #[test]
+    fn receives_every_frame_of_doubling_filter_without_delay() {
+        const COUNT: usize = 8;
+        let (received_before_end, timestamps) = filter_frames(
+            "yadif=mode=send_field:deint=all",
+            TIME_BASE,
+            test_frames(COUNT, FRAME_INTERVAL),
+        );
+        // The filter needs the next frame to deinterlace a frame, so only the fields of the last
+        // frame wait for the end of the input.
+        assert_eq!(received_before_end.len(), 2 * (COUNT - 1));
+        assert_eq!(timestamps.len(), 2 * COUNT);
+    }
+
+    #[test]
+    fn decodes_every_frame_of_doubling_filter() {
+        const COUNT: i64 = 6;
+        let (width, height) = testing::SIZE;
+        let path = testing::temp_path("decode-doubling-filter.mkv");
+        testing::write_video(
+            &path,
+            Settings::preset_ffv1(width, height, AvPixel::YUV420P),
+            COUNT,
+        );
+
+        let mut decoder = DecoderBuilder::new(path.as_path())
+            .with_filter("yadif=mode=send_field:deint=all")
+            .build()
+            .unwrap();
+        let mut decoded = 0;
+        let result = loop {
+            match decoder.decode_frame() {
+                Ok(_) => decoded += 1,
+                Err(err) => break err,
+            }
+        };
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Error::DecodeExhausted), "{result}");
+        assert_eq!(decoded, 2 * COUNT);
+    }
+
+    #[test]
+    fn bob_deinterlacing_produces_frames_at_field_rate() {
+        const COUNT: usize = 6;
+        // With one tick per frame, the fields fall between the ticks of the stream time base.
+        let frames = test_frames(COUNT, 1)
+            .into_iter()
+            .map(|mut frame| {
+                ffi::set_frame_interlaced_top_field_first(&mut frame);
+                frame
+            })
+            .collect();
+        let description = Deinterlace::new()
+            .with_mode(crate::filter::DeinterlaceMode::Bob)
+            .description();
+        let (_, timestamps) = filter_frames(&description, AvRational(1, 25), frames);
+        assert_eq!(timestamps.len(), 2 * COUNT);
+        let interval = |pair: &[Time]| pair[1].as_secs_f64() - pair[0].as_secs_f64();
+        assert!(timestamps
+            .windows(2)
+            .all(|pair| (interval(pair) - 0.02).abs() < 1e-9));
+    }
+}
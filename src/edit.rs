@@ -0,0 +1,1421 @@
+extern crate ffmpeg_next as ffmpeg;
+
+use std::cmp::Ordering;
+
+use ffmpeg::codec::packet::Packet as AvPacket;
+use ffmpeg::codec::{Context as AvContext, Id as AvCodecId};
+use ffmpeg::media::Type as AvMediaType;
+use ffmpeg::{Error as AvError, Rational as AvRational};
+
+use crate::decode::DecoderSplit;
+use crate::encode::{Codec, EncoderSplit, GopSettings, RateControl, Settings};
+use crate::error::Error;
+use crate::extradata::{
+    extract_nal_framing_and_parameter_sets, join_nal_units, split_nal_units, NalFraming,
+};
+use crate::ffi;
+use crate::frame::{PixelFormat, VideoFrame};
+use crate::io::{Reader, Write};
+use crate::location::Location;
+use crate::mux::{Muxer, MuxerBuilder};
+use crate::options::Options;
+use crate::packet::Packet;
+use crate::time::Time;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// How far past the end of a range reading goes on after the reference stream has ended, in
+/// seconds. Other streams can stop before the end of the range, and sparse streams such as
+/// subtitles may have no packets near it at all, so they do not all reach the end.
+const END_MARGIN_SECS: f32 = 5.0;
+
+/// Cuts a time range out of a video without decoding and encoding it, by copying the packets of
+/// the range into a new container.
+///
+/// Packets can only be copied from a keyframe on, so the clip starts at the last keyframe at or
+/// before the start of the range. By default, timestamps are rebased so that this keyframe is at
+/// zero. With [`Trim::with_edit_list`], timestamps are rebased so that the start of the range is
+/// at zero instead, and the container is asked to write an edit list that hides the frames before
+/// it. Containers that support edit lists (MP4 and MOV) then start playing exactly at the start of
+/// the range.
+///
+/// At the end of the range, packets are copied as long as their decoding timestamp lies before the
+/// end, so that all frames before the end can be decoded. Copying stops when every stream has
+/// reached the end, or when the reference stream has reached it and reading has moved a few
+/// seconds past it, so that streams that stop early or have sparse packets, such as subtitles, do
+/// not keep the whole file from being read.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut reader = Reader::new(Path::new("recording.mp4"))?;
+/// let writer = Writer::new(Path::new("incident.mp4"))?;
+/// Trim::new(Time::from_secs(3600.0), Time::from_secs(3660.0))
+///     .with_edit_list(true)
+///     .run(&mut reader, writer)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Trim {
+    start: Time,
+    end: Time,
+    edit_list: bool,
+    stream_indices: Option<Vec<usize>>,
+}
+
+impl Trim {
+    /// Create a trim operation for a time range.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Start of range.
+    /// * `end` - End of range (exclusive).
+    pub fn new(start: Time, end: Time) -> Self {
+        Self {
+            start,
+            end,
+            edit_list: false,
+            stream_indices: None,
+        }
+    }
+
+    /// Set whether to start the clip exactly at the start of the range with an edit list. Defaults
+    /// to `false`.
+    ///
+    /// # Arguments
+    ///
+    /// * `edit_list` - Whether to use an edit list.
+    pub fn with_edit_list(mut self, edit_list: bool) -> Self {
+        self.edit_list = edit_list;
+        self
+    }
+
+    /// Only copy the given streams. By default, all streams are copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_indices` - Indices of the streams in the reader to copy.
+    pub fn with_streams(mut self, stream_indices: impl IntoIterator<Item = usize>) -> Self {
+        self.stream_indices = Some(stream_indices.into_iter().collect());
+        self
+    }
+
+    /// Copy the range from a reader to a writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Reader to copy from. The reader is moved to the start of the range.
+    /// * `writer` - Writer to copy to.
+    ///
+    /// # Return value
+    ///
+    /// What the writer produced for each write, such as the buffers of a
+    /// [`BufWriter`](crate::io::BufWriter).
+    pub fn run<W: Write>(&self, reader: &mut Reader, writer: W) -> Result<Vec<W::Out>> {
+        if !self.start.has_value() || compare(self.start, self.end) != Some(Ordering::Less) {
+            return Err(Error::InvalidTimeRange);
+        }
+
+        let stream_indices = match &self.stream_indices {
+            Some(stream_indices) => stream_indices.clone(),
+            None => reader
+                .input
+                .streams()
+                .map(|stream| stream.index())
+                .collect(),
+        };
+        // The stream that decides where the clip starts: the video stream if there is one.
+        let reference_stream_index = reader
+            .best_video_stream_index()
+            .ok()
+            .filter(|index| stream_indices.contains(index))
+            .or_else(|| stream_indices.first().copied())
+            .ok_or(AvError::StreamNotFound)?;
+
+        let mut muxer = stream_indices
+            .iter()
+            .try_fold(MuxerBuilder::new(writer), |builder, &index| {
+                builder.with_stream(reader.stream_info(index)?)
+            })?
+            .interleaved()
+            .build();
+        if self.edit_list {
+            request_edit_list(&mut muxer)?;
+        }
+
+        let reference_position = stream_indices
+            .iter()
+            .position(|index| *index == reference_stream_index)
+            .ok_or(AvError::StreamNotFound)?;
+
+        reader.seek_to_keyframe_before(self.start)?;
+
+        let mut outputs = Vec::new();
+        let mut offset = self.edit_list.then_some(self.start);
+        let mut started = vec![false; stream_indices.len()];
+        let mut ended = vec![false; stream_indices.len()];
+        let mut pending = Vec::new();
+        for (stream, packet) in reader.input.packets() {
+            let packet = Packet::new(packet, stream.time_base());
+            if ended[reference_position] && is_past_end_margin(&packet, self.end) {
+                break;
+            }
+            let Some(position) = stream_indices
+                .iter()
+                .position(|index| *index == stream.index())
+            else {
+                continue;
+            };
+            if ended[position] {
+                continue;
+            }
+            match compare(decode_time(&packet), self.end) {
+                Some(Ordering::Less) => {}
+                // Packets without timestamps cannot be placed in the range.
+                None => continue,
+                Some(_) => {
+                    ended[position] = true;
+                    if ended.iter().all(|ended| *ended) {
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            if stream.index() == reference_stream_index {
+                if !started[position] {
+                    if !packet.is_key() {
+                        continue;
+                    }
+                    started[position] = true;
+                    offset.get_or_insert(packet.pts());
+                }
+            } else if stream.parameters().medium() == AvMediaType::Video && !started[position] {
+                // Other video streams also have to start at a keyframe to be decodable.
+                if !packet.is_key() {
+                    continue;
+                }
+                started[position] = true;
+            }
+
+            // Until the reference stream reaches its first keyframe, it is not known where the
+            // clip starts.
+            let Some(offset) = offset else {
+                pending.push(packet);
+                continue;
+            };
+            for packet in pending.drain(..).chain(std::iter::once(packet)) {
+                // Packets of other streams before the start of the clip are left out, so that
+                // all streams start together.
+                if packet.stream_index() != reference_stream_index
+                    && compare(packet.pts(), offset) == Some(Ordering::Less)
+                {
+                    continue;
+                }
+                outputs.push(muxer.mux(rebase(packet, offset))?);
+            }
+        }
+
+        if let Some(output) = muxer.finish()? {
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+}
+
+/// Cut a time range out of a video without decoding and encoding it. See [`Trim`] for details and
+/// more options.
+///
+/// # Arguments
+///
+/// * `reader` - Reader to copy from.
+/// * `start` - Start of range. The clip starts at the last keyframe at or before it.
+/// * `end` - End of range (exclusive).
+/// * `writer` - Writer to copy to.
+///
+/// # Return value
+///
+/// What the writer produced for each write.
+pub fn trim<W: Write>(
+    reader: &mut Reader,
+    start: Time,
+    end: Time,
+    writer: W,
+) -> Result<Vec<W::Out>> {
+    Trim::new(start, end).run(reader, writer)
+}
+
+/// Cuts a time range out of an H.264 or H.265 video exactly, while re-encoding as little of it as
+/// possible.
+///
+/// Copying packets as [`Trim`] does is only exact up to a group of pictures (GOP). A smart cut
+/// decodes and encodes only the frames between the start of the range and the first keyframe in
+/// it, and the frames between the last keyframe in the range and the end. The GOPs in between are
+/// copied as they are. The re-encoded frames use the codec, pixel format and size of the source,
+/// and carry their own parameter sets. The parameter sets of the source are inserted again before
+/// the first copied keyframe, so that decoders switch back to them. When the range contains fewer
+/// than two keyframes, the whole range is re-encoded.
+///
+/// In containers that keep the parameter sets out of the video stream, such as MP4, the video
+/// stream is marked with the `avc3` or `hev1` codec tag, which allows parameter sets inside the
+/// stream. Output formats that cannot signal this are rejected with
+/// [`Error::UnsupportedInBandParameterSets`].
+///
+/// Audio and other non-video streams are copied from the start to the end of the range. Only the
+/// best video stream is kept. Timestamps are rebased so that the start of the range is at zero.
+///
+/// Smart cuts work best on sources with closed GOPs. In open GOPs, pictures at the start of a GOP
+/// may refer to the previous GOP. Those pictures are re-encoded at both cut points, but a decoder
+/// may still drop or conceal them at the first copied keyframe.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut reader = Reader::new(Path::new("recording.mp4"))?;
+/// let writer = Writer::new(Path::new("incident.mp4"))?;
+/// SmartCut::new(Time::from_secs(3600.0), Time::from_secs(3660.0))
+///     .with_rate_control(RateControl::Crf(20.0))
+///     .run(&mut reader, writer)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct SmartCut {
+    start: Time,
+    end: Time,
+    rate_control: RateControl,
+}
+
+impl SmartCut {
+    /// Default rate control of the re-encoded frames. Visually close to the source for most
+    /// H.264 and H.265 encoders.
+    const RATE_CONTROL: RateControl = RateControl::Crf(18.0);
+
+    /// Create a smart cut operation for a time range.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Start of range.
+    /// * `end` - End of range (exclusive).
+    pub fn new(start: Time, end: Time) -> Self {
+        Self {
+            start,
+            end,
+            rate_control: Self::RATE_CONTROL,
+        }
+    }
+
+    /// Set the rate control of the re-encoded frames. Defaults to a CRF of 18.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate_control` - Rate control mode.
+    pub fn with_rate_control(mut self, rate_control: RateControl) -> Self {
+        self.rate_control = rate_control;
+        self
+    }
+
+    /// Cut the range from a reader to a writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Reader to cut from. The reader is moved to the start of the range.
+    /// * `writer` - Writer to write the clip to.
+    ///
+    /// # Return value
+    ///
+    /// What the writer produced for each write, such as the buffers of a
+    /// [`BufWriter`](crate::io::BufWriter).
+    pub fn run<W: Write>(&self, reader: &mut Reader, writer: W) -> Result<Vec<W::Out>> {
+        if !self.start.has_value() || compare(self.start, self.end) != Some(Ordering::Less) {
+            return Err(Error::InvalidTimeRange);
+        }
+
+        let video_stream_index = reader.best_video_stream_index()?;
+        let source = SourceVideo::new(reader, video_stream_index)?;
+
+        // The first pass finds the keyframes in the range, which decide what is copied.
+        reader.seek_to_keyframe_before(self.start)?;
+        let mut keyframes = Vec::new();
+        for (stream, packet) in reader.input.packets() {
+            if stream.index() != video_stream_index {
+                continue;
+            }
+            let packet = Packet::new(packet, stream.time_base());
+            match compare(decode_time(&packet), self.end) {
+                Some(Ordering::Less) => {}
+                // Packets without timestamps cannot be placed in the range.
+                None => continue,
+                Some(_) => break,
+            }
+            if packet.is_key()
+                && compare(packet.pts(), self.start) != Some(Ordering::Less)
+                && compare(packet.pts(), self.end) == Some(Ordering::Less)
+            {
+                keyframes.push(Keyframe {
+                    pts: packet.pts(),
+                    dts: packet.dts(),
+                });
+            }
+        }
+        let copy = match keyframes.as_slice() {
+            [first, .., tail_from, last] => Some(CopyRange {
+                first: *first,
+                tail_from: *tail_from,
+                last: *last,
+            }),
+            [first, last] => Some(CopyRange {
+                first: *first,
+                tail_from: *first,
+                last: *last,
+            }),
+            _ => None,
+        };
+
+        let head = Reencoder::new(
+            reader,
+            &source,
+            self.rate_control,
+            Some(self.start),
+            copy.map_or(self.end, |copy| copy.first.pts),
+            copy.map_or(0, |copy| copy.first.decode_delay()),
+        )?;
+        let tail = copy
+            .map(|copy| {
+                Reencoder::new(
+                    reader,
+                    &source,
+                    self.rate_control,
+                    None,
+                    self.end,
+                    copy.last.decode_delay(),
+                )
+            })
+            .transpose()?;
+
+        let stream_indices = reader
+            .input
+            .streams()
+            .filter(|stream| {
+                stream.index() == video_stream_index
+                    || stream.parameters().medium() != AvMediaType::Video
+            })
+            .map(|stream| stream.index())
+            .collect::<Vec<_>>();
+        let mut muxer = stream_indices
+            .iter()
+            .try_fold(MuxerBuilder::new(writer), |builder, &index| {
+                builder.with_stream(reader.stream_info(index)?)
+            })?
+            .interleaved()
+            .build();
+        let video_output_index = stream_indices
+            .iter()
+            .position(|index| *index == video_stream_index)
+            .ok_or(AvError::StreamNotFound)?;
+        allow_in_band_parameter_sets(&mut muxer, video_output_index, &source)?;
+
+        reader.seek_to_keyframe_before(self.start)?;
+
+        let mut splice = Splice::new(source, copy, head, tail, self.start);
+        let mut outputs = Vec::new();
+        let mut ended = vec![false; stream_indices.len()];
+        for (stream, packet) in reader.input.packets() {
+            let packet = Packet::new(packet, stream.time_base());
+            if ended[video_output_index] && is_past_end_margin(&packet, self.end) {
+                break;
+            }
+            let Some(position) = stream_indices
+                .iter()
+                .position(|index| *index == stream.index())
+            else {
+                continue;
+            };
+            if ended[position] {
+                continue;
+            }
+            match compare(decode_time(&packet), self.end) {
+                Some(Ordering::Less) => {}
+                None => continue,
+                Some(_) => {
+                    ended[position] = true;
+                    if stream.index() == video_stream_index {
+                        for packet in splice.finish()? {
+                            outputs.push(muxer.mux(packet)?);
+                        }
+                    }
+                    if ended.iter().all(|ended| *ended) {
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            if stream.index() == video_stream_index {
+                for packet in splice.push(packet)? {
+                    outputs.push(muxer.mux(packet)?);
+                }
+            } else if compare(packet.pts(), self.start) != Some(Ordering::Less) {
+                outputs.push(muxer.mux(rebase(packet, self.start))?);
+            }
+        }
+        for packet in splice.finish()? {
+            outputs.push(muxer.mux(packet)?);
+        }
+
+        if let Some(output) = muxer.finish()? {
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+}
+
+/// Cut a time range out of an H.264 or H.265 video exactly, re-encoding only the partial GOPs at
+/// the cut points. See [`SmartCut`] for details and more options.
+///
+/// # Arguments
+///
+/// * `reader` - Reader to cut from.
+/// * `start` - Start of range.
+/// * `end` - End of range (exclusive).
+/// * `writer` - Writer to write the clip to.
+///
+/// # Return value
+///
+/// What the writer produced for each write.
+pub fn smart_cut<W: Write>(
+    reader: &mut Reader,
+    start: Time,
+    end: Time,
+    writer: W,
+) -> Result<Vec<W::Out>> {
+    SmartCut::new(start, end).run(reader, writer)
+}
+
+/// Joins several inputs into one output without decoding and encoding them, for example to stitch
+/// consecutive recordings together.
+///
+/// All inputs must have the same streams as the first input, with compatible codec parameters:
+/// the same codec, picture size and pixel format or audio format, and the same extradata. This is
+/// verified before anything is written. The timestamps of each input are shifted so that it
+/// starts where the previous input ends, which keeps them continuous and monotonic.
+///
+/// # Example
+///
+/// ```ignore
+/// let writer = Writer::new(Path::new("incident.mp4"))?;
+/// Concat::new([
+///     Path::new("camera-0900.mp4"),
+///     Path::new("camera-1000.mp4"),
+///     Path::new("camera-1100.mp4"),
+/// ])
+/// .run(writer)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Concat {
+    sources: Vec<Location>,
+}
+
+impl Concat {
+    /// Maximum number of packets to read ahead to find where an input starts.
+    const MAX_PROBE_PACKETS: usize = 1024;
+
+    /// Create a concatenation of inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - Inputs to concatenate, in order.
+    pub fn new(sources: impl IntoIterator<Item = impl Into<Location>>) -> Self {
+        Self {
+            sources: sources.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Concatenate the inputs into a writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Writer to write to.
+    ///
+    /// # Return value
+    ///
+    /// What the writer produced for each write, such as the buffers of a
+    /// [`BufWriter`](crate::io::BufWriter). Without any inputs, [`Error::NoInputs`] is returned.
+    pub fn run<W: Write>(&self, writer: W) -> Result<Vec<W::Out>> {
+        let mut readers = self
+            .sources
+            .iter()
+            .cloned()
+            .map(Reader::new)
+            .collect::<Result<Vec<_>>>()?;
+        let first = readers.first().ok_or(Error::NoInputs)?;
+
+        let stream_infos = first
+            .input
+            .streams()
+            .map(|stream| first.stream_info(stream.index()))
+            .collect::<Result<Vec<_>>>()?;
+        for (input, reader) in readers.iter().enumerate().skip(1) {
+            let stream_count = reader.input.nb_streams() as usize;
+            if stream_count != stream_infos.len() {
+                return Err(Error::IncompatibleInputs {
+                    input,
+                    reason: format!(
+                        "it has {stream_count} streams instead of {}",
+                        stream_infos.len()
+                    ),
+                });
+            }
+            for (index, stream_info) in stream_infos.iter().enumerate() {
+                if let Some(reason) =
+                    stream_info.incompatibility_with(&reader.stream_info(index)?)?
+                {
+                    return Err(Error::IncompatibleInputs {
+                        input,
+                        reason: format!("stream {index} {reason}"),
+                    });
+                }
+            }
+        }
+
+        // Packets are muxed in the time bases of the streams of the first input.
+        let time_bases = first
+            .input
+            .streams()
+            .map(|stream| stream.time_base())
+            .collect::<Vec<_>>();
+        let is_audio_or_video = first
+            .input
+            .streams()
+            .map(|stream| {
+                matches!(
+                    stream.parameters().medium(),
+                    AvMediaType::Audio | AvMediaType::Video
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut muxer = MuxerBuilder::new(writer)
+            .with_streams(first)?
+            .interleaved()
+            .build();
+
+        let mut outputs = Vec::new();
+        let mut offset = Time::zero();
+        let mut ends: Vec<Option<Time>> = vec![None; time_bases.len()];
+        for reader in &mut readers {
+            let mut packets = reader.input.packets().map(|(stream, packet)| {
+                with_time_base(
+                    Packet::new(packet, stream.time_base()),
+                    time_bases[stream.index()],
+                )
+            });
+
+            // Streams do not start together, and the stream that starts first in decoding order
+            // is not necessarily first in the file, so look ahead until every audio and video
+            // stream has started.
+            let mut probed = Vec::new();
+            let mut started = vec![false; time_bases.len()];
+            while probed.len() < Self::MAX_PROBE_PACKETS
+                && (0..started.len()).any(|index| is_audio_or_video[index] && !started[index])
+            {
+                let Some(packet) = packets.next() else {
+                    break;
+                };
+                started[packet.stream_index()] = true;
+                probed.push(packet);
+            }
+            // The earliest packet of an input is placed where the previous input ended.
+            let Some(start) = earliest_decode_time(&probed) else {
+                continue;
+            };
+            let shift = start.aligned_with(offset).subtract();
+
+            for packet in probed.into_iter().chain(packets) {
+                let index = packet.stream_index();
+                let packet = rebase(packet, shift);
+
+                // Every packet lasts at least one tick, so that the next input starts after it.
+                let duration = packet
+                    .duration()
+                    .into_value()
+                    .filter(|duration| *duration > 0)
+                    .unwrap_or(1);
+                let end = decode_time(&packet)
+                    .aligned_with(Time::new(Some(duration), packet.time_base()))
+                    .add();
+                if end.has_value()
+                    && ends[index].is_none_or(|last| compare(end, last) == Some(Ordering::Greater))
+                {
+                    ends[index] = Some(end);
+                }
+
+                outputs.push(muxer.mux(packet)?);
+            }
+            offset = ends.iter().flatten().copied().fold(offset, |offset, end| {
+                if compare(end, offset) == Some(Ordering::Greater) {
+                    end
+                } else {
+                    offset
+                }
+            });
+        }
+
+        if let Some(output) = muxer.finish()? {
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+}
+
+/// Concatenate inputs without decoding and encoding them. See [`Concat`] for details.
+///
+/// # Arguments
+///
+/// * `sources` - Inputs to concatenate, in order.
+/// * `writer` - Writer to write to.
+///
+/// # Return value
+///
+/// What the writer produced for each write.
+pub fn concat<W: Write>(
+    sources: impl IntoIterator<Item = impl Into<Location>>,
+    writer: W,
+) -> Result<Vec<W::Out>> {
+    Concat::new(sources).run(writer)
+}
+
+/// Properties of the source video stream of a smart cut.
+struct SourceVideo {
+    stream_index: usize,
+    codec: Codec,
+    pixel_format: PixelFormat,
+    size: (u32, u32),
+    time_base: AvRational,
+    framing: NalFraming,
+    parameter_sets: Vec<Vec<u8>>,
+}
+
+impl SourceVideo {
+    /// Read the properties of a video stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Reader that contains the stream.
+    /// * `stream_index` - Index of the video stream.
+    fn new(reader: &Reader, stream_index: usize) -> Result<Self> {
+        let stream = reader
+            .input
+            .stream(stream_index)
+            .ok_or(AvError::StreamNotFound)?;
+        let codec = match stream.parameters().id() {
+            AvCodecId::H264 => Codec::H264,
+            AvCodecId::HEVC => Codec::H265,
+            _ => return Err(Error::UnsupportedCodecParameterSets),
+        };
+        let decoder = AvContext::from_parameters(stream.parameters())?
+            .decoder()
+            .video()?;
+        // The re-encoded frames use the pixel format of the source, so it must be known up front.
+        if decoder.format() == PixelFormat::None {
+            return Err(Error::MissingCodecParameters);
+        }
+        let extradata = ffi::input_extradata(&reader.input, stream_index)?;
+        let (framing, parameter_sets) = extract_nal_framing_and_parameter_sets(codec, extradata)?;
+        Ok(Self {
+            stream_index,
+            codec,
+            pixel_format: decoder.format(),
+            size: (decoder.width(), decoder.height()),
+            time_base: stream.time_base(),
+            framing,
+            parameter_sets: parameter_sets.into_iter().map(<[u8]>::to_vec).collect(),
+        })
+    }
+}
+
+/// Timestamps of a keyframe in the source video stream.
+#[derive(Debug, Clone, Copy)]
+struct Keyframe {
+    pts: Time,
+    dts: Time,
+}
+
+impl Keyframe {
+    /// Whether a packet is this keyframe.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - Packet of the same stream.
+    fn is(&self, packet: &Packet) -> bool {
+        packet.is_key() && compare(packet.pts(), self.pts) == Some(Ordering::Equal)
+    }
+
+    /// How far the decoding timestamp of the keyframe lies before its presentation timestamp, in
+    /// the time base of the stream.
+    fn decode_delay(&self) -> i64 {
+        self.pts
+            .aligned_with(self.dts)
+            .subtract()
+            .into_value()
+            .unwrap_or(0)
+            .max(0)
+    }
+}
+
+/// The keyframes that delimit the copied part of a smart cut.
+#[derive(Debug, Clone, Copy)]
+struct CopyRange {
+    /// First keyframe in the range, where copying starts.
+    first: Keyframe,
+    /// Keyframe before the last keyframe, from where the tail is decoded so that pictures that
+    /// refer back across the last keyframe can be decoded.
+    tail_from: Keyframe,
+    /// Last keyframe in the range, where copying stops.
+    last: Keyframe,
+}
+
+/// Decodes part of the source video stream and encodes the frames within a time range again.
+struct Reencoder {
+    decoder: DecoderSplit,
+    encoder: EncoderSplit,
+    start: Option<Time>,
+    end: Time,
+    decode_delay: i64,
+    packets: Vec<Packet>,
+}
+
+impl Reencoder {
+    /// Create a re-encoder for the video stream of a reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Reader that contains the stream.
+    /// * `source` - Properties of the stream.
+    /// * `rate_control` - Rate control of the encoder.
+    /// * `start` - Start of the frames to encode, or `None` to not encode any frames until it is
+    ///   set.
+    /// * `end` - End of the frames to encode (exclusive).
+    /// * `decode_delay` - Decoding delay of the keyframe that follows the encoded frames (or
+    ///   precedes them), which the decoding timestamps of the encoded packets are aligned to.
+    fn new(
+        reader: &Reader,
+        source: &SourceVideo,
+        rate_control: RateControl,
+        start: Option<Time>,
+        end: Time,
+        decode_delay: i64,
+    ) -> Result<Self> {
+        let options = match source.codec {
+            Codec::H265 => Options::preset_h265(),
+            _ => Options::preset_h264(),
+        };
+        let (width, height) = source.size;
+        // Without B-frames, the decoding order of the encoded frames is the presentation order,
+        // which keeps the decoding timestamps simple to splice.
+        let settings = Settings::preset_custom(
+            source.codec,
+            width as usize,
+            height as usize,
+            source.pixel_format,
+            options,
+        )
+        .with_rate_control(rate_control)
+        .with_gop(GopSettings::new().with_max_b_frames(0));
+        Ok(Self {
+            decoder: DecoderSplit::new_native(reader, source.stream_index)?,
+            encoder: EncoderSplit::new(settings, false)?.with_stream_index(source.stream_index),
+            start,
+            end,
+            decode_delay,
+            packets: Vec::new(),
+        })
+    }
+
+    /// Decode a packet and encode the resulting frame if it lies within the range.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - Packet of the source video stream.
+    fn decode(&mut self, packet: Packet) -> Result<()> {
+        if let Some(frame) = self.decoder.decode_frame(packet)? {
+            self.encode(frame)?;
+        }
+        Ok(())
+    }
+
+    /// Encode a frame if it lies within the range.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Decoded frame.
+    fn encode(&mut self, frame: VideoFrame) -> Result<()> {
+        let Some(start) = self.start else {
+            return Ok(());
+        };
+        if compare(frame.pts(), start) != Some(Ordering::Less)
+            && compare(frame.pts(), self.end) == Some(Ordering::Less)
+        {
+            self.packets.extend(self.encoder.encode_frame(frame)?);
+        }
+        Ok(())
+    }
+
+    /// Drain the decoder and encoder.
+    ///
+    /// # Return value
+    ///
+    /// All encoded packets.
+    fn finish(mut self) -> Result<Vec<Packet>> {
+        loop {
+            match self.decoder.drain_frame() {
+                Ok(Some(frame)) => self.encode(frame)?,
+                Ok(None) | Err(Error::ReadExhausted) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        self.packets.extend(self.encoder.drain()?);
+        Ok(self.packets)
+    }
+}
+
+/// Splices re-encoded and copied packets of the video stream of a smart cut together.
+struct Splice {
+    source: SourceVideo,
+    copy: Option<CopyRange>,
+    head: Option<Reencoder>,
+    tail: Option<Reencoder>,
+    offset: Time,
+    /// Copied packets that wait for the head to be finished.
+    held: Vec<Packet>,
+    copying: bool,
+    tail_decoding: bool,
+    tail_reached: bool,
+    last_copied_pts: Option<Time>,
+    last_dts: Option<i64>,
+}
+
+impl Splice {
+    /// Create a splice.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Properties of the source video stream.
+    /// * `copy` - Keyframes that delimit the copied part, if any.
+    /// * `head` - Re-encoder for the frames before the copied part.
+    /// * `tail` - Re-encoder for the frames after the copied part.
+    /// * `offset` - Timestamp that becomes zero.
+    fn new(
+        source: SourceVideo,
+        copy: Option<CopyRange>,
+        head: Reencoder,
+        tail: Option<Reencoder>,
+        offset: Time,
+    ) -> Self {
+        Self {
+            source,
+            copy,
+            head: Some(head),
+            tail,
+            offset,
+            held: Vec::new(),
+            copying: false,
+            tail_decoding: false,
+            tail_reached: false,
+            last_copied_pts: None,
+            last_dts: None,
+        }
+    }
+
+    /// Process the next packet of the source video stream in decoding order.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - Packet of the source video stream.
+    ///
+    /// # Return value
+    ///
+    /// Packets that are ready to be muxed.
+    fn push(&mut self, packet: Packet) -> Result<Vec<Packet>> {
+        let Some(copy) = self.copy else {
+            if let Some(head) = self.head.as_mut() {
+                head.decode(packet)?;
+            }
+            return Ok(Vec::new());
+        };
+
+        if let Some(tail) = self.tail.as_mut() {
+            self.tail_decoding |= copy.tail_from.is(&packet);
+            if copy.last.is(&packet) {
+                // Everything up to and including the last copied frame is already in the clip.
+                tail.start = Some(match self.last_copied_pts {
+                    Some(pts) => pts
+                        .aligned_with(Time::new(Some(1), self.source.time_base))
+                        .add(),
+                    None => copy.last.pts,
+                });
+                self.tail_reached = true;
+            }
+            if self.tail_decoding {
+                tail.decode(packet.clone())?;
+            }
+        }
+
+        let mut packets = Vec::new();
+        if !self.copying {
+            if copy.first.is(&packet) {
+                // The head decodes the keyframe as well, for the pictures that refer to it but
+                // precede it.
+                if let Some(head) = self.head.as_mut() {
+                    head.decode(packet.clone())?;
+                }
+                self.copying = true;
+                self.last_copied_pts = Some(packet.pts());
+                let packet = self.with_parameter_sets(packet)?;
+                self.held.push(packet);
+            } else if let Some(head) = self.head.as_mut() {
+                head.decode(packet)?;
+            }
+            return Ok(packets);
+        }
+
+        if let Some(head) = self.head.as_mut() {
+            // Pictures that follow the first copied keyframe in decoding order but precede it in
+            // presentation order may refer to the GOP before it, so they are re-encoded too.
+            if !self.tail_reached && compare(packet.pts(), copy.first.pts) == Some(Ordering::Less) {
+                head.decode(packet)?;
+                return Ok(packets);
+            }
+            packets.extend(self.finish_head()?);
+        }
+
+        if !self.tail_reached {
+            self.last_copied_pts = Some(match self.last_copied_pts {
+                Some(pts) if compare(pts, packet.pts()) == Some(Ordering::Greater) => pts,
+                _ => packet.pts(),
+            });
+            packets.push(self.copied(packet));
+        }
+        Ok(packets)
+    }
+
+    /// Finish the video stream.
+    ///
+    /// # Return value
+    ///
+    /// The remaining packets.
+    fn finish(&mut self) -> Result<Vec<Packet>> {
+        let mut packets = self.finish_head()?;
+        if let Some(tail) = self.tail.take() {
+            let decode_delay = tail.decode_delay;
+            for packet in tail.finish()? {
+                packets.push(self.encoded(packet, decode_delay)?);
+            }
+        }
+        Ok(packets)
+    }
+
+    /// Finish the head, followed by the copied packets that waited for it.
+    fn finish_head(&mut self) -> Result<Vec<Packet>> {
+        let mut packets = Vec::new();
+        if let Some(head) = self.head.take() {
+            let decode_delay = head.decode_delay;
+            for packet in head.finish()? {
+                packets.push(self.encoded(packet, decode_delay)?);
+            }
+        }
+        for packet in std::mem::take(&mut self.held) {
+            packets.push(self.copied(packet));
+        }
+        Ok(packets)
+    }
+
+    /// Prepare a copied packet for muxing.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - Packet of the source video stream.
+    fn copied(&mut self, packet: Packet) -> Packet {
+        self.last_dts = packet.dts().into_value().or(self.last_dts);
+        rebase(packet, self.offset)
+    }
+
+    /// Prepare an encoded packet for muxing: convert it to the time base and NAL framing of the
+    /// source stream, and give it a decoding timestamp that fits in between the copied packets.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - Encoded packet.
+    /// * `decode_delay` - Decoding delay of the adjacent copied keyframe.
+    fn encoded(&mut self, packet: Packet, decode_delay: i64) -> Result<Packet> {
+        let (mut inner, time_base) = packet.into_inner_parts();
+        inner.rescale_ts(time_base, self.source.time_base);
+        let mut inner = reframe(inner, NalFraming::AnnexB, self.source.framing, &[])?;
+        if let Some(pts) = inner.pts() {
+            let dts = match self.last_dts {
+                Some(last_dts) => (pts - decode_delay).max(last_dts + 1).min(pts),
+                None => pts - decode_delay,
+            };
+            inner.set_dts(Some(dts));
+            self.last_dts = Some(dts);
+        }
+        Ok(rebase(
+            Packet::new(inner, self.source.time_base),
+            self.offset,
+        ))
+    }
+
+    /// Put the parameter sets of the source in front of a copied keyframe.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - Keyframe packet of the source video stream.
+    fn with_parameter_sets(&self, packet: Packet) -> Result<Packet> {
+        let (inner, time_base) = packet.into_inner_parts();
+        let parameter_sets = self
+            .source
+            .parameter_sets
+            .iter()
+            .map(Vec::as_slice)
+            .collect::<Vec<_>>();
+        let inner = reframe(
+            inner,
+            self.source.framing,
+            self.source.framing,
+            &parameter_sets,
+        )?;
+        Ok(Packet::new(inner, time_base))
+    }
+}
+
+/// Rewrite the NAL units of an H.264 or H.265 packet in another framing, optionally preceded by
+/// other NAL units.
+///
+/// # Arguments
+///
+/// * `packet` - Packet to rewrite.
+/// * `from` - Framing of the packet.
+/// * `to` - Framing to rewrite the packet in.
+/// * `prefix` - NAL units to put in front of the NAL units of the packet.
+fn reframe(
+    packet: AvPacket,
+    from: NalFraming,
+    to: NalFraming,
+    prefix: &[&[u8]],
+) -> Result<AvPacket> {
+    if from == to && prefix.is_empty() {
+        return Ok(packet);
+    }
+    let Some(data) = packet.data() else {
+        return Ok(packet);
+    };
+    let nals = split_nal_units(data, from)?;
+    let mut reframed = AvPacket::copy(&join_nal_units(prefix.iter().copied().chain(nals), to));
+    reframed.set_pts(packet.pts());
+    reframed.set_dts(packet.dts());
+    reframed.set_duration(packet.duration());
+    reframed.set_flags(packet.flags());
+    reframed.set_stream(packet.stream());
+    Ok(reframed)
+}
+
+/// Mark the video stream of a smart cut as carrying parameter sets inside the stream, which the
+/// re-encoded parts do. Streams that frame NAL units with length prefixes, as in MP4, use the
+/// `avc3` or `hev1` codec tag for this, which the output format must accept.
+///
+/// # Arguments
+///
+/// * `muxer` - Muxer that has not written its header yet.
+/// * `stream_index` - Index of the video stream in the output.
+/// * `source` - Source video stream.
+fn allow_in_band_parameter_sets<W: Write>(
+    muxer: &mut Muxer<W>,
+    stream_index: usize,
+    source: &SourceVideo,
+) -> Result<()> {
+    let tag = match (source.framing, source.codec) {
+        (NalFraming::AnnexB, _) => return Ok(()),
+        (NalFraming::LengthPrefixed(_), Codec::H264) => *b"avc3",
+        (NalFraming::LengthPrefixed(_), Codec::H265) => *b"hev1",
+        _ => return Err(Error::UnsupportedCodecParameterSets),
+    };
+    if ffi::set_output_codec_tag(muxer.writer.output_mut(), stream_index, tag)? {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedInBandParameterSets)
+    }
+}
+
+/// Ask the container format of a muxer to write an edit list when the first timestamps are not
+/// zero. Formats that do not support edit lists are left alone.
+///
+/// # Arguments
+///
+/// * `muxer` - Muxer that has not written its header yet.
+fn request_edit_list<W: Write>(muxer: &mut Muxer<W>) -> Result<()> {
+    match ffi::set_output_private_option(muxer.writer.output_mut(), "use_editlist", "1") {
+        Ok(()) | Err(AvError::OptionNotFound) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Whether a packet lies so far past the end of a range that reading can stop, see
+/// [`END_MARGIN_SECS`].
+///
+/// # Arguments
+///
+/// * `packet` - Packet that was read.
+/// * `end` - End of range.
+fn is_past_end_margin(packet: &Packet, end: Time) -> bool {
+    let limit = end.aligned_with(Time::from_secs(END_MARGIN_SECS)).add();
+    matches!(
+        compare(decode_time(packet), limit),
+        Some(Ordering::Greater | Ordering::Equal)
+    )
+}
+
+/// Shift the timestamps of a packet back by an offset.
+///
+/// # Arguments
+///
+/// * `packet` - Packet to shift.
+/// * `offset` - Timestamp that becomes zero.
+fn rebase(mut packet: Packet, offset: Time) -> Packet {
+    let pts = packet.pts().aligned_with(offset).subtract();
+    let dts = packet.dts().aligned_with(offset).subtract();
+    packet.set_pts(pts);
+    packet.set_dts(dts);
+    packet
+}
+
+/// Convert the timestamps of a packet to another time base.
+///
+/// # Arguments
+///
+/// * `packet` - Packet to convert.
+/// * `time_base` - Time base to convert to.
+fn with_time_base(packet: Packet, time_base: AvRational) -> Packet {
+    let (mut inner, packet_time_base) = packet.into_inner_parts();
+    inner.rescale_ts(packet_time_base, time_base);
+    Packet::new(inner, time_base)
+}
+
+/// Find the earliest decoding timestamp of a number of packets.
+///
+/// # Arguments
+///
+/// * `packets` - Packets to search, of any streams.
+///
+/// # Return value
+///
+/// The earliest decoding timestamp, or `None` if no packet has a timestamp.
+fn earliest_decode_time(packets: &[Packet]) -> Option<Time> {
+    packets
+        .iter()
+        .map(decode_time)
+        .filter(Time::has_value)
+        .reduce(|earliest, time| {
+            if compare(time, earliest) == Some(Ordering::Less) {
+                time
+            } else {
+                earliest
+            }
+        })
+}
+
+/// Get the decoding timestamp of a packet, or its presentation timestamp if it has none.
+///
+/// # Arguments
+///
+/// * `packet` - Packet to get timestamp of.
+fn decode_time(packet: &Packet) -> Time {
+    if packet.dts().has_value() {
+        packet.dts()
+    } else {
+        packet.pts()
+    }
+}
+
+/// Compare two timestamps exactly, also when they are in different time bases.
+///
+/// # Arguments
+///
+/// * `lhs` - Left-hand side timestamp.
+/// * `rhs` - Right-hand side timestamp.
+///
+/// # Return value
+///
+/// The ordering, or `None` if either timestamp has no value.
+fn compare(lhs: Time, rhs: Time) -> Option<Ordering> {
+    let (Some(lhs), lhs_time_base) = lhs.into_parts() else {
+        return None;
+    };
+    let (Some(rhs), rhs_time_base) = rhs.into_parts() else {
+        return None;
+    };
+    // Converting one timestamp to the time base of the other would round it, so both sides are
+    // multiplied out instead.
+    let lhs = lhs as i128 * lhs_time_base.numerator() as i128 * rhs_time_base.denominator() as i128;
+    let rhs = rhs as i128 * rhs_time_base.numerator() as i128 * lhs_time_base.denominator() as i128;
+    Some(lhs.cmp(&rhs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::decode::Decoder;
+    use crate::io::{BufWriter, Writer};
+    use crate::testing;
+
+    #[test]
+    fn compares_timestamps_across_time_bases() {
+        let millis = |time| Time::new(Some(time), AvRational::new(1, 1000));
+        let ticks = |time| Time::new(Some(time), AvRational::new(1, 90_000));
+        assert_eq!(compare(millis(1000), ticks(90_000)), Some(Ordering::Equal));
+        assert_eq!(compare(millis(999), ticks(90_000)), Some(Ordering::Less));
+        // An end between two frames of a coarse time base must not round onto a frame.
+        let frames = |time| Time::new(Some(time), AvRational::new(1, 25));
+        assert_eq!(compare(frames(25), millis(1010)), Some(Ordering::Less));
+        assert_eq!(compare(frames(26), millis(1030)), Some(Ordering::Greater));
+        assert_eq!(
+            compare(ticks(180_000), millis(1500)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(millis(0), Time::new(None, AvRational::new(1, 1000))),
+            None
+        );
+    }
+
+    #[test]
+    fn rebases_packet_timestamps() {
+        let mut packet = ffmpeg::Packet::empty();
+        packet.set_pts(Some(9000));
+        packet.set_dts(Some(6000));
+        let packet = rebase(
+            Packet::new(packet, AvRational::new(1, 90_000)),
+            Time::new(Some(50), AvRational::new(1, 1000)),
+        );
+        assert_eq!(packet.pts().into_value(), Some(4500));
+        assert_eq!(packet.dts().into_value(), Some(1500));
+    }
+
+    #[test]
+    fn stops_reading_past_end_margin() {
+        let packet = |dts| {
+            let mut packet = ffmpeg::Packet::empty();
+            packet.set_dts(dts);
+            Packet::new(packet, AvRational::new(1, 90_000))
+        };
+        let end = Time::new(Some(10_000), AvRational::new(1, 1000));
+        assert!(!is_past_end_margin(&packet(Some(90_000 * 12)), end));
+        assert!(is_past_end_margin(&packet(Some(90_000 * 15)), end));
+        assert!(!is_past_end_margin(&packet(None), end));
+    }
+
+    #[test]
+    fn reframes_packets_with_parameter_sets() {
+        let mut packet = AvPacket::copy(&[0, 0, 0, 1, 0x65, 1, 0, 0, 1, 0x41, 2]);
+        packet.set_pts(Some(3000));
+        packet.set_stream(1);
+        let packet = reframe(
+            packet,
+            NalFraming::AnnexB,
+            NalFraming::LengthPrefixed(4),
+            &[&[0x67, 3]],
+        )
+        .unwrap();
+        assert_eq!(
+            packet.data(),
+            Some(&[0, 0, 0, 2, 0x67, 3, 0, 0, 0, 2, 0x65, 1, 0, 0, 0, 2, 0x41, 2][..])
+        );
+        assert_eq!(packet.pts(), Some(3000));
+        assert_eq!(packet.stream(), 1);
+    }
+
+    #[test]
+    fn converts_packet_time_base() {
+        let mut packet = ffmpeg::Packet::empty();
+        packet.set_pts(Some(3003));
+        packet.set_dts(Some(0));
+        let packet = with_time_base(
+            Packet::new(packet, AvRational::new(1, 30_000)),
+            AvRational::new(1, 90_000),
+        );
+        assert_eq!(packet.time_base(), AvRational::new(1, 90_000));
+        assert_eq!(packet.pts().into_value(), Some(9009));
+        assert_eq!(packet.dts().into_value(), Some(0));
+    }
+
+    #[test]
+    fn finds_earliest_start_of_staggered_streams() {
+        let packet = |stream, dts, time_base| {
+            let mut packet = ffmpeg::Packet::empty();
+            packet.set_stream(stream);
+            packet.set_dts(Some(dts));
+            packet.set_pts(Some(dts));
+            Packet::new(packet, time_base)
+        };
+        // An AAC priming packet comes first in the file, but the video starts two frames before
+        // zero in decoding order.
+        let audio = AvRational::new(1, 48_000);
+        let video = AvRational::new(1, 90_000);
+        let packets = [
+            packet(1, -1024, audio),
+            packet(0, -6006, video),
+            packet(1, 0, audio),
+            packet(0, -3003, video),
+        ];
+        let start = earliest_decode_time(&packets).unwrap();
+        assert_eq!(start.into_parts(), (Some(-6006), video));
+        assert_eq!(
+            earliest_decode_time(&packets[..1]).unwrap().into_value(),
+            Some(-1024)
+        );
+        assert!(earliest_decode_time(&[]).is_none());
+    }
+
+    #[test]
+    fn smart_cuts_partial_gops() {
+        let (width, height) = testing::SIZE;
+        let source = testing::temp_path("smart-cut-source.mp4");
+        let clip = testing::temp_path("smart-cut-clip.mp4");
+        // Keyframes at frames 0, 10, 20 and 30, so the cut from frame 5 to frame 33 re-encodes a
+        // partial GOP at either end and copies the two whole GOPs in between.
+        testing::write_video(
+            &source,
+            Settings::preset_h264_yuv420p(width, height, false).with_keyframe_interval(10),
+            40,
+        );
+
+        let start = Time::new(Some(5), testing::TIME_BASE);
+        let end = Time::new(Some(33), testing::TIME_BASE);
+        let result = Reader::new(source.as_path()).and_then(|mut reader| {
+            smart_cut(&mut reader, start, end, Writer::new(clip.as_path())?)
+        });
+        let mut timestamps = Vec::new();
+        let decoded = Decoder::new(clip.as_path()).and_then(|mut decoder| loop {
+            match decoder.decode_frame() {
+                Ok(frame) => timestamps.push(frame.pts().as_secs_f64()),
+                Err(Error::DecodeExhausted) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        });
+        std::fs::remove_file(&source).unwrap();
+        let _ = std::fs::remove_file(&clip);
+
+        result.unwrap();
+        decoded.unwrap();
+        assert_eq!(timestamps.len(), 28);
+        for (index, timestamp) in timestamps.iter().enumerate() {
+            assert!(
+                (timestamp - index as f64 * 0.04).abs() < 0.001,
+                "frame {index} at {timestamp}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_concatenation_without_inputs() {
+        let writer = BufWriter::new("mp4").unwrap();
+        let result = concat(Vec::<Location>::new(), writer);
+        assert!(matches!(result, Err(Error::NoInputs)));
+    }
+}
@@ -1,11 +1,17 @@
 extern crate ffmpeg_next as ffmpeg;
 
+use std::ffi::CString;
+use std::path::PathBuf;
+
+#[cfg(feature = "ndarray")]
+use ndarray::{ArrayView2, ArrayViewMut2, ShapeBuilder};
+
 use ffmpeg::codec::codec::Codec as AvCodec;
 use ffmpeg::codec::encoder::video::Encoder as AvEncoder;
 use ffmpeg::codec::encoder::video::Video as AvVideo;
 use ffmpeg::codec::flag::Flags as AvCodecFlags;
 use ffmpeg::codec::packet::Packet as AvPacket;
-use ffmpeg::codec::{Context as AvContext, Id as AvCodecId};
+use ffmpeg::codec::{Id as AvCodecId, Parameters as AvCodecParameters};
 use ffmpeg::format::flag::Flags as AvFormatFlags;
 use ffmpeg::software::scaling::context::Context as AvScaler;
 use ffmpeg::software::scaling::flag::Flags as AvScalerFlags;
@@ -13,6 +19,7 @@ use ffmpeg::util::error::EAGAIN;
 use ffmpeg::util::format::Pixel as AvPixel;
 use ffmpeg::util::mathematics::rescale::TIME_BASE;
 use ffmpeg::util::picture::Type as AvFrameType;
+use ffmpeg::Dictionary as AvDictionary;
 use ffmpeg::Error as AvError;
 use ffmpeg::Rational as AvRational;
 
@@ -20,12 +27,14 @@ use crate::error::Error;
 use crate::ffi;
 #[cfg(feature = "ndarray")]
 use crate::frame::Frame;
-use crate::frame::{PixelFormat, RawFrame, FRAME_PIXEL_FORMAT};
-use crate::io::private::Write;
-use crate::io::{Writer, WriterBuilder};
+use crate::frame::{FramePool, PixelFormat, RawFrame, VideoFrame, FRAME_PIXEL_FORMAT};
+use crate::io::private::{Output, Write};
+use crate::io::{Buf, BufWriter, BufWriterBuilder, Writer, WriterBuilder};
 use crate::location::Location;
 use crate::options::Options;
-#[cfg(feature = "ndarray")]
+use crate::packet::Packet;
+use crate::stream::StreamInfo;
+use crate::subtitle::{SubtitleCodec, SubtitleCue, SubtitleEncoder};
 use crate::time::Time;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -37,6 +46,9 @@ pub struct EncoderBuilder<'a> {
     options: Option<&'a Options>,
     format: Option<&'a str>,
     interleaved: bool,
+    input_pixel_format: Option<PixelFormat>,
+    input_size: Option<(u32, u32)>,
+    subtitle_codec: Option<SubtitleCodec>,
 }
 
 impl<'a> EncoderBuilder<'a> {
@@ -51,6 +63,9 @@ impl<'a> EncoderBuilder<'a> {
             options: None,
             format: None,
             interleaved: false,
+            input_pixel_format: None,
+            input_size: None,
+            subtitle_codec: None,
         }
     }
 
@@ -81,6 +96,48 @@ impl<'a> EncoderBuilder<'a> {
         self
     }
 
+    /// Add a subtitle stream to the output, next to the video stream. Cues are written to it with
+    /// [`Encoder::encode_subtitle`]. The codec must be supported by the container format, for
+    /// example [`SubtitleCodec::MovText`] for MP4 and [`SubtitleCodec::Srt`] for MKV.
+    ///
+    /// Since cues are usually not written in lockstep with the frames, the encoder should be
+    /// [`interleaved`](EncoderBuilder::interleaved) as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `codec` - Subtitle codec to use.
+    pub fn with_subtitle_stream(mut self, codec: SubtitleCodec) -> Self {
+        self.subtitle_codec = Some(codec);
+        self
+    }
+
+    /// Set the pixel format of the frames that will be passed to the encoder. By default, the
+    /// encoder expects RGB24 frames.
+    ///
+    /// If the input pixel format and size match the encoder settings, frames are passed to the
+    /// encoder as-is without any conversion.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixel_format` - Pixel format of input frames.
+    pub fn with_input_pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+        self.input_pixel_format = Some(pixel_format);
+        self
+    }
+
+    /// Set the size of the frames that will be passed to the encoder. By default, the encoder
+    /// expects frames with the size in the encoder settings. Frames with a different size are
+    /// resized to the size in the encoder settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of input frames.
+    /// * `height` - Height of input frames.
+    pub fn with_input_size(mut self, width: u32, height: u32) -> Self {
+        self.input_size = Some((width, height));
+        self
+    }
+
     /// Build an [`Encoder`].
     pub fn build(self) -> Result<Encoder> {
         let mut writer_builder = WriterBuilder::new(self.destination);
@@ -90,7 +147,14 @@ impl<'a> EncoderBuilder<'a> {
         if let Some(format) = self.format {
             writer_builder = writer_builder.with_format(format);
         }
-        Encoder::from_writer(writer_builder.build()?, self.interleaved, self.settings)
+        Encoder::from_writer(
+            writer_builder.build()?,
+            self.interleaved,
+            self.settings,
+            self.input_pixel_format.unwrap_or(FRAME_PIXEL_FORMAT),
+            self.input_size,
+            self.subtitle_codec,
+        )
     }
 }
 
@@ -117,14 +181,10 @@ impl<'a> EncoderBuilder<'a> {
 pub struct Encoder {
     writer: Writer,
     writer_stream_index: usize,
-    encoder: AvEncoder,
-    encoder_time_base: AvRational,
-    keyframe_interval: u64,
+    split: EncoderSplit,
+    subtitle: Option<(SubtitleEncoder, usize)>,
     interleaved: bool,
-    scaler: AvScaler,
-    scaler_width: u32,
-    scaler_height: u32,
-    frame_count: u64,
+    emitted_packets: Vec<PacketInfo>,
     have_written_header: bool,
     have_written_trailer: bool,
 }
@@ -143,68 +203,161 @@ impl Encoder {
     ///
     /// # Arguments
     ///
-    /// * `frame` - Frame to encode in `HWC` format and standard layout.
+    /// * `frame` - Frame to encode in `HWC` format and standard layout. Frames with a size other
+    ///   than the size in the encoder settings are resized.
     /// * `source_timestamp` - Frame timestamp of original source. This is necessary to make sure
     ///   the output will be timed correctly.
     #[cfg(feature = "ndarray")]
     pub fn encode(&mut self, frame: &Frame, source_timestamp: Time) -> Result<()> {
-        let (height, width, channels) = frame.dim();
-        if height != self.scaler_height as usize
-            || width != self.scaler_width as usize
-            || channels != 3
-        {
-            return Err(Error::InvalidFrameFormat);
-        }
-
-        let mut frame = ffi::convert_ndarray_to_frame_rgb24(frame).map_err(Error::BackendError)?;
+        let packets = self.split.encode(frame, source_timestamp)?;
+        self.write_packets(packets)
+    }
 
-        frame.set_pts(
-            source_timestamp
-                .aligned_with_rational(self.encoder_time_base)
-                .into_value(),
-        );
+    /// Encode a single [`VideoFrame`]. The frame PTS is used as timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode. See [`Encoder::encode_raw`] for which pixel formats and sizes
+    ///   are accepted.
+    pub fn encode_frame(&mut self, frame: VideoFrame) -> Result<()> {
+        self.encode_with(frame, EncodeFlags::default())
+    }
 
-        self.encode_raw(frame)
+    /// Encode a single [`VideoFrame`] with per-frame flags, for example to force a keyframe at a
+    /// segment boundary or when a new viewer joins a stream. The frame PTS is used as timestamp.
+    ///
+    /// Use [`Encoder::emitted_packets`] afterwards to find out which packets were written and
+    /// whether they are keyframes.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode. See [`Encoder::encode_raw`] for which pixel formats and sizes
+    ///   are accepted.
+    /// * `flags` - Flags for this frame.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// encoder.encode_with(frame, EncodeFlags { force_keyframe: true, ..Default::default() })?;
+    /// assert!(encoder.emitted_packets().iter().any(|packet| packet.is_key()));
+    /// ```
+    pub fn encode_with(&mut self, frame: VideoFrame, flags: EncodeFlags) -> Result<()> {
+        let packets = self.split.encode_with(frame, flags)?;
+        self.write_packets(packets)
     }
 
     /// Encode a single raw frame.
     ///
+    /// Frames are converted to the pixel format and size in the encoder settings. If they already
+    /// match, the frame is passed to the encoder as-is. The converter is set up for the input pixel
+    /// format and size declared on [`EncoderBuilder`], and rebuilt automatically whenever a frame
+    /// with another pixel format or size comes along.
+    ///
     /// # Arguments
     ///
     /// * `frame` - Frame to encode.
     pub fn encode_raw(&mut self, frame: RawFrame) -> Result<()> {
-        if frame.width() != self.scaler_width
-            || frame.height() != self.scaler_height
-            || frame.format() != FRAME_PIXEL_FORMAT
-        {
-            return Err(Error::InvalidFrameFormat);
-        }
+        let packets = self.split.encode_raw(frame)?;
+        self.write_packets(packets)
+    }
 
-        // Write file header if we hadn't done that yet.
-        if !self.have_written_header {
-            self.writer.write_header()?;
-            self.have_written_header = true;
-        }
+    /// Encode a single frame from separate planes, such as the Y, U and V planes of a YUV420P
+    /// frame. The planes must be in the pixel format of the encoder, which means no pixel format
+    /// conversion is needed, unlike [`Encoder::encode`] and [`Encoder::encode_raw`].
+    ///
+    /// # Arguments
+    ///
+    /// * `planes` - One array per plane of the encoder pixel format, each with dims `(H, W)`
+    ///   where `W` is the number of bytes per row. Note that chroma planes may be subsampled.
+    /// * `source_timestamp` - Frame timestamp of original source. This is necessary to make sure
+    ///   the output will be timed correctly.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let settings = Settings::preset_h264_yuv420p(1280, 720, false);
+    /// let mut encoder = Encoder::new(Path::new("video.mp4"), settings)?;
+    /// let y = Array2::<u8>::zeros((720, 1280));
+    /// let u = Array2::<u8>::from_elem((360, 640), 128);
+    /// let v = Array2::<u8>::from_elem((360, 640), 128);
+    /// encoder.encode_planar(&[y.view(), u.view(), v.view()], Time::zero())?;
+    /// ```
+    #[cfg(feature = "ndarray")]
+    pub fn encode_planar(
+        &mut self,
+        planes: &[ArrayView2<u8>],
+        source_timestamp: Time,
+    ) -> Result<()> {
+        let packets = self.split.encode_planar(planes, source_timestamp)?;
+        self.write_packets(packets)
+    }
 
-        // Reformat frame to target pixel format.
-        let mut frame = self.scale(frame)?;
-        // Producer key frame every once in a while
-        if self.frame_count.is_multiple_of(self.keyframe_interval) {
-            frame.set_kind(AvFrameType::I);
-        }
+    /// Change the rate control of the running encoder. See [`EncoderSplit::reconfigure`].
+    ///
+    /// # Arguments
+    ///
+    /// * `rate_control` - New rate control settings.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let settings = Settings::preset_h264_yuv420p(1280, 720, true)
+    ///     .with_rate_control(RateControl::Cbr(4_000_000));
+    /// let mut encoder = Encoder::new(Path::new("stream.ts"), settings)?;
+    /// // ...
+    /// encoder.reconfigure(RateControl::Cbr(1_500_000))?;
+    /// ```
+    pub fn reconfigure(&mut self, rate_control: RateControl) -> Result<()> {
+        self.split.reconfigure(rate_control)
+    }
 
-        self.encoder
-            .send_frame(&frame)
-            .map_err(Error::BackendError)?;
-        // Increment frame count regardless of whether or not frame is written, see
-        // https://github.com/oddity-ai/video-rs/issues/46.
-        self.frame_count += 1;
+    /// Switch the encoder to another output resolution without interrupting the stream. See
+    /// [`EncoderSplit::switch_resolution`]. Only container formats that carry the parameter sets
+    /// in-band, such as MPEG-TS, support this.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - New width.
+    /// * `height` - New height.
+    pub fn switch_resolution(&mut self, width: u32, height: u32) -> Result<()> {
+        let packets = self.split.switch_resolution(width, height)?;
+        self.write_packets(packets)
+    }
 
-        if let Some(packet) = self.encoder_receive_packet()? {
-            self.write(packet)?;
-        }
+    /// Encode a subtitle cue into the subtitle stream that was added with
+    /// [`EncoderBuilder::with_subtitle_stream`].
+    ///
+    /// # Arguments
+    ///
+    /// * `cue` - Cue to encode. The cue times are relative to the same start as the frame
+    ///   timestamps.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut encoder = EncoderBuilder::new(Path::new("video.mp4"), settings)
+    ///     .with_subtitle_stream(SubtitleCodec::MovText)
+    ///     .interleaved()
+    ///     .build()?;
+    /// encoder.encode_subtitle(&SubtitleCue::text(
+    ///     Time::from_secs(0.0),
+    ///     Time::from_secs(2.5),
+    ///     "Camera 1",
+    /// ))?;
+    /// ```
+    pub fn encode_subtitle(&mut self, cue: &SubtitleCue) -> Result<()> {
+        let (subtitle_encoder, subtitle_stream_index) =
+            self.subtitle.as_mut().ok_or(AvError::StreamNotFound)?;
+        let packet = subtitle_encoder.encode(cue)?;
+        let subtitle_stream_index = *subtitle_stream_index;
+        self.write_packets_to(subtitle_stream_index, vec![packet])
+    }
 
-        Ok(())
+    /// Packets that were emitted by the encoder during the last call to one of the encode
+    /// functions or [`Encoder::finish`]. Since encoders may buffer frames, this can be empty or
+    /// contain packets that belong to earlier frames.
+    pub fn emitted_packets(&self) -> &[PacketInfo] {
+        &self.emitted_packets
     }
 
     /// Signal to the encoder that writing has finished. This will cause any packets in the encoder
@@ -216,7 +369,8 @@ impl Encoder {
     pub fn finish(&mut self) -> Result<()> {
         if self.have_written_header && !self.have_written_trailer {
             self.have_written_trailer = true;
-            self.flush()?;
+            let packets = self.split.drain()?;
+            self.write_packets(packets)?;
             self.writer.write_trailer()?;
         }
 
@@ -226,7 +380,7 @@ impl Encoder {
     /// Get encoder time base.
     #[inline]
     pub fn time_base(&self) -> AvRational {
-        self.encoder_time_base
+        self.split.time_base()
     }
 
     /// Create an encoder from a [`Writer`].
@@ -236,257 +390,1842 @@ impl Encoder {
     /// * `writer` - [`Writer`] to create encoder from.
     /// * `interleaved` - Whether or not to use interleaved write.
     /// * `settings` - Encoder settings to use.
-    fn from_writer(mut writer: Writer, interleaved: bool, settings: Settings) -> Result<Self> {
-        let global_header = writer
-            .output
-            .format()
-            .flags()
-            .contains(AvFormatFlags::GLOBAL_HEADER);
-
-        let mut writer_stream = writer.output.add_stream(settings.codec())?;
-        let writer_stream_index = writer_stream.index();
-
-        let mut encoder_context = match settings.codec() {
-            Some(codec) => ffi::codec_context_as(&codec)?,
-            None => AvContext::new(),
-        };
-
-        // Some formats require this flag to be set or the output will
-        // not be playable by dumb players.
-        if global_header {
-            encoder_context.set_flags(AvCodecFlags::GLOBAL_HEADER);
-        }
-
-        let mut encoder = encoder_context.encoder().video()?;
-        settings.apply_to(&mut encoder);
-
-        // Just use the ffmpeg global time base which is precise enough
-        // that we should never get in trouble.
-        encoder.set_time_base(TIME_BASE);
-
-        let encoder = encoder.open_with(settings.options().to_dict())?;
-        let encoder_time_base = ffi::get_encoder_time_base(&encoder);
-
-        writer_stream.set_parameters(&encoder);
-
-        let scaler_width = encoder.width();
-        let scaler_height = encoder.height();
-        let scaler = AvScaler::get(
-            FRAME_PIXEL_FORMAT,
-            scaler_width,
-            scaler_height,
-            encoder.format(),
-            scaler_width,
-            scaler_height,
-            AvScalerFlags::empty(),
-        )?;
+    /// * `input_pixel_format` - Pixel format of input frames.
+    /// * `input_size` - Size of input frames, if different from the encoder settings.
+    /// * `subtitle_codec` - Codec of the subtitle stream, if any.
+    fn from_writer(
+        mut writer: Writer,
+        interleaved: bool,
+        settings: Settings,
+        input_pixel_format: AvPixel,
+        input_size: Option<(u32, u32)>,
+        subtitle_codec: Option<SubtitleCodec>,
+    ) -> Result<Self> {
+        let (split, writer_stream_index) =
+            split_with_stream(&mut writer, &settings, input_pixel_format, input_size)?;
+        let subtitle = subtitle_codec
+            .map(|codec| subtitle_encoder_with_stream(&mut writer, codec))
+            .transpose()?;
 
         Ok(Self {
             writer,
             writer_stream_index,
-            encoder,
-            encoder_time_base,
-            keyframe_interval: settings.keyframe_interval,
+            split,
+            subtitle,
             interleaved,
-            scaler,
-            scaler_width,
-            scaler_height,
-            frame_count: 0,
+            emitted_packets: Vec::new(),
             have_written_header: false,
             have_written_trailer: false,
         })
     }
 
-    /// Apply scaling (or pixel reformatting in this case) on the frame with the scaler we
-    /// initialized earlier.
+    /// Write encoded packets to output stream. This writes the file header first if that has not
+    /// been done yet.
     ///
     /// # Arguments
     ///
-    /// * `frame` - Frame to rescale.
-    fn scale(&mut self, frame: RawFrame) -> Result<RawFrame> {
-        let mut frame_scaled = RawFrame::empty();
-        self.scaler
-            .run(&frame, &mut frame_scaled)
-            .map_err(Error::BackendError)?;
-        // Copy over PTS from old frame.
-        frame_scaled.set_pts(frame.pts());
-
-        Ok(frame_scaled)
-    }
-
-    /// Pull an encoded packet from the decoder. This function also handles the possible `EAGAIN`
-    /// result, in which case we just need to go again.
-    fn encoder_receive_packet(&mut self) -> Result<Option<AvPacket>> {
-        let mut packet = AvPacket::empty();
-        let encode_result = self.encoder.receive_packet(&mut packet);
-        match encode_result {
-            Ok(()) => Ok(Some(packet)),
-            Err(AvError::Other { errno }) if errno == EAGAIN => Ok(None),
-            Err(err) => Err(err.into()),
-        }
+    /// * `packets` - Encoded packets.
+    fn write_packets(&mut self, packets: Vec<Packet>) -> Result<()> {
+        self.write_packets_to(self.writer_stream_index, packets)
     }
 
-    /// Acquire the time base of the output stream.
-    fn stream_time_base(&mut self) -> AvRational {
-        self.writer
-            .output
-            .stream(self.writer_stream_index)
-            .unwrap()
-            .time_base()
-    }
-
-    /// Write encoded packet to output stream.
+    /// Write encoded packets to an output stream. This writes the file header first if that has
+    /// not been done yet.
     ///
     /// # Arguments
     ///
-    /// * `packet` - Encoded packet.
-    fn write(&mut self, mut packet: AvPacket) -> Result<()> {
-        packet.set_stream(self.writer_stream_index);
-        packet.set_position(-1);
-        packet.rescale_ts(self.encoder_time_base, self.stream_time_base());
-        if self.interleaved {
-            self.writer.write_interleaved(&mut packet)?;
-        } else {
-            self.writer.write(&mut packet)?;
-        };
-
-        Ok(())
-    }
-
-    /// Flush the encoder, drain any packets that still need processing.
-    fn flush(&mut self) -> Result<()> {
-        // Maximum number of invocations to `encoder_receive_packet`
-        // to drain the items still on the queue before giving up.
-        const MAX_DRAIN_ITERATIONS: u32 = 100;
+    /// * `stream_index` - Index of the output stream.
+    /// * `packets` - Encoded packets.
+    fn write_packets_to(&mut self, stream_index: usize, packets: Vec<Packet>) -> Result<()> {
+        self.emitted_packets.clear();
 
-        // Notify the encoder that the last frame has been sent.
-        self.encoder.send_eof()?;
+        // Write file header if we hadn't done that yet.
+        if !self.have_written_header {
+            self.writer.write_header()?;
+            self.have_written_header = true;
+        }
 
-        // We need to drain the items still in the encoders queue.
-        for _ in 0..MAX_DRAIN_ITERATIONS {
-            match self.encoder_receive_packet() {
-                Ok(Some(packet)) => self.write(packet)?,
-                Ok(None) => continue,
-                Err(_) => break,
-            }
+        for packet in packets {
+            self.write(stream_index, packet)?;
         }
 
         Ok(())
     }
-}
 
-impl Drop for Encoder {
-    fn drop(&mut self) {
-        let _ = self.finish();
+    /// Write encoded packet to an output stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_index` - Index of the output stream.
+    /// * `packet` - Encoded packet.
+    fn write(&mut self, stream_index: usize, packet: Packet) -> Result<()> {
+        let (packet, time_base) = packet.into_inner_parts();
+        self.emitted_packets
+            .push(PacketInfo::new(&packet, time_base));
+        write_packet(
+            &mut self.writer,
+            stream_index,
+            self.interleaved,
+            packet,
+            time_base,
+        )
     }
 }
 
-/// Holds a logical combination of encoder settings.
-#[derive(Debug, Clone)]
-pub struct Settings {
-    width: u32,
-    height: u32,
-    pixel_format: AvPixel,
-    keyframe_interval: u64,
-    options: Options,
+/// Builds a [`BufEncoder`].
+pub struct BufEncoderBuilder<'a> {
+    format: &'a str,
+    settings: Settings,
+    options: Option<&'a Options>,
+    interleaved: bool,
+    input_pixel_format: Option<PixelFormat>,
+    input_size: Option<(u32, u32)>,
 }
 
-impl Settings {
-    /// Default keyframe interval.
-    const KEY_FRAME_INTERVAL: u64 = 12;
-
-    /// This is the assumed FPS for the encoder to use. Note that this does not need to be correct
-    /// exactly.
-    const FRAME_RATE: i32 = 30;
-
-    /// Create encoder settings for an H264 stream with YUV420p pixel format. This will encode to
-    /// arguably the most widely compatible video file since H264 is a common codec and YUV420p is
-    /// the most commonly used pixel format.
-    pub fn preset_h264_yuv420p(width: usize, height: usize, realtime: bool) -> Settings {
-        let options = if realtime {
-            Options::preset_h264_realtime()
-        } else {
-            Options::preset_h264()
-        };
-
+impl<'a> BufEncoderBuilder<'a> {
+    /// Create an encoder that writes to a buffer with the specified container format and
+    /// settings.
+    ///
+    /// * `format` - Container format to use.
+    /// * `settings` - Encoding settings.
+    pub fn new(format: &'a str, settings: Settings) -> Self {
         Self {
-            width: width as u32,
-            height: height as u32,
-            pixel_format: AvPixel::YUV420P,
-            keyframe_interval: Self::KEY_FRAME_INTERVAL,
-            options,
+            format,
+            settings,
+            options: None,
+            interleaved: false,
+            input_pixel_format: None,
+            input_size: None,
         }
     }
 
-    /// Create encoder settings for an H264 stream with a custom pixel format and options.
-    /// This allows for greater flexibility in encoding settings, enabling specific requirements
-    /// or optimizations to be set depending on the use case.
+    /// Set the output options for the encoder, for example
+    /// [`Options::preset_fragmented_mov`] to produce fragmented MP4.
     ///
     /// # Arguments
     ///
-    /// * `width` - The width of the video stream.
-    /// * `height` - The height of the video stream.
-    /// * `pixel_format` - The desired pixel format for the video stream.
-    /// * `options` - Custom H264 encoding options.
-    ///
-    /// # Return value
-    ///
-    /// A `Settings` instance with the specified configuration.+
-    pub fn preset_h264_custom(
-        width: usize,
-        height: usize,
-        pixel_format: PixelFormat,
-        options: Options,
-    ) -> Settings {
-        Self {
-            width: width as u32,
-            height: height as u32,
-            pixel_format,
-            keyframe_interval: Self::KEY_FRAME_INTERVAL,
-            options,
-        }
-    }
-
-    /// Set the keyframe interval.
-    pub fn set_keyframe_interval(&mut self, keyframe_interval: u64) {
-        self.keyframe_interval = keyframe_interval;
+    /// * `options` - The output options.
+    pub fn with_options(mut self, options: &'a Options) -> Self {
+        self.options = Some(options);
+        self
     }
 
-    /// Set the keyframe interval.
-    pub fn with_keyframe_interval(mut self, keyframe_interval: u64) -> Self {
-        self.set_keyframe_interval(keyframe_interval);
+    /// Set interleaved. This will cause the encoder to use interleaved write instead of normal
+    /// write.
+    pub fn interleaved(mut self) -> Self {
+        self.interleaved = true;
         self
     }
 
-    /// Apply the settings to an encoder.
+    /// Set the pixel format of the frames that will be passed to the encoder. See
+    /// [`EncoderBuilder::with_input_pixel_format`].
     ///
     /// # Arguments
     ///
-    /// * `encoder` - Encoder to apply settings to.
+    /// * `pixel_format` - Pixel format of input frames.
+    pub fn with_input_pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+        self.input_pixel_format = Some(pixel_format);
+        self
+    }
+
+    /// Set the size of the frames that will be passed to the encoder. See
+    /// [`EncoderBuilder::with_input_size`].
     ///
-    /// # Return value
+    /// # Arguments
     ///
-    /// New encoder with settings applied.
-    fn apply_to(&self, encoder: &mut AvVideo) {
-        encoder.set_width(self.width);
-        encoder.set_height(self.height);
-        encoder.set_format(self.pixel_format);
-        encoder.set_frame_rate(Some((Self::FRAME_RATE, 1)));
+    /// * `width` - Width of input frames.
+    /// * `height` - Height of input frames.
+    pub fn with_input_size(mut self, width: u32, height: u32) -> Self {
+        self.input_size = Some((width, height));
+        self
     }
 
-    /// Get codec.
-    fn codec(&self) -> Option<AvCodec> {
-        // Try to use the libx264 decoder. If it is not available, then use use whatever default
-        // h264 decoder we have.
-        Some(
-            ffmpeg::encoder::find_by_name("libx264")
-                .unwrap_or(ffmpeg::encoder::find(AvCodecId::H264)?),
-        )
+    /// Build a [`BufEncoder`].
+    pub fn build(self) -> Result<BufEncoder> {
+        let mut writer_builder = BufWriterBuilder::new(self.format);
+        if let Some(options) = self.options {
+            writer_builder = writer_builder.with_options(options);
+        }
+        let mut writer = writer_builder.build()?;
+        let (split, writer_stream_index) = split_with_stream(
+            &mut writer,
+            &self.settings,
+            self.input_pixel_format.unwrap_or(FRAME_PIXEL_FORMAT),
+            self.input_size,
+        )?;
+
+        Ok(BufEncoder {
+            writer,
+            writer_stream_index,
+            split,
+            interleaved: self.interleaved,
+            have_written_header: false,
+            have_written_trailer: false,
+        })
+    }
+}
+
+/// Encodes frames into an in-memory buffer. Every call returns the muxed bytes that were produced,
+/// so the output never touches disk.
+///
+/// Note that, unlike [`Encoder`], the encoder does not finish by itself when dropped, since the
+/// resulting bytes would be lost. Call [`BufEncoder::finish`] to get the remaining bytes.
+///
+/// # Example
+///
+/// ```ignore
+/// let settings = Settings::preset_h264_yuv420p(1280, 720, false);
+/// let options = Options::preset_fragmented_mov();
+/// let mut encoder = BufEncoderBuilder::new("mp4", settings)
+///     .with_options(&options)
+///     .build()?;
+/// let mut bytes = encoder.write_header()?;
+/// for frame in frames {
+///     bytes.extend(encoder.encode_frame(frame)?);
+/// }
+/// bytes.extend(encoder.finish()?);
+/// ```
+pub struct BufEncoder {
+    writer: BufWriter,
+    writer_stream_index: usize,
+    split: EncoderSplit,
+    interleaved: bool,
+    have_written_header: bool,
+    have_written_trailer: bool,
+}
+
+impl BufEncoder {
+    /// Create an encoder that writes to a buffer with the specified container format and
+    /// settings.
+    ///
+    /// * `format` - Container format to use.
+    /// * `settings` - Encoding settings.
+    #[inline]
+    pub fn new(format: &str, settings: Settings) -> Result<Self> {
+        BufEncoderBuilder::new(format, settings).build()
+    }
+
+    /// Write the container header and return its bytes. If this is not called explicitly, the
+    /// header bytes are returned by the first encode call instead.
+    pub fn write_header(&mut self) -> Result<Buf> {
+        if self.have_written_header {
+            return Ok(Buf::new());
+        }
+
+        let header = self.writer.write_header()?;
+        self.have_written_header = true;
+        Ok(header)
+    }
+
+    /// Encode a single `ndarray` frame and return the resulting bytes. See [`Encoder::encode`].
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode in `HWC` format and standard layout.
+    /// * `source_timestamp` - Frame timestamp of original source.
+    #[cfg(feature = "ndarray")]
+    pub fn encode(&mut self, frame: &Frame, source_timestamp: Time) -> Result<Buf> {
+        let packets = self.split.encode(frame, source_timestamp)?;
+        self.write_packets(packets)
+    }
+
+    /// Encode a single [`VideoFrame`] and return the resulting bytes. The frame PTS is used as
+    /// timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    pub fn encode_frame(&mut self, frame: VideoFrame) -> Result<Buf> {
+        self.encode_with(frame, EncodeFlags::default())
+    }
+
+    /// Encode a single [`VideoFrame`] with per-frame flags and return the resulting bytes. See
+    /// [`Encoder::encode_with`].
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    /// * `flags` - Flags for this frame.
+    pub fn encode_with(&mut self, frame: VideoFrame, flags: EncodeFlags) -> Result<Buf> {
+        let packets = self.split.encode_with(frame, flags)?;
+        self.write_packets(packets)
+    }
+
+    /// Encode a single raw frame and return the resulting bytes. See [`Encoder::encode_raw`].
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    pub fn encode_raw(&mut self, frame: RawFrame) -> Result<Buf> {
+        let packets = self.split.encode_raw(frame)?;
+        self.write_packets(packets)
+    }
+
+    /// Change the rate control of the running encoder. See [`EncoderSplit::reconfigure`].
+    ///
+    /// # Arguments
+    ///
+    /// * `rate_control` - New rate control settings.
+    pub fn reconfigure(&mut self, rate_control: RateControl) -> Result<()> {
+        self.split.reconfigure(rate_control)
+    }
+
+    /// Switch the encoder to another output resolution and return the bytes of the packets that
+    /// were still buffered. See [`EncoderSplit::switch_resolution`].
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - New width.
+    /// * `height` - New height.
+    pub fn switch_resolution(&mut self, width: u32, height: u32) -> Result<Buf> {
+        let packets = self.split.switch_resolution(width, height)?;
+        self.write_packets(packets)
+    }
+
+    /// Flush the encoder, write the trailer if the container format has one, and return the
+    /// resulting bytes.
+    pub fn finish(&mut self) -> Result<Buf> {
+        if self.have_written_header && !self.have_written_trailer {
+            self.have_written_trailer = true;
+            let packets = self.split.drain()?;
+            let mut buf = self.write_packets(packets)?;
+            buf.extend(self.writer.write_trailer()?);
+            Ok(buf)
+        } else {
+            Ok(Buf::new())
+        }
+    }
+
+    /// Get encoder time base.
+    #[inline]
+    pub fn time_base(&self) -> AvRational {
+        self.split.time_base()
+    }
+
+    /// Write encoded packets to the buffer, and the header before them if that has not been done
+    /// yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `packets` - Encoded packets.
+    fn write_packets(&mut self, packets: Vec<Packet>) -> Result<Buf> {
+        let mut buf = self.write_header()?;
+        for packet in packets {
+            let (packet, time_base) = packet.into_inner_parts();
+            buf.extend(write_packet(
+                &mut self.writer,
+                self.writer_stream_index,
+                self.interleaved,
+                packet,
+                time_base,
+            )?);
+        }
+
+        Ok(buf)
+    }
+}
+
+unsafe impl Send for BufEncoder {}
+unsafe impl Sync for BufEncoder {}
+
+/// Create a split encoder for the writer, and add an output stream for it to the writer.
+///
+/// # Arguments
+///
+/// * `writer` - Writer to add stream to.
+/// * `settings` - Encoder settings to use.
+/// * `input_pixel_format` - Pixel format of input frames.
+/// * `input_size` - Size of input frames, if different from the encoder settings.
+///
+/// # Return value
+///
+/// The split encoder and the index of the output stream.
+fn split_with_stream<W: Write + Output>(
+    writer: &mut W,
+    settings: &Settings,
+    input_pixel_format: AvPixel,
+    input_size: Option<(u32, u32)>,
+) -> Result<(EncoderSplit, usize)> {
+    // Some formats require this flag to be set or the output will
+    // not be playable by dumb players.
+    let global_header = writer
+        .output()
+        .format()
+        .flags()
+        .contains(AvFormatFlags::GLOBAL_HEADER);
+
+    let split =
+        EncoderSplit::from_settings(settings, global_header, input_pixel_format, input_size)?;
+
+    let mut writer_stream = writer.output_mut().add_stream(split.encoder.codec())?;
+    let writer_stream_index = writer_stream.index();
+    writer_stream.set_parameters(&split.encoder);
+    // Tag the stream with the frame rate so that players and muxers do not have to guess. The
+    // muxer may still pick another time base when writing the header.
+    writer_stream.set_time_base(split.time_base());
+    writer_stream.set_avg_frame_rate(settings.frame_rate);
+    if !settings.variable_frame_rate {
+        writer_stream.set_rate(settings.frame_rate);
+    }
+
+    Ok((split, writer_stream_index))
+}
+
+/// Create a subtitle encoder for the writer, and add an output stream for it to the writer.
+///
+/// # Arguments
+///
+/// * `writer` - Writer to add stream to.
+/// * `codec` - Subtitle codec to use.
+///
+/// # Return value
+///
+/// The subtitle encoder and the index of the output stream.
+fn subtitle_encoder_with_stream<W: Write + Output>(
+    writer: &mut W,
+    codec: SubtitleCodec,
+) -> Result<(SubtitleEncoder, usize)> {
+    let subtitle_encoder = SubtitleEncoder::new(codec)?;
+    let (_, codec_parameters, time_base) = subtitle_encoder.stream_info()?.into_parts();
+
+    let mut writer_stream = writer
+        .output_mut()
+        .add_stream(ffmpeg::encoder::find(codec_parameters.id()))?;
+    let writer_stream_index = writer_stream.index();
+    writer_stream.set_parameters(codec_parameters);
+    writer_stream.set_time_base(time_base);
+
+    let subtitle_encoder = subtitle_encoder.with_stream_index(writer_stream_index);
+
+    Ok((subtitle_encoder, writer_stream_index))
+}
+
+/// Write encoded packet to output stream.
+///
+/// # Arguments
+///
+/// * `writer` - Writer to write to.
+/// * `stream_index` - Index of the output stream.
+/// * `interleaved` - Whether or not to use interleaved write.
+/// * `packet` - Encoded packet.
+/// * `time_base` - Time base of the encoded packet.
+fn write_packet<W: Write + Output>(
+    writer: &mut W,
+    stream_index: usize,
+    interleaved: bool,
+    mut packet: AvPacket,
+    time_base: AvRational,
+) -> Result<W::Out> {
+    let stream_time_base = writer
+        .output()
+        .stream(stream_index)
+        .ok_or(AvError::StreamNotFound)?
+        .time_base();
+    packet.set_stream(stream_index);
+    packet.set_position(-1);
+    packet.rescale_ts(time_base, stream_time_base);
+    if interleaved {
+        writer.write_interleaved(&mut packet)
+    } else {
+        writer.write(&mut packet)
+    }
+}
+
+/// Encoder part of a split [`Encoder`] and [`Writer`]. Frames are encoded into [`Packet`]s, which
+/// can be fed to a [`Muxer`](crate::mux::Muxer), an [`RtpMuxer`](crate::rtp::RtpMuxer) or
+/// anything else.
+///
+/// Important note: Do not forget to drain the encoder after the last frame. It may still contain
+/// packets. Run `drain()` to get them.
+///
+/// # Example
+///
+/// ```ignore
+/// let settings = Settings::preset_h264_yuv420p(1280, 720, false);
+/// let mut encoder = EncoderSplit::new(settings, true)?;
+/// let mut muxer = MuxerBuilder::new(BufWriter::new("mp4")?)
+///     .with_stream(encoder.stream_info()?)?
+///     .build();
+/// for frame in frames {
+///     for packet in encoder.encode_frame(frame)? {
+///         muxer.mux(packet)?;
+///     }
+/// }
+/// for packet in encoder.drain()? {
+///     muxer.mux(packet)?;
+/// }
+/// muxer.finish()?;
+/// ```
+pub struct EncoderSplit {
+    encoder: AvEncoder,
+    encoder_time_base: AvRational,
+    settings: Settings,
+    family: EncoderFamily,
+    global_header: bool,
+    stream_index: usize,
+    keyframe_interval: u64,
+    scaler: Option<AvScaler>,
+    scaler_input: (AvPixel, (u32, u32)),
+    size: (u32, u32),
+    frame_pool: FramePool,
+    frame_count: u64,
+    last_keyframe_count: u64,
+    two_pass_stats: Option<TwoPassStats>,
+}
+
+impl EncoderSplit {
+    /// Create a split encoder with the specified settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Encoding settings.
+    /// * `global_header` - Whether or not to put the codec parameter sets in the stream
+    ///   information instead of in every keyframe. Container formats such as MP4 and MKV require
+    ///   this. Use `false` for formats that carry the parameter sets in-band, such as MPEG-TS.
+    pub fn new(settings: Settings, global_header: bool) -> Result<Self> {
+        Self::from_settings(&settings, global_header, FRAME_PIXEL_FORMAT, None)
+    }
+
+    /// Set the stream index of the encoded packets and stream information. Defaults to zero.
+    pub fn with_stream_index(mut self, stream_index: usize) -> Self {
+        self.stream_index = stream_index;
+        self
+    }
+
+    /// Get stream information of the encoded stream. This can be used to add the stream to a
+    /// [`Muxer`](crate::mux::Muxer).
+    pub fn stream_info(&self) -> Result<StreamInfo> {
+        StreamInfo::from_params(
+            AvCodecParameters::from(&self.encoder),
+            self.encoder_time_base,
+            self.stream_index,
+        )
+    }
+
+    /// Get encoder time base.
+    #[inline]
+    pub fn time_base(&self) -> AvRational {
+        self.encoder_time_base
+    }
+
+    /// Encode a single `ndarray` frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode in `HWC` format and standard layout. Frames with a size other
+    ///   than the size in the encoder settings are resized.
+    /// * `source_timestamp` - Frame timestamp of original source. This is necessary to make sure
+    ///   the output will be timed correctly.
+    #[cfg(feature = "ndarray")]
+    pub fn encode(&mut self, frame: &Frame, source_timestamp: Time) -> Result<Vec<Packet>> {
+        let (height, width, channels) = frame.dim();
+        if height == 0 || width == 0 || channels != 3 {
+            return Err(Error::InvalidFrameFormat);
+        }
+
+        let mut frame = ffi::convert_ndarray_to_frame_rgb24(frame).map_err(Error::BackendError)?;
+
+        frame.set_pts(
+            source_timestamp
+                .aligned_with_rational(self.encoder_time_base)
+                .into_value(),
+        );
+
+        self.encode_raw(frame)
+    }
+
+    /// Encode a single [`VideoFrame`]. The frame PTS is used as timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode. See [`Encoder::encode_raw`] for which pixel formats and sizes
+    ///   are accepted.
+    pub fn encode_frame(&mut self, frame: VideoFrame) -> Result<Vec<Packet>> {
+        self.encode_with(frame, EncodeFlags::default())
+    }
+
+    /// Encode a single [`VideoFrame`] with per-frame flags. See [`Encoder::encode_with`].
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    /// * `flags` - Flags for this frame.
+    pub fn encode_with(&mut self, frame: VideoFrame, flags: EncodeFlags) -> Result<Vec<Packet>> {
+        let timestamp = frame.pts();
+        let mut frame = frame.into_inner();
+        frame.set_pts(
+            timestamp
+                .aligned_with_rational(self.encoder_time_base)
+                .into_value(),
+        );
+
+        self.encode_raw_with(frame, flags)
+    }
+
+    /// Encode a single raw frame. See [`Encoder::encode_raw`].
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    pub fn encode_raw(&mut self, frame: RawFrame) -> Result<Vec<Packet>> {
+        self.encode_raw_with(frame, EncodeFlags::default())
+    }
+
+    /// Encode a single frame from separate planes. See [`Encoder::encode_planar`].
+    ///
+    /// # Arguments
+    ///
+    /// * `planes` - One array per plane of the encoder pixel format.
+    /// * `source_timestamp` - Frame timestamp of original source.
+    #[cfg(feature = "ndarray")]
+    pub fn encode_planar(
+        &mut self,
+        planes: &[ArrayView2<u8>],
+        source_timestamp: Time,
+    ) -> Result<Vec<Packet>> {
+        let mut frame = self.frame_pool.take_or_alloc();
+        let row_sizes = ffi::image_plane_row_sizes(frame.format(), frame.width())
+            .map_err(Error::BackendError)?;
+        if planes.len() != frame.planes() {
+            return Err(Error::InvalidFrameFormat);
+        }
+
+        for (index, plane) in planes.iter().enumerate() {
+            let plane_dims = (frame.plane_height(index) as usize, row_sizes[index]);
+            if plane.dim() != plane_dims {
+                return Err(Error::InvalidFrameFormat);
+            }
+            let stride = frame.stride(index);
+            ArrayViewMut2::from_shape(plane_dims.strides((stride, 1)), frame.data_mut(index))
+                .map_err(|_| Error::InvalidFrameFormat)?
+                .assign(plane);
+        }
+
+        frame.set_pts(
+            source_timestamp
+                .aligned_with_rational(self.encoder_time_base)
+                .into_value(),
+        );
+        // Frames from the pool may still carry the picture type of an earlier frame.
+        frame.set_kind(AvFrameType::None);
+
+        self.encode_scaled(frame, EncodeFlags::default())
+    }
+
+    /// Change the rate control of the running encoder, for example to follow the available
+    /// bandwidth of a live stream. The change applies from the next frame on.
+    ///
+    /// Only libx264 supports this, and only within the same rate control mode: the bitrates of
+    /// [`RateControl::Cbr`], [`RateControl::Abr`] and [`RateControl::Vbr`], and the CRF of
+    /// [`RateControl::Crf`] and [`RateControl::ConstrainedQuality`] can be changed. Anything else
+    /// results in [`Error::UnsupportedReconfiguration`].
+    ///
+    /// # Arguments
+    ///
+    /// * `rate_control` - New rate control settings.
+    pub fn reconfigure(&mut self, rate_control: RateControl) -> Result<()> {
+        let same_mode = self.settings.rate_control.is_some_and(|current| {
+            std::mem::discriminant(&current) == std::mem::discriminant(&rate_control)
+        });
+        if self.family != EncoderFamily::X264
+            || !same_mode
+            || matches!(rate_control, RateControl::Cqp(_))
+        {
+            return Err(Error::UnsupportedReconfiguration);
+        }
+
+        self.settings.rate_control = Some(rate_control);
+        self.settings
+            .apply_rate_control_to(&mut self.encoder, self.family);
+        // The libx264 wrapper compares the CRF option to the active CRF before every frame.
+        if let RateControl::Crf(crf) | RateControl::ConstrainedQuality { crf, .. } = rate_control {
+            ffi::set_encoder_private_option(&mut self.encoder, "crf", &crf.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Switch the encoder to another output resolution without interrupting the stream. The
+    /// encoder is flushed and reopened with the new size, so that the next frame is encoded as an
+    /// IDR frame with new parameter sets. Frame timestamps are not touched, so the timeline stays
+    /// continuous. Input frames are scaled to the new size from then on.
+    ///
+    /// This only works if the parameter sets are carried in-band, which means the encoder must not
+    /// use a global header. Two-pass encoding cannot be combined with switching either. In those
+    /// cases [`Error::UnsupportedReconfiguration`] is returned.
+    ///
+    /// Note that the encoder delay starts over after the switch. Use settings without B-frames,
+    /// like the realtime presets, to keep decoding timestamps strictly increasing.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - New width.
+    /// * `height` - New height.
+    ///
+    /// # Return value
+    ///
+    /// Packets that were still buffered in the encoder before the switch.
+    pub fn switch_resolution(&mut self, width: u32, height: u32) -> Result<Vec<Packet>> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidResizeParameters);
+        }
+        if self.global_header || self.settings.two_pass.is_some() {
+            return Err(Error::UnsupportedReconfiguration);
+        }
+
+        let packets = self.drain()?;
+
+        let settings = self.settings.clone().with_size(width, height);
+        // Creating a new encoder resets the frame count, which makes sure the first frame after
+        // the switch is a keyframe.
+        let (input_pixel_format, input_size) = self.scaler_input;
+        *self = Self::from_settings(&settings, false, input_pixel_format, Some(input_size))?
+            .with_stream_index(self.stream_index);
+
+        Ok(packets)
+    }
+
+    /// Signal to the encoder that the last frame has been sent, and drain any packets that still
+    /// need processing. The encoder cannot be used anymore after this.
+    pub fn drain(&mut self) -> Result<Vec<Packet>> {
+        // Maximum number of invocations to `encoder_receive_packet`
+        // to drain the items still on the queue before giving up.
+        const MAX_DRAIN_ITERATIONS: u32 = 100;
+
+        // Notify the encoder that the last frame has been sent.
+        self.encoder.send_eof()?;
+
+        // We need to drain the items still in the encoders queue.
+        let mut packets = Vec::new();
+        for _ in 0..MAX_DRAIN_ITERATIONS {
+            match self.encoder_receive_packet() {
+                Ok(Some(packet)) => {
+                    self.collect_two_pass_stats();
+                    packets.push(packet);
+                }
+                Ok(None) => continue,
+                Err(_) => break,
+            }
+        }
+
+        // The encoder adds its final statistics when it is flushed.
+        self.collect_two_pass_stats();
+        if let Some(two_pass_stats) = self.two_pass_stats.as_mut() {
+            two_pass_stats.finish()?;
+        }
+
+        Ok(packets)
+    }
+
+    /// Create a split encoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Encoder settings to use.
+    /// * `global_header` - Whether or not to use a global header.
+    /// * `input_pixel_format` - Pixel format of input frames.
+    /// * `input_size` - Size of input frames, if different from the encoder settings.
+    fn from_settings(
+        settings: &Settings,
+        global_header: bool,
+        input_pixel_format: AvPixel,
+        input_size: Option<(u32, u32)>,
+    ) -> Result<Self> {
+        let codec = settings.find_codec()?;
+        let mut encoder_context = ffi::codec_context_as(&codec)?;
+
+        let mut codec_flags = settings.codec_flags(&codec);
+        if global_header {
+            codec_flags |= AvCodecFlags::GLOBAL_HEADER;
+        }
+        encoder_context.set_flags(codec_flags);
+
+        let mut encoder = encoder_context.encoder().video()?;
+        settings.apply_to(&mut encoder, &codec);
+        encoder.set_time_base(settings.time_base());
+
+        // Encoders that do not manage the stats file themselves exchange statistics through the
+        // codec context, which means we have to write and read the stats file for them.
+        let mut two_pass_stats = match &settings.two_pass {
+            Some((pass, stats_path)) if !EncoderFamily::of(&codec).manages_stats_file() => {
+                Some(TwoPassStats::new(*pass, stats_path.clone())?)
+            }
+            _ => None,
+        };
+        if let Some(two_pass_stats) = two_pass_stats.as_mut() {
+            two_pass_stats.prepare(&mut encoder);
+        }
+
+        let encoder = encoder.open_with(settings.encoder_options(&codec))?;
+        let encoder_time_base = ffi::get_encoder_time_base(&encoder);
+
+        let size = (encoder.width(), encoder.height());
+        let scaler_input = (input_pixel_format, input_size.unwrap_or(size));
+        let scaler = Self::create_scaler(scaler_input, (encoder.format(), size))?;
+        let frame_pool = FramePool::new(encoder.format(), size.0, size.1);
+
+        Ok(Self {
+            encoder,
+            encoder_time_base,
+            settings: settings.clone(),
+            family: EncoderFamily::of(&codec),
+            global_header,
+            stream_index: 0,
+            keyframe_interval: settings.keyframe_interval,
+            scaler,
+            scaler_input,
+            size,
+            frame_pool,
+            frame_count: 0,
+            last_keyframe_count: 0,
+            two_pass_stats,
+        })
+    }
+
+    /// Encode a single raw frame with per-frame flags.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    /// * `flags` - Flags for this frame.
+    fn encode_raw_with(&mut self, frame: RawFrame, flags: EncodeFlags) -> Result<Vec<Packet>> {
+        if frame.width() == 0 || frame.height() == 0 || frame.format() == AvPixel::None {
+            return Err(Error::InvalidFrameFormat);
+        }
+
+        // Reformat frame to target pixel format and size.
+        let frame = self.scale(frame)?;
+
+        self.encode_scaled(frame, flags)
+    }
+
+    /// Encode a single frame that is already in the pixel format and size of the encoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    /// * `flags` - Flags for this frame.
+    fn encode_scaled(&mut self, mut frame: RawFrame, flags: EncodeFlags) -> Result<Vec<Packet>> {
+        // Producer key frame every once in a while, counting from the last keyframe so that a
+        // forced keyframe restarts the interval.
+        if flags.force_keyframe
+            || (self.frame_count - self.last_keyframe_count).is_multiple_of(self.keyframe_interval)
+        {
+            frame.set_kind(AvFrameType::I);
+            self.last_keyframe_count = self.frame_count;
+        }
+
+        self.encoder
+            .send_frame(&frame)
+            .map_err(Error::BackendError)?;
+        // The frame can be reused for the next frame after the encoder is done with it.
+        self.frame_pool.put(frame);
+        // Increment frame count regardless of whether or not frame is written, see
+        // https://github.com/oddity-ai/video-rs/issues/46.
+        self.frame_count += 1;
+
+        let mut packets = Vec::new();
+        while let Some(packet) = self.encoder_receive_packet()? {
+            self.collect_two_pass_stats();
+            packets.push(packet);
+        }
+
+        Ok(packets)
+    }
+
+    /// Collect the two-pass statistics of the last packet, if this is the first of two passes.
+    /// The encoder replaces its statistics with every packet it produces, so they must be
+    /// collected after each packet.
+    fn collect_two_pass_stats(&mut self) {
+        if let Some(two_pass_stats) = self.two_pass_stats.as_mut() {
+            two_pass_stats.collect(&mut self.encoder);
+        }
+    }
+
+    /// Create a scaler that converts frames to the pixel format and size of the encoder. If the
+    /// input pixel format and size are equal to those of the encoder, no scaler is needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Pixel format and size of input frames.
+    /// * `output` - Pixel format and size of the encoder.
+    fn create_scaler(
+        input: (AvPixel, (u32, u32)),
+        output: (AvPixel, (u32, u32)),
+    ) -> Result<Option<AvScaler>> {
+        let (input_format, (input_width, input_height)) = input;
+        let (output_format, (output_width, output_height)) = output;
+        if input == output {
+            return Ok(None);
+        }
+
+        // If only the pixel format needs to be converted, no scaling algorithm is necessary.
+        let flags = if input.1 == output.1 {
+            AvScalerFlags::empty()
+        } else {
+            AvScalerFlags::AREA
+        };
+
+        Ok(Some(AvScaler::get(
+            input_format,
+            input_width,
+            input_height,
+            output_format,
+            output_width,
+            output_height,
+            flags,
+        )?))
+    }
+
+    /// Apply scaling (or pixel reformatting) on the frame. The scaler is rebuilt if the pixel
+    /// format or size of the frame differs from the previous frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to rescale.
+    fn scale(&mut self, mut frame: RawFrame) -> Result<RawFrame> {
+        let frame_input = (frame.format(), (frame.width(), frame.height()));
+        if frame_input != self.scaler_input {
+            self.scaler = Self::create_scaler(frame_input, (self.encoder.format(), self.size))?;
+            self.scaler_input = frame_input;
+        }
+
+        let Some(scaler) = self.scaler.as_mut() else {
+            // Frames that come from a decoder carry their original picture type, which would
+            // otherwise be forced upon the encoder.
+            frame.set_kind(AvFrameType::None);
+            return Ok(frame);
+        };
+
+        let mut frame_scaled = self.frame_pool.take();
+        scaler
+            .run(&frame, &mut frame_scaled)
+            .map_err(Error::BackendError)?;
+        // Copy over PTS from old frame.
+        frame_scaled.set_pts(frame.pts());
+        // Frames from the pool may still carry the picture type of an earlier frame.
+        frame_scaled.set_kind(AvFrameType::None);
+
+        Ok(frame_scaled)
+    }
+
+    /// Pull an encoded packet from the decoder. This function also handles the possible `EAGAIN`
+    /// result, in which case we just need to go again.
+    fn encoder_receive_packet(&mut self) -> Result<Option<Packet>> {
+        let mut packet = AvPacket::empty();
+        let encode_result = self.encoder.receive_packet(&mut packet);
+        match encode_result {
+            Ok(()) => {
+                packet.set_stream(self.stream_index);
+                Ok(Some(Packet::new(packet, self.encoder_time_base)))
+            }
+            Err(AvError::Other { errno }) if errno == EAGAIN => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+unsafe impl Send for EncoderSplit {}
+unsafe impl Sync for EncoderSplit {}
+
+/// Handles the two-pass statistics for encoders that pass them through the codec context
+/// (`stats_out` and `stats_in`) instead of reading and writing the stats file themselves.
+struct TwoPassStats {
+    pass: Pass,
+    stats_path: PathBuf,
+    stats: String,
+    // Must outlive the encoder since the codec context points to it.
+    stats_in: Option<CString>,
+}
+
+impl TwoPassStats {
+    /// Set up two-pass statistics handling. For the second pass, this reads the statistics that
+    /// were written during the first pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `pass` - Current pass.
+    /// * `stats_path` - Path of stats file.
+    fn new(pass: Pass, stats_path: PathBuf) -> Result<Self> {
+        let stats_in = match pass {
+            Pass::First => None,
+            Pass::Second => {
+                let stats = std::fs::read(&stats_path).map_err(|_| Error::MissingTwoPassStats)?;
+                Some(CString::new(stats).map_err(|_| Error::MissingTwoPassStats)?)
+            }
+        };
+
+        Ok(Self {
+            pass,
+            stats_path,
+            stats: String::new(),
+            stats_in,
+        })
+    }
+
+    /// Pass the first pass statistics to the encoder before it is opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - Encoder that is not opened yet.
+    fn prepare(&mut self, encoder: &mut AvVideo) {
+        if let Some(stats_in) = self.stats_in.as_ref() {
+            ffi::set_encoder_stats_in(encoder, stats_in);
+        }
+    }
+
+    /// Collect the statistics that the encoder produced since the last call.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - Opened encoder.
+    fn collect(&mut self, encoder: &mut AvEncoder) {
+        if self.pass == Pass::First {
+            if let Some(stats_out) = ffi::take_encoder_stats_out(encoder) {
+                self.stats.push_str(&stats_out);
+            }
+        }
+    }
+
+    /// Write the collected statistics to the stats file after the first pass.
+    fn finish(&mut self) -> Result<()> {
+        if self.pass == Pass::First {
+            std::fs::write(&self.stats_path, self.stats.as_bytes())
+                .map_err(|_| Error::MissingTwoPassStats)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pass of a two-pass encode, see [`Settings::with_two_pass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// First pass, in which the encoder only analyzes the input and writes statistics.
+    First,
+    /// Second pass, in which the encoder uses the statistics of the first pass to distribute the
+    /// bitrate.
+    Second,
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Per-frame encoding flags, see [`Encoder::encode_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeFlags {
+    /// Force the frame to be encoded as keyframe (IDR frame for H264 and H265). The regular
+    /// keyframe interval restarts at this frame.
+    pub force_keyframe: bool,
+}
+
+/// Information about a packet that was emitted by the encoder.
+#[derive(Debug, Clone, Copy)]
+pub struct PacketInfo {
+    pts: Time,
+    dts: Time,
+    duration: Time,
+    size: usize,
+    is_key: bool,
+}
+
+impl PacketInfo {
+    /// Create packet information from an encoded packet.
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - Encoded packet.
+    /// * `time_base` - Time base of the packet timestamps.
+    fn new(packet: &AvPacket, time_base: AvRational) -> Self {
+        Self {
+            pts: Time::new(packet.pts(), time_base),
+            dts: Time::new(packet.dts(), time_base),
+            duration: Time::new(Some(packet.duration()), time_base),
+            size: packet.size(),
+            is_key: packet.is_key(),
+        }
+    }
+
+    /// Get packet PTS (presentation timestamp).
+    #[inline]
+    pub fn pts(&self) -> Time {
+        self.pts
+    }
+
+    /// Get packet DTS (decoder timestamp).
+    #[inline]
+    pub fn dts(&self) -> Time {
+        self.dts
+    }
+
+    /// Get packet duration.
+    #[inline]
+    pub fn duration(&self) -> Time {
+        self.duration
+    }
+
+    /// Get packet size in bytes.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether or not the packet is a keyframe.
+    #[inline]
+    pub fn is_key(&self) -> bool {
+        self.is_key
+    }
+}
+
+/// Video codecs that the encoder can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// H.264 (AVC).
+    H264,
+    /// H.265 (HEVC).
+    H265,
+    /// VP9.
+    Vp9,
+    /// AV1.
+    Av1,
+    /// Motion JPEG.
+    Mjpeg,
+    /// FFV1 (lossless).
+    Ffv1,
+    /// Apple ProRes.
+    ProRes,
+}
+
+impl Codec {
+    /// Names of the encoder implementations to try, in order of preference, before falling back
+    /// to whatever default encoder ffmpeg has for the codec.
+    fn preferred_encoder_names(&self) -> &'static [&'static str] {
+        match self {
+            Codec::H264 => &["libx264"],
+            Codec::H265 => &["libx265"],
+            Codec::Vp9 => &["libvpx-vp9"],
+            Codec::Av1 => &["libsvtav1", "libaom-av1", "librav1e"],
+            Codec::Mjpeg => &[],
+            Codec::Ffv1 => &[],
+            Codec::ProRes => &["prores_ks"],
+        }
+    }
+
+    /// Get the ffmpeg codec ID.
+    fn id(&self) -> AvCodecId {
+        match self {
+            Codec::H264 => AvCodecId::H264,
+            Codec::H265 => AvCodecId::HEVC,
+            Codec::Vp9 => AvCodecId::VP9,
+            Codec::Av1 => AvCodecId::AV1,
+            Codec::Mjpeg => AvCodecId::MJPEG,
+            Codec::Ffv1 => AvCodecId::FFV1,
+            Codec::ProRes => AvCodecId::PRORES,
+        }
+    }
+}
+
+/// Holds a logical combination of encoder settings.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    width: u32,
+    height: u32,
+    pixel_format: AvPixel,
+    keyframe_interval: u64,
+    frame_rate: AvRational,
+    variable_frame_rate: bool,
+    rate_control: Option<RateControl>,
+    gop: GopSettings,
+    two_pass: Option<(Pass, PathBuf)>,
+    options: Options,
+    codec: Codec,
+    codec_name: Option<String>,
+}
+
+impl Settings {
+    /// Conversion factor from quantization parameter to lambda, as in `FF_QP2LAMBDA`.
+    const QP2LAMBDA_FACTOR: i32 = 118;
+
+    /// Default keyframe interval.
+    const KEY_FRAME_INTERVAL: u64 = 12;
+
+    /// This is the assumed FPS for the encoder to use if no frame rate is set. Note that this does
+    /// not need to be correct exactly since the encoder uses variable frame rate by default.
+    const FRAME_RATE: i32 = 30;
+
+    /// Create encoder settings for an H264 stream with YUV420p pixel format. This will encode to
+    /// arguably the most widely compatible video file since H264 is a common codec and YUV420p is
+    /// the most commonly used pixel format.
+    pub fn preset_h264_yuv420p(width: usize, height: usize, realtime: bool) -> Settings {
+        let options = if realtime {
+            Options::preset_h264_realtime()
+        } else {
+            Options::preset_h264()
+        };
+
+        Self::preset_custom(Codec::H264, width, height, AvPixel::YUV420P, options)
+    }
+
+    /// Create encoder settings for an H264 stream with a custom pixel format and options.
+    /// This allows for greater flexibility in encoding settings, enabling specific requirements
+    /// or optimizations to be set depending on the use case.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the video stream.
+    /// * `height` - The height of the video stream.
+    /// * `pixel_format` - The desired pixel format for the video stream.
+    /// * `options` - Custom H264 encoding options.
+    ///
+    /// # Return value
+    ///
+    /// A `Settings` instance with the specified configuration.+
+    pub fn preset_h264_custom(
+        width: usize,
+        height: usize,
+        pixel_format: PixelFormat,
+        options: Options,
+    ) -> Settings {
+        Self::preset_custom(Codec::H264, width, height, pixel_format, options)
+    }
+
+    /// Create encoder settings for an H265 stream with YUV420p pixel format. H265 produces
+    /// considerably smaller files than H264 at the same quality, but is not as widely supported.
+    pub fn preset_h265_yuv420p(width: usize, height: usize, realtime: bool) -> Settings {
+        let options = if realtime {
+            Options::preset_h265_realtime()
+        } else {
+            Options::preset_h265()
+        };
+
+        Self::preset_custom(Codec::H265, width, height, AvPixel::YUV420P, options)
+    }
+
+    /// Create encoder settings for a VP9 stream with YUV420p pixel format. This is the most
+    /// common choice for the WebM container.
+    pub fn preset_vp9_yuv420p(width: usize, height: usize, realtime: bool) -> Settings {
+        let options = if realtime {
+            Options::preset_vp9_realtime()
+        } else {
+            Options::preset_vp9()
+        };
+
+        Self::preset_custom(Codec::Vp9, width, height, AvPixel::YUV420P, options)
+    }
+
+    /// Create encoder settings for an AV1 stream with YUV420p pixel format.
+    ///
+    /// The first available encoder out of `libsvtav1`, `libaom-av1` and `librav1e` is used. Use
+    /// [`Settings::with_codec_name`] to pick a specific one.
+    pub fn preset_av1_yuv420p(width: usize, height: usize) -> Settings {
+        Self::preset_custom(
+            Codec::Av1,
+            width,
+            height,
+            AvPixel::YUV420P,
+            Options::default(),
+        )
+    }
+
+    /// Create encoder settings for a Motion JPEG stream. Every frame is encoded as a separate
+    /// JPEG image, which makes the stream trivially seekable at the cost of file size.
+    pub fn preset_mjpeg(width: usize, height: usize) -> Settings {
+        Self::preset_custom(
+            Codec::Mjpeg,
+            width,
+            height,
+            AvPixel::YUVJ420P,
+            Options::default(),
+        )
+    }
+
+    /// Create encoder settings for a lossless FFV1 stream. FFV1 is commonly used for archival in
+    /// the Matroska container.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the video stream.
+    /// * `height` - The height of the video stream.
+    /// * `pixel_format` - The pixel format of the video stream. Use a planar RGB format such as
+    ///   `GBRP` to keep RGB input bit-exact.
+    pub fn preset_ffv1(width: usize, height: usize, pixel_format: PixelFormat) -> Settings {
+        Self::preset_custom(Codec::Ffv1, width, height, pixel_format, Options::default())
+    }
+
+    /// Create encoder settings for an Apple ProRes 422 stream with 10-bit YUV422p pixel format.
+    /// ProRes is an intermediate codec that is mostly used in the MOV container.
+    pub fn preset_prores(width: usize, height: usize) -> Settings {
+        Self::preset_custom(
+            Codec::ProRes,
+            width,
+            height,
+            AvPixel::YUV422P10LE,
+            Options::default(),
+        )
+    }
+
+    /// Create encoder settings for a lossless FFV1 stream with the GBRP pixel format. Since GBRP
+    /// is just a planar version of RGB24, RGB input frames survive the round trip bit-exactly.
+    /// Use the Matroska container for the output.
+    pub fn preset_lossless_ffv1(width: usize, height: usize) -> Settings {
+        Self::preset_custom(
+            Codec::Ffv1,
+            width,
+            height,
+            AvPixel::GBRP,
+            Options::preset_ffv1_archival(),
+        )
+    }
+
+    /// Create encoder settings for a lossless H264 stream (`qp=0`). This uses the `libx264rgb`
+    /// encoder with the RGB24 pixel format, so that RGB input frames survive the round trip
+    /// bit-exactly. Note that not all players support RGB H264, and that the MP4 container does
+    /// not support it. Use the Matroska container for the output.
+    pub fn preset_lossless_h264(width: usize, height: usize) -> Settings {
+        Self::preset_custom(
+            Codec::H264,
+            width,
+            height,
+            AvPixel::RGB24,
+            Options::preset_h264(),
+        )
+        .with_codec_name("libx264rgb")
+        .with_rate_control(RateControl::Cqp(0))
+    }
+
+    /// Create encoder settings for any supported codec with a custom pixel format and options.
+    ///
+    /// # Arguments
+    ///
+    /// * `codec` - The codec to encode with.
+    /// * `width` - The width of the video stream.
+    /// * `height` - The height of the video stream.
+    /// * `pixel_format` - The desired pixel format for the video stream.
+    /// * `options` - Custom encoding options.
+    pub fn preset_custom(
+        codec: Codec,
+        width: usize,
+        height: usize,
+        pixel_format: PixelFormat,
+        options: Options,
+    ) -> Settings {
+        Self {
+            width: width as u32,
+            height: height as u32,
+            pixel_format,
+            keyframe_interval: Self::KEY_FRAME_INTERVAL,
+            frame_rate: AvRational::new(Self::FRAME_RATE, 1),
+            variable_frame_rate: true,
+            rate_control: None,
+            gop: GopSettings::default(),
+            two_pass: None,
+            options,
+            codec,
+            codec_name: None,
+        }
+    }
+
+    /// Set a constant frame rate. The frame rate is a rational number, which means NTSC rates can
+    /// be expressed exactly, for example `(30000, 1001)` for 29.97 fps or `(60000, 1001)` for
+    /// 59.94 fps.
+    ///
+    /// With a constant frame rate, the encoder and stream time base is the inverse of the frame
+    /// rate. Frame timestamps are rounded to the nearest frame.
+    ///
+    /// A frame rate that is zero or negative has no inverse to use as time base. It is ignored,
+    /// and the encoder uses variable frame rate instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_rate` - Frame rate in frames per second.
+    pub fn set_frame_rate(&mut self, frame_rate: impl Into<AvRational>) {
+        let frame_rate = frame_rate.into();
+        if frame_rate.numerator() <= 0 || frame_rate.denominator() <= 0 {
+            self.variable_frame_rate = true;
+            return;
+        }
+        self.frame_rate = frame_rate;
+        self.variable_frame_rate = false;
+    }
+
+    /// Set a constant frame rate. See [`Settings::set_frame_rate`].
+    pub fn with_frame_rate(mut self, frame_rate: impl Into<AvRational>) -> Self {
+        self.set_frame_rate(frame_rate);
+        self
+    }
+
+    /// Set whether or not to use variable frame rate. With variable frame rate, frame timestamps
+    /// are kept at microsecond precision and the frame rate is only used as a hint for the
+    /// encoder rate control and the stream average frame rate. This is the default.
+    pub fn set_variable_frame_rate(&mut self, variable_frame_rate: bool) {
+        self.variable_frame_rate = variable_frame_rate;
+    }
+
+    /// Set whether or not to use variable frame rate. See [`Settings::set_variable_frame_rate`].
+    pub fn with_variable_frame_rate(mut self, variable_frame_rate: bool) -> Self {
+        self.set_variable_frame_rate(variable_frame_rate);
+        self
+    }
+
+    /// Get the (nominal) frame rate.
+    pub fn frame_rate(&self) -> AvRational {
+        self.frame_rate
+    }
+
+    /// Whether or not variable frame rate is used.
+    pub fn variable_frame_rate(&self) -> bool {
+        self.variable_frame_rate
+    }
+
+    /// Set the rate control mode. By default, the encoder uses its own default rate control.
+    pub fn set_rate_control(&mut self, rate_control: RateControl) {
+        self.rate_control = Some(rate_control);
+    }
+
+    /// Set the rate control mode. See [`Settings::set_rate_control`].
+    pub fn with_rate_control(mut self, rate_control: RateControl) -> Self {
+        self.set_rate_control(rate_control);
+        self
+    }
+
+    /// Set the group of pictures (GOP) settings.
+    pub fn set_gop(&mut self, gop: GopSettings) {
+        self.gop = gop;
+    }
+
+    /// Set the group of pictures (GOP) settings.
+    pub fn with_gop(mut self, gop: GopSettings) -> Self {
+        self.set_gop(gop);
+        self
+    }
+
+    /// Get the GOP settings.
+    pub fn gop(&self) -> &GopSettings {
+        &self.gop
+    }
+
+    /// Set up one pass of a two-pass encode. Both passes must use the same settings and the
+    /// same input frames, and only differ in the pass. The first pass writes statistics to the
+    /// stats file and its output can be discarded, the second pass reads them to distribute the
+    /// bitrate over the video. This is mostly useful with [`RateControl::Abr`].
+    ///
+    /// Note that some encoders (such as `libx265`) do not accept paths containing colons.
+    ///
+    /// # Arguments
+    ///
+    /// * `pass` - Pass to encode.
+    /// * `stats_path` - Path of the stats file that is shared between the passes.
+    pub fn with_two_pass(mut self, pass: Pass, stats_path: impl Into<PathBuf>) -> Self {
+        self.two_pass = Some((pass, stats_path.into()));
+        self
+    }
+
+    /// Use a specific encoder implementation by name, for example `libsvtav1` or `libopenh264`.
+    ///
+    /// Building the encoder fails with [`Error::UnsupportedCodec`] if the encoder is not
+    /// compiled into the ffmpeg build that is used, or if it encodes another codec than the codec
+    /// of the settings.
+    pub fn with_codec_name(mut self, codec_name: impl Into<String>) -> Self {
+        self.codec_name = Some(codec_name.into());
+        self
+    }
+
+    /// Get the codec.
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Set the keyframe interval.
+    pub fn set_keyframe_interval(&mut self, keyframe_interval: u64) {
+        self.keyframe_interval = keyframe_interval;
+    }
+
+    /// Set the keyframe interval.
+    pub fn with_keyframe_interval(mut self, keyframe_interval: u64) -> Self {
+        self.set_keyframe_interval(keyframe_interval);
+        self
+    }
+
+    /// Get the keyframe interval.
+    pub fn keyframe_interval(&self) -> u64 {
+        self.keyframe_interval
+    }
+
+    /// Set the output size.
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Set the output size.
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.set_size(width, height);
+        self
+    }
+
+    /// Get the output size as width and height.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Apply the settings to an encoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - Encoder to apply settings to.
+    ///
+    /// # Return value
+    ///
+    /// New encoder with settings applied.
+    fn apply_to(&self, encoder: &mut AvVideo, codec: &AvCodec) {
+        encoder.set_width(self.width);
+        encoder.set_height(self.height);
+        encoder.set_format(self.pixel_format);
+        encoder.set_frame_rate(Some(self.frame_rate));
+
+        self.apply_rate_control_to(encoder, EncoderFamily::of(codec));
+
+        if let Some(max_b_frames) = self.gop.max_b_frames {
+            encoder.set_max_b_frames(max_b_frames as usize);
+        }
+        if let Some(min_keyframe_interval) = self.gop.min_keyframe_interval {
+            ffi::set_encoder_keyint_min(encoder, min_keyframe_interval as i32);
+        }
+    }
+
+    /// Apply the rate control settings to an encoder. This only covers the fields of the codec
+    /// context. See [`Settings::encoder_options`] for the codec private options.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - Encoder to apply settings to.
+    /// * `family` - Encoder family.
+    fn apply_rate_control_to(&self, encoder: &mut AvVideo, family: EncoderFamily) {
+        match self.rate_control {
+            Some(RateControl::Crf(crf)) => match family {
+                // These encoders only use constant quality mode if there is no target bitrate.
+                EncoderFamily::Vpx | EncoderFamily::Aom => encoder.set_bit_rate(0),
+                EncoderFamily::Other => {
+                    encoder.set_global_quality((crf * Self::QP2LAMBDA_FACTOR as f32) as i32)
+                }
+                _ => {}
+            },
+            Some(RateControl::Cqp(qp)) => match family {
+                EncoderFamily::Vpx | EncoderFamily::Aom => {
+                    encoder.set_bit_rate(0);
+                    encoder.set_qmin(qp as i32);
+                    encoder.set_qmax(qp as i32);
+                }
+                EncoderFamily::Other => {
+                    encoder.set_global_quality(qp as i32 * Self::QP2LAMBDA_FACTOR)
+                }
+                _ => {}
+            },
+            Some(RateControl::Cbr(bitrate)) => {
+                encoder.set_bit_rate(bitrate as usize);
+                encoder.set_max_bit_rate(bitrate as usize);
+                ffi::set_encoder_rc_min_rate(encoder, bitrate as i64);
+                ffi::set_encoder_rc_buffer_size(encoder, bitrate as i32);
+            }
+            Some(RateControl::Abr(bitrate)) => {
+                encoder.set_bit_rate(bitrate as usize);
+            }
+            Some(RateControl::Vbr {
+                bitrate,
+                max_bitrate,
+                buffer_size,
+            }) => {
+                encoder.set_bit_rate(bitrate as usize);
+                encoder.set_max_bit_rate(max_bitrate as usize);
+                ffi::set_encoder_rc_buffer_size(encoder, buffer_size as i32);
+            }
+            Some(RateControl::ConstrainedQuality {
+                crf,
+                max_bitrate,
+                buffer_size,
+            }) => {
+                // The libvpx encoder uses the target bitrate as upper bound in constrained
+                // quality mode.
+                if family == EncoderFamily::Vpx {
+                    encoder.set_bit_rate(max_bitrate as usize);
+                }
+                if family == EncoderFamily::Other {
+                    encoder.set_global_quality((crf * Self::QP2LAMBDA_FACTOR as f32) as i32);
+                }
+                encoder.set_max_bit_rate(max_bitrate as usize);
+                ffi::set_encoder_rc_buffer_size(encoder, buffer_size as i32);
+            }
+            _ => {}
+        }
+    }
+
+    /// Get the time base for the encoder and stream.
+    fn time_base(&self) -> AvRational {
+        if self.variable_frame_rate {
+            // Just use the ffmpeg global time base which is precise enough that we should never
+            // get in trouble.
+            TIME_BASE
+        } else {
+            self.frame_rate.invert()
+        }
+    }
+
+    /// Get codec, and check that it supports the pixel format in the settings.
+    fn find_codec(&self) -> Result<AvCodec> {
+        let codec = match &self.codec_name {
+            Some(codec_name) => ffmpeg::encoder::find_by_name(codec_name)
+                // The rest of the settings, such as the options, are meant for the codec of the
+                // settings, so an encoder for another codec cannot be used.
+                .filter(|codec| codec.id() == self.codec.id())
+                .ok_or_else(|| {
+                    Error::UnsupportedCodec(format!("{codec_name} for {:?}", self.codec))
+                })?,
+            // Try to use the preferred encoders first. If none of them are available, then use
+            // whatever default encoder we have for the codec.
+            None => self
+                .codec
+                .preferred_encoder_names()
+                .iter()
+                .find_map(|codec_name| ffmpeg::encoder::find_by_name(codec_name))
+                .or_else(|| ffmpeg::encoder::find(self.codec.id()))
+                .ok_or_else(|| Error::UnsupportedCodec(format!("{:?}", self.codec)))?,
+        };
+
+        let supports_pixel_format = codec
+            .video()?
+            .formats()
+            .map(|mut formats| formats.any(|format| format == self.pixel_format))
+            // Encoders that do not list pixel formats will check the pixel format themselves.
+            .unwrap_or(true);
+        if !supports_pixel_format {
+            return Err(Error::UnsupportedCodecPixelFormat {
+                codec: codec.name().to_string(),
+                pixel_format: self.pixel_format,
+            });
+        }
+
+        Ok(codec)
+    }
+
+    /// Get codec flags that follow from the rate control and GOP settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `codec` - Codec that will be used.
+    fn codec_flags(&self, codec: &AvCodec) -> AvCodecFlags {
+        let mut flags = AvCodecFlags::empty();
+        if self.gop.closed {
+            flags |= AvCodecFlags::CLOSED_GOP;
+        }
+        match self.two_pass {
+            Some((Pass::First, _)) => flags |= AvCodecFlags::PASS1,
+            Some((Pass::Second, _)) => flags |= AvCodecFlags::PASS2,
+            None => {}
+        }
+        // Encoders without a private quality option use the generic fixed quality scale.
+        if EncoderFamily::of(codec) == EncoderFamily::Other {
+            if let Some(
+                RateControl::Crf(_) | RateControl::Cqp(_) | RateControl::ConstrainedQuality { .. },
+            ) = self.rate_control
+            {
+                flags |= AvCodecFlags::QSCALE;
+            }
+        }
+        flags
+    }
+
+    /// Get encoder options. These are the options in the settings, with the codec private options
+    /// for rate control and GOP settings added (overriding the same options if set explicitly).
+    ///
+    /// # Arguments
+    ///
+    /// * `codec` - Codec that will be used.
+    fn encoder_options(&self, codec: &AvCodec) -> AvDictionary<'_> {
+        let mut options = self.options.to_dict();
+        let family = EncoderFamily::of(codec);
+        let mut codec_params = Vec::new();
+
+        match (self.rate_control, family) {
+            (
+                Some(RateControl::Crf(crf) | RateControl::ConstrainedQuality { crf, .. }),
+                EncoderFamily::X264
+                | EncoderFamily::X265
+                | EncoderFamily::Vpx
+                | EncoderFamily::SvtAv1
+                | EncoderFamily::Aom,
+            ) => options.set("crf", &crf.to_string()),
+            (
+                Some(RateControl::Cqp(qp)),
+                EncoderFamily::X264 | EncoderFamily::X265 | EncoderFamily::SvtAv1,
+            ) => options.set("qp", &qp.to_string()),
+            // Constant quality mode with the quantizer range pinned to a single value.
+            (Some(RateControl::Cqp(qp)), EncoderFamily::Vpx | EncoderFamily::Aom) => {
+                options.set("crf", &qp.to_string())
+            }
+            (Some(RateControl::Cbr(_)), EncoderFamily::X264) => options.set("nal-hrd", "cbr"),
+            (Some(RateControl::Cbr(_)), EncoderFamily::X265) => {
+                codec_params.push("strict-cbr=1".to_string())
+            }
+            _ => {}
+        }
+
+        if self.gop.scene_cut == Some(false) {
+            match family {
+                EncoderFamily::X264 => options.set("sc_threshold", "0"),
+                EncoderFamily::X265 => codec_params.push("scenecut=0".to_string()),
+                EncoderFamily::SvtAv1 => codec_params.push("scd=0".to_string()),
+                _ => {}
+            }
+        }
+
+        if let Some((pass, stats_path)) = &self.two_pass {
+            let stats_path = stats_path.to_string_lossy();
+            match family {
+                EncoderFamily::X264 => options.set("stats", &stats_path),
+                EncoderFamily::X265 => {
+                    let pass = match pass {
+                        Pass::First => 1,
+                        Pass::Second => 2,
+                    };
+                    codec_params.push(format!("pass={pass}"));
+                    codec_params.push(format!("stats={stats_path}"));
+                }
+                _ => {}
+            }
+        }
+
+        // Make sure that forced keyframes are IDR frames, so that decoding can start there.
+        if matches!(family, EncoderFamily::X264 | EncoderFamily::X265)
+            && options.get("forced-idr").is_none()
+        {
+            options.set("forced-idr", "1");
+        }
+
+        if self.gop.closed && family == EncoderFamily::X265 {
+            // The libx265 wrapper does not look at the closed GOP flag.
+            codec_params.push("open-gop=0".to_string());
+        }
+
+        // Some encoders take additional parameters as a single colon-separated option.
+        let codec_params_key = match family {
+            EncoderFamily::X265 => Some("x265-params"),
+            EncoderFamily::SvtAv1 => Some("svtav1-params"),
+            _ => None,
+        };
+        if let Some(key) = codec_params_key.filter(|_| !codec_params.is_empty()) {
+            if let Some(existing) = options.get(key) {
+                codec_params.insert(0, existing.to_string());
+            }
+            options.set(key, &codec_params.join(":"));
+        }
+
+        options
+    }
+}
+
+/// Encoder implementations that need codec specific options for rate control and GOP settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EncoderFamily {
+    X264,
+    X265,
+    Vpx,
+    SvtAv1,
+    Aom,
+    Other,
+}
+
+impl EncoderFamily {
+    /// Determine encoder family by the name of the encoder.
+    fn of(codec: &AvCodec) -> Self {
+        match codec.name() {
+            "libx264" | "libx264rgb" => EncoderFamily::X264,
+            "libx265" => EncoderFamily::X265,
+            "libvpx" | "libvpx-vp9" => EncoderFamily::Vpx,
+            "libsvtav1" => EncoderFamily::SvtAv1,
+            "libaom-av1" => EncoderFamily::Aom,
+            _ => EncoderFamily::Other,
+        }
+    }
+
+    /// Whether or not the encoder reads and writes the two-pass stats file by itself.
+    fn manages_stats_file(&self) -> bool {
+        matches!(self, EncoderFamily::X264 | EncoderFamily::X265)
+    }
+}
+
+/// Rate control mode of the encoder.
+///
+/// Bitrates and buffer sizes are in bits (per second). Quality values are on the scale of the
+/// codec, where lower means better quality. For example, the default CRF for H264 is 23 and the
+/// range is 0 to 51.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateControl {
+    /// Constant rate factor, or constant perceptual quality.
+    Crf(f32),
+    /// Constant quantization parameter.
+    Cqp(u32),
+    /// Constant bitrate.
+    Cbr(u64),
+    /// Average bitrate, without constraints on the local bitrate. Most useful for two-pass
+    /// encoding.
+    Abr(u64),
+    /// Variable bitrate with a target bitrate, a maximum bitrate and a rate control buffer size.
+    Vbr {
+        bitrate: u64,
+        max_bitrate: u64,
+        buffer_size: u64,
+    },
+    /// Constant rate factor, but constrained by a maximum bitrate and rate control buffer size.
+    ConstrainedQuality {
+        crf: f32,
+        max_bitrate: u64,
+        buffer_size: u64,
+    },
+}
+
+/// Group of pictures (GOP) settings. The maximum keyframe interval is set with
+/// [`Settings::with_keyframe_interval`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GopSettings {
+    max_b_frames: Option<u32>,
+    closed: bool,
+    scene_cut: Option<bool>,
+    min_keyframe_interval: Option<u32>,
+}
+
+impl GopSettings {
+    /// Create GOP settings that leave the encoder defaults as they are.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of consecutive B-frames. Use zero to disable B-frames altogether,
+    /// which reduces latency.
+    pub fn with_max_b_frames(mut self, max_b_frames: u32) -> Self {
+        self.max_b_frames = Some(max_b_frames);
+        self
+    }
+
+    /// Set whether or not to use closed GOPs, in which frames never reference frames from the
+    /// previous GOP.
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
     }
 
-    /// Get encoder options.
-    fn options(&self) -> &Options {
-        &self.options
+    /// Set whether or not the encoder may insert keyframes on scene cuts.
+    pub fn with_scene_cut(mut self, scene_cut: bool) -> Self {
+        self.scene_cut = Some(scene_cut);
+        self
+    }
+
+    /// Set the minimum interval between keyframes (in frames).
+    pub fn with_min_keyframe_interval(mut self, min_keyframe_interval: u32) -> Self {
+        self.min_keyframe_interval = Some(min_keyframe_interval);
+        self
     }
 }
 
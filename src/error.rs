@@ -1,5 +1,6 @@
 extern crate ffmpeg_next as ffmpeg;
 
+use ffmpeg::util::format::Pixel as FfmpegPixel;
 use ffmpeg::Error as FfmpegError;
 
 /// Represents video I/O Errors. Some errors are generated by the ffmpeg backend, and are wrapped in
@@ -16,6 +17,23 @@ pub enum Error {
     InvalidResizeParameters,
     UninitializedCodec,
     UnsupportedCodecHardwareAccelerationDeviceType,
+    UnsupportedHardwareAccelerationDeviceType,
+    UnsupportedCodec(String),
+    MissingTwoPassStats,
+    UnsupportedCodecPixelFormat {
+        codec: String,
+        pixel_format: FfmpegPixel,
+    },
+    UnsupportedReconfiguration,
+    UnsupportedSubtitleContent,
+    InvalidTimeRange,
+    InvalidFrameRate,
+    UnsupportedInBandParameterSets,
+    IncompatibleInputs {
+        input: usize,
+        reason: String,
+    },
+    NoInputs,
     BackendError(FfmpegError),
 }
 
@@ -32,6 +50,17 @@ impl std::error::Error for Error {
             Error::InvalidResizeParameters => None,
             Error::UninitializedCodec => None,
             Error::UnsupportedCodecHardwareAccelerationDeviceType => None,
+            Error::UnsupportedHardwareAccelerationDeviceType => None,
+            Error::UnsupportedCodec(_) => None,
+            Error::MissingTwoPassStats => None,
+            Error::UnsupportedCodecPixelFormat { .. } => None,
+            Error::UnsupportedReconfiguration => None,
+            Error::UnsupportedSubtitleContent => None,
+            Error::InvalidTimeRange => None,
+            Error::InvalidFrameRate => None,
+            Error::UnsupportedInBandParameterSets => None,
+            Error::IncompatibleInputs { .. } => None,
+            Error::NoInputs => None,
             Error::BackendError(ref internal) => Some(internal),
         }
     }
@@ -64,6 +93,41 @@ impl std::fmt::Display for Error {
             Error::UnsupportedCodecHardwareAccelerationDeviceType => {
                 write!(f, "codec does not supported hardware acceleration device")
             }
+            Error::UnsupportedHardwareAccelerationDeviceType => {
+                write!(f, "hardware acceleration device type is not supported")
+            }
+            Error::UnsupportedCodec(ref codec) => {
+                write!(f, "encoder not available in this ffmpeg build: {codec}")
+            }
+            Error::MissingTwoPassStats => {
+                write!(f, "cannot read or write two-pass statistics file")
+            }
+            Error::UnsupportedCodecPixelFormat {
+                ref codec,
+                pixel_format,
+            } => write!(
+                f,
+                "encoder {codec} does not support pixel format: {pixel_format:?}"
+            ),
+            Error::UnsupportedReconfiguration => {
+                write!(f, "encoder cannot be reconfigured this way while running")
+            }
+            Error::UnsupportedSubtitleContent => {
+                write!(f, "subtitle encoder cannot encode this kind of cue")
+            }
+            Error::InvalidTimeRange => {
+                write!(f, "time range is empty or its start or end has no value")
+            }
+            Error::InvalidFrameRate => write!(f, "frame rate must be positive"),
+            Error::UnsupportedInBandParameterSets => write!(
+                f,
+                "output format cannot signal parameter sets inside the video stream"
+            ),
+            Error::IncompatibleInputs { input, ref reason } => write!(
+                f,
+                "input {input} cannot be concatenated with the first input: {reason}"
+            ),
+            Error::NoInputs => write!(f, "no inputs to concatenate"),
             Error::BackendError(ref internal) => internal.fmt(f),
         }
     }
@@ -1,3 +1,4 @@
+use crate::encode::Codec;
 use crate::error::Error;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -143,3 +144,224 @@ fn find_avc_start_code(bytes: &[u8], offset: usize) -> Option<(usize, usize)> {
         None
     }
 }
+
+/// How NAL units are delimited in H.264 and H.265 packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NalFraming {
+    /// Every NAL unit is preceded by a start code (Annex B), as in MPEG-TS.
+    AnnexB,
+    /// Every NAL unit is preceded by its size as a big endian number of the given number of bytes,
+    /// as in MP4.
+    LengthPrefixed(usize),
+}
+
+/// Determine the NAL framing and extract all parameter sets (VPS, SPS and PPS) from H.264 or H.265
+/// extradata. Extradata in `avcC` or `hvcC` format means the packets are length prefixed, other
+/// extradata means the packets use start codes.
+///
+/// # Arguments
+///
+/// * `codec` - Codec of the stream, either H.264 or H.265.
+/// * `bytes` - Extradata bytes.
+///
+/// # Return value
+///
+/// NAL framing of the packets and the parameter set NAL units in order.
+pub(crate) fn extract_nal_framing_and_parameter_sets(
+    codec: Codec,
+    bytes: &[u8],
+) -> Result<(NalFraming, Vec<&[u8]>)> {
+    match (codec, bytes.first()) {
+        (Codec::H264, Some(0x01)) => {
+            let (sps, pps) = extract_parameter_sets_from_extradata_h264_avcc(bytes)?;
+            let length_size = (bytes[4] & 0x03) as usize + 1;
+            Ok((
+                NalFraming::LengthPrefixed(length_size),
+                std::iter::once(sps).chain(pps).collect(),
+            ))
+        }
+        (Codec::H265, Some(0x01)) => extract_parameter_sets_from_extradata_hevc_hvcc(bytes),
+        (Codec::H264 | Codec::H265, _) => {
+            let parameter_sets = split_nal_units(bytes, NalFraming::AnnexB)?
+                .into_iter()
+                .filter(|nal| is_parameter_set(codec, nal))
+                .collect();
+            Ok((NalFraming::AnnexB, parameter_sets))
+        }
+        _ => Err(Error::UnsupportedCodecParameterSets),
+    }
+}
+
+/// Split H.264 or H.265 packet data into NAL units.
+///
+/// # Arguments
+///
+/// * `bytes` - Packet data.
+/// * `framing` - How the NAL units are delimited.
+pub(crate) fn split_nal_units(bytes: &[u8], framing: NalFraming) -> Result<Vec<&[u8]>> {
+    let mut nals = Vec::new();
+    match framing {
+        NalFraming::AnnexB => {
+            let mut index_current = find_avc_start_code(bytes, 0).map(|(_, index_next)| index_next);
+            while let Some(index) = index_current {
+                let (end, index_next) = match find_avc_start_code(bytes, index) {
+                    Some((end, index_next)) => (end, Some(index_next)),
+                    None => (bytes.len(), None),
+                };
+                if end > index {
+                    nals.push(&bytes[index..end]);
+                }
+                index_current = index_next;
+            }
+        }
+        NalFraming::LengthPrefixed(length_size) => {
+            let mut rest = bytes;
+            while !rest.is_empty() {
+                if rest.len() < length_size {
+                    return Err(Error::InvalidExtraData);
+                }
+                let size = rest[..length_size]
+                    .iter()
+                    .fold(0usize, |size, byte| (size << 8) | *byte as usize);
+                let nal = rest
+                    .get(length_size..length_size + size)
+                    .ok_or(Error::InvalidExtraData)?;
+                nals.push(nal);
+                rest = &rest[length_size + size..];
+            }
+        }
+    }
+    Ok(nals)
+}
+
+/// Join NAL units into H.264 or H.265 packet data.
+///
+/// # Arguments
+///
+/// * `nals` - NAL units.
+/// * `framing` - How to delimit the NAL units.
+pub(crate) fn join_nal_units<'a>(
+    nals: impl IntoIterator<Item = &'a [u8]>,
+    framing: NalFraming,
+) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for nal in nals {
+        match framing {
+            NalFraming::AnnexB => bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]),
+            NalFraming::LengthPrefixed(length_size) => {
+                bytes.extend_from_slice(&(nal.len() as u64).to_be_bytes()[8 - length_size..])
+            }
+        }
+        bytes.extend_from_slice(nal);
+    }
+    bytes
+}
+
+/// Extract parameter sets from H.265 stream extradata in `hvcC` format, which is used in
+/// combination with the MP4 container format. The VPS, SPS and PPS arrays are stored after a 22
+/// byte header.
+fn extract_parameter_sets_from_extradata_hevc_hvcc(
+    bytes: &[u8],
+) -> Result<(NalFraming, Vec<&[u8]>)> {
+    if bytes.len() < 23 {
+        return Err(Error::InvalidExtraData);
+    }
+    let length_size = (bytes[21] & 0x03) as usize + 1;
+    let mut parameter_sets = Vec::new();
+    let mut offset = 23;
+    for _ in 0..bytes[22] {
+        let header = bytes
+            .get(offset..offset + 3)
+            .ok_or(Error::InvalidExtraData)?;
+        let nal_count = u16::from_be_bytes([header[1], header[2]]);
+        offset += 3;
+        for _ in 0..nal_count {
+            let size = bytes
+                .get(offset..offset + 2)
+                .ok_or(Error::InvalidExtraData)?;
+            let size = u16::from_be_bytes([size[0], size[1]]) as usize;
+            let nal = bytes
+                .get(offset + 2..offset + 2 + size)
+                .ok_or(Error::InvalidExtraData)?;
+            if is_parameter_set(Codec::H265, nal) {
+                parameter_sets.push(nal);
+            }
+            offset += 2 + size;
+        }
+    }
+    Ok((NalFraming::LengthPrefixed(length_size), parameter_sets))
+}
+
+/// Whether a NAL unit is a parameter set: an SPS or PPS for H.264, or a VPS, SPS or PPS for H.265.
+///
+/// # Arguments
+///
+/// * `codec` - Codec of the stream.
+/// * `nal` - NAL unit, starting with its header.
+fn is_parameter_set(codec: Codec, nal: &[u8]) -> bool {
+    match (codec, nal.first()) {
+        (Codec::H264, Some(header)) => matches!(header & 0x1f, 0x07 | 0x08),
+        (Codec::H265, Some(header)) => matches!((header >> 1) & 0x3f, 32..=34),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reframes_nal_units() {
+        let annex_b = [
+            0, 0, 0, 1, 0x67, 1, 2, 0, 0, 1, 0x68, 3, 0, 0, 0, 1, 0x65, 4, 5,
+        ];
+        let nals = split_nal_units(&annex_b, NalFraming::AnnexB).unwrap();
+        assert_eq!(nals, vec![&[0x67, 1, 2][..], &[0x68, 3], &[0x65, 4, 5]]);
+
+        let length_prefixed = join_nal_units(nals.clone(), NalFraming::LengthPrefixed(4));
+        assert_eq!(
+            length_prefixed,
+            [0, 0, 0, 3, 0x67, 1, 2, 0, 0, 0, 2, 0x68, 3, 0, 0, 0, 3, 0x65, 4, 5]
+        );
+        assert_eq!(
+            split_nal_units(&length_prefixed, NalFraming::LengthPrefixed(4)).unwrap(),
+            nals
+        );
+        assert!(split_nal_units(&[0, 0, 9, 1], NalFraming::LengthPrefixed(2)).is_err());
+    }
+
+    #[test]
+    fn extracts_h264_parameter_sets() {
+        let avcc = [
+            0x01, 0x64, 0x00, 0x1f, 0xff, 0xe1, 0x00, 0x03, 0x67, 0x64, 0x00, 0x01, 0x00, 0x02,
+            0x68, 0xee,
+        ];
+        let (framing, parameter_sets) =
+            extract_nal_framing_and_parameter_sets(Codec::H264, &avcc).unwrap();
+        assert_eq!(framing, NalFraming::LengthPrefixed(4));
+        assert_eq!(parameter_sets, vec![&[0x67, 0x64, 0x00][..], &[0x68, 0xee]]);
+
+        let annex_b = [
+            0, 0, 0, 1, 0x67, 0x64, 0, 0, 0, 1, 0x68, 0xee, 0, 0, 1, 0x06, 0x05,
+        ];
+        let (framing, parameter_sets) =
+            extract_nal_framing_and_parameter_sets(Codec::H264, &annex_b).unwrap();
+        assert_eq!(framing, NalFraming::AnnexB);
+        assert_eq!(parameter_sets, vec![&[0x67, 0x64][..], &[0x68, 0xee]]);
+    }
+
+    #[test]
+    fn extracts_hevc_parameter_sets() {
+        let mut hvcc = vec![0x01];
+        hvcc.extend_from_slice(&[0; 20]);
+        hvcc.push(0x03); // 4 byte NAL unit lengths.
+        hvcc.push(2); // Number of arrays.
+        hvcc.extend_from_slice(&[0x20, 0x00, 0x01, 0x00, 0x02, 0x40, 0x01]); // VPS.
+        hvcc.extend_from_slice(&[0x27, 0x00, 0x01, 0x00, 0x02, 0x4e, 0x01]); // SEI.
+        let (framing, parameter_sets) =
+            extract_nal_framing_and_parameter_sets(Codec::H265, &hvcc).unwrap();
+        assert_eq!(framing, NalFraming::LengthPrefixed(4));
+        assert_eq!(parameter_sets, vec![&[0x40, 0x01][..]]);
+        assert!(extract_nal_framing_and_parameter_sets(Codec::Vp9, &hvcc).is_err());
+    }
+}
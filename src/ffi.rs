@@ -1,16 +1,18 @@
 extern crate ffmpeg_next as ffmpeg;
 
 #[cfg(feature = "ndarray")]
-use ndarray::Array3;
+use ndarray::{Array3, ArrayView3, ShapeBuilder};
 
 use ffmpeg::codec::codec::Codec;
 use ffmpeg::codec::context::Context;
+use ffmpeg::codec::parameters::Parameters;
+use ffmpeg::codec::subtitle::{Bitmap as SubtitleBitmap, Subtitle};
+use ffmpeg::encoder::subtitle::Encoder as SubtitleEncoder;
 use ffmpeg::encoder::video::Video;
-use ffmpeg::format::context::Output;
+use ffmpeg::format::context::{Input, Output};
 use ffmpeg::util::frame::video::Video as Frame;
 use ffmpeg::{Error, Rational};
 
-#[cfg(feature = "ndarray")]
 use ffmpeg::util::format::Pixel;
 
 use ffmpeg::ffi::*;
@@ -243,6 +245,319 @@ pub fn get_encoder_time_base(encoder: &Video) -> Rational {
     unsafe { (*encoder.0.as_ptr()).time_base.into() }
 }
 
+/// Set the `rc_buffer_size` field of an encoder. (Not natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `encoder` - Encoder to set `rc_buffer_size` of.
+/// * `rc_buffer_size` - Rate control buffer size in bits.
+pub fn set_encoder_rc_buffer_size(encoder: &mut Video, rc_buffer_size: i32) {
+    unsafe {
+        (*encoder.as_mut_ptr()).rc_buffer_size = rc_buffer_size;
+    }
+}
+
+/// Set the `rc_min_rate` field of an encoder. (Not natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `encoder` - Encoder to set `rc_min_rate` of.
+/// * `rc_min_rate` - Minimum bitrate in bits per second.
+pub fn set_encoder_rc_min_rate(encoder: &mut Video, rc_min_rate: i64) {
+    unsafe {
+        (*encoder.as_mut_ptr()).rc_min_rate = rc_min_rate;
+    }
+}
+
+/// Set the `keyint_min` field of an encoder. (Not natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `encoder` - Encoder to set `keyint_min` of.
+/// * `keyint_min` - Minimum GOP size.
+pub fn set_encoder_keyint_min(encoder: &mut Video, keyint_min: i32) {
+    unsafe {
+        (*encoder.as_mut_ptr()).keyint_min = keyint_min;
+    }
+}
+
+/// Set a private option of an opened encoder, such as the `crf` option of libx264. Only encoders
+/// that look at their options for every frame will pick up the change.
+///
+/// # Arguments
+///
+/// * `encoder` - Encoder to set private option of.
+/// * `name` - Option name.
+/// * `value` - Option value.
+pub fn set_encoder_private_option(
+    encoder: &mut Video,
+    name: &str,
+    value: &str,
+) -> Result<(), Error> {
+    let name = std::ffi::CString::new(name).map_err(|_| Error::OptionNotFound)?;
+    let value = std::ffi::CString::new(value).map_err(|_| Error::InvalidData)?;
+    unsafe {
+        match av_opt_set(
+            (*encoder.as_mut_ptr()).priv_data,
+            name.as_ptr(),
+            value.as_ptr(),
+            0,
+        ) {
+            0 => Ok(()),
+            e => Err(Error::from(e)),
+        }
+    }
+}
+
+/// Set a private option of an output format, such as the `use_editlist` option of the MP4 muxer.
+/// Must be called before the header is written. (Not natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `output` - Output to set private option of.
+/// * `name` - Option name.
+/// * `value` - Option value.
+pub fn set_output_private_option(
+    output: &mut Output,
+    name: &str,
+    value: &str,
+) -> Result<(), Error> {
+    let name = std::ffi::CString::new(name).map_err(|_| Error::OptionNotFound)?;
+    let value = std::ffi::CString::new(value).map_err(|_| Error::InvalidData)?;
+    unsafe {
+        let priv_data = (*output.as_mut_ptr()).priv_data;
+        if priv_data.is_null() {
+            return Err(Error::OptionNotFound);
+        }
+        match av_opt_set(priv_data, name.as_ptr(), value.as_ptr(), 0) {
+            0 => Ok(()),
+            e => Err(Error::from(e)),
+        }
+    }
+}
+
+/// Set the codec tag of an output stream, such as `avc3` for H.264 in MP4, if the output format
+/// accepts the tag for the codec of the stream. Must be called before the header is written. (Not
+/// natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `output` - Output that contains the stream.
+/// * `stream_index` - Index of stream.
+/// * `tag` - Codec tag as four characters.
+///
+/// # Return value
+///
+/// Whether the output format accepts the tag. If not, the stream is left alone.
+pub fn set_output_codec_tag(
+    output: &mut Output,
+    stream_index: usize,
+    tag: [u8; 4],
+) -> Result<bool, Error> {
+    let tag = u32::from_le_bytes(tag);
+    unsafe {
+        let context = output.as_mut_ptr();
+        if stream_index >= (*context).nb_streams as usize {
+            return Err(Error::StreamNotFound);
+        }
+        let parameters = (**(*context).streams.add(stream_index)).codecpar;
+        let codec_tags = (*(*context).oformat).codec_tag;
+        if codec_tags.is_null() || av_codec_get_id(codec_tags, tag) != (*parameters).codec_id {
+            return Ok(false);
+        }
+        (*parameters).codec_tag = tag;
+    }
+    Ok(true)
+}
+
+/// Take the contents of the `stats_out` field of an encoder, which holds the two-pass statistics
+/// that the encoder produced. (Not natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `encoder` - Encoder to take `stats_out` of.
+pub fn take_encoder_stats_out(encoder: &mut Video) -> Option<String> {
+    unsafe {
+        let stats_out = (*encoder.as_mut_ptr()).stats_out;
+        if stats_out.is_null() || *stats_out == 0 {
+            return None;
+        }
+        let stats = std::ffi::CStr::from_ptr(stats_out)
+            .to_string_lossy()
+            .into_owned();
+        // Mark the statistics as consumed so that they are not taken twice. The buffer itself is
+        // owned by the encoder.
+        *stats_out = 0;
+        Some(stats)
+    }
+}
+
+/// Set the `stats_in` field of an encoder. (Not natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `encoder` - Encoder to set `stats_in` of.
+/// * `stats_in` - Two-pass statistics. The caller must make sure it outlives the encoder.
+pub fn set_encoder_stats_in(encoder: &mut Video, stats_in: &std::ffi::CStr) {
+    unsafe {
+        (*encoder.as_mut_ptr()).stats_in = stats_in.as_ptr() as *mut _;
+    }
+}
+
+/// Get the pixel format, width and height of the frames that come out of a buffer sink. (Not
+/// natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `sink` - Buffer sink filter context of a configured filter graph.
+#[cfg(feature = "filter")]
+pub fn filter_sink_properties(sink: &ffmpeg::filter::Context) -> (Pixel, u32, u32) {
+    unsafe {
+        let sink = sink.as_ptr() as *mut AVFilterContext;
+        let format =
+            std::mem::transmute::<std::ffi::c_int, AVPixelFormat>(av_buffersink_get_format(sink));
+        (
+            Pixel::from(format),
+            av_buffersink_get_w(sink) as u32,
+            av_buffersink_get_h(sink) as u32,
+        )
+    }
+}
+
+/// Set the `time_base` field of an encoder context before it is opened. (Not natively supported in
+/// the public API for subtitle encoders.)
+///
+/// # Arguments
+///
+/// * `encoder_context` - Encoder context.
+/// * `time_base` - Time base to assign.
+pub fn set_encoder_context_time_base(encoder_context: &mut Context, time_base: Rational) {
+    unsafe {
+        (*encoder_context.as_mut_ptr()).time_base = time_base.into();
+    }
+}
+
+/// Set the `subtitle_header` field of a codec context, which holds the ASS script header that text
+/// subtitle encoders require. (Not natively supported in the public API.)
+///
+/// # Arguments
+///
+/// * `context` - Codec context.
+/// * `header` - Subtitle header.
+pub fn set_codec_context_subtitle_header(context: &mut Context, header: &str) -> Result<(), Error> {
+    unsafe {
+        let context = context.as_mut_ptr();
+        // The codec context owns the header and frees it with `av_free`, so it must be allocated
+        // with `av_malloc`. The header is NUL-terminated, but the size excludes the terminator.
+        let buf = av_malloc(header.len() + 1) as *mut u8;
+        if buf.is_null() {
+            return Err(Error::Unknown);
+        }
+        std::ptr::copy_nonoverlapping(header.as_ptr(), buf, header.len());
+        *buf.add(header.len()) = 0;
+        av_freep(&mut (*context).subtitle_header as *mut *mut u8 as *mut std::ffi::c_void);
+        (*context).subtitle_header = buf;
+        (*context).subtitle_header_size = header.len() as std::ffi::c_int;
+    }
+    Ok(())
+}
+
+/// Get the width and height of a codec context. For bitmap subtitle decoders, this is the size of
+/// the canvas that the bitmaps are positioned on. (Not natively supported in the public API for
+/// subtitle decoders.)
+///
+/// # Arguments
+///
+/// * `context` - Codec context.
+pub fn codec_context_size(context: &Context) -> (u32, u32) {
+    unsafe {
+        let context = context.as_ptr();
+        (
+            (*context).width.max(0) as u32,
+            (*context).height.max(0) as u32,
+        )
+    }
+}
+
+/// Encode a subtitle into a buffer. Unlike the binding in ffmpeg-next, this returns the number of
+/// bytes that were written.
+///
+/// # Arguments
+///
+/// * `encoder` - Opened subtitle encoder.
+/// * `subtitle` - Subtitle to encode.
+/// * `buf` - Buffer to encode into.
+pub fn encode_subtitle(
+    encoder: &mut SubtitleEncoder,
+    subtitle: &Subtitle,
+    buf: &mut [u8],
+) -> Result<usize, Error> {
+    unsafe {
+        match avcodec_encode_subtitle(
+            encoder.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len() as std::ffi::c_int,
+            subtitle.as_ptr(),
+        ) {
+            e if e < 0 => Err(Error::from(e)),
+            size => Ok(size as usize),
+        }
+    }
+}
+
+/// Free the rectangles of a subtitle. (The subtitle wrapper in ffmpeg-next does not free them
+/// when it is dropped.)
+///
+/// # Arguments
+///
+/// * `subtitle` - Subtitle to free rectangles of.
+pub fn free_subtitle(subtitle: &mut Subtitle) {
+    unsafe {
+        avsubtitle_free(subtitle.as_mut_ptr());
+    }
+}
+
+/// Convert the paletted image of a bitmap subtitle rectangle to packed RGBA.
+///
+/// # Arguments
+///
+/// * `bitmap` - Bitmap subtitle rectangle.
+///
+/// # Return value
+///
+/// RGBA pixels, row by row without padding.
+pub fn subtitle_bitmap_to_rgba(bitmap: &SubtitleBitmap) -> Vec<u8> {
+    unsafe {
+        let rect = bitmap.as_ptr();
+        let (width, height) = ((*rect).w.max(0) as usize, (*rect).h.max(0) as usize);
+        let indices = (*rect).data[0];
+        let palette = (*rect).data[1] as *const u32;
+        let stride = (*rect).linesize[0].max(0) as usize;
+        let colors = (*rect).nb_colors.max(0) as usize;
+
+        let mut rgba = vec![0; width * height * 4];
+        if indices.is_null() || palette.is_null() {
+            return rgba;
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let index = *indices.add(y * stride + x) as usize;
+                if index >= colors {
+                    continue;
+                }
+                // Palette entries are native-endian 0xAARRGGBB.
+                let argb = *palette.add(index);
+                let offset = (y * width + x) * 4;
+                rgba[offset] = (argb >> 16) as u8;
+                rgba[offset + 1] = (argb >> 8) as u8;
+                rgba[offset + 2] = argb as u8;
+                rgba[offset + 3] = (argb >> 24) as u8;
+            }
+        }
+        rgba
+    }
+}
+
 /// Copy frame properties from `src` to `dst`.
 ///
 /// # Arguments
@@ -255,6 +570,142 @@ pub fn copy_frame_props(src: &Frame, dst: &mut Frame) {
     }
 }
 
+/// Make sure the frame data is writable. If the frame buffers are shared with another frame, the
+/// data is copied into new buffers first.
+///
+/// # Arguments
+///
+/// * `frame` - Frame to make writable.
+pub fn frame_make_writable(frame: &mut Frame) -> Result<(), Error> {
+    unsafe {
+        match av_frame_make_writable(frame.as_mut_ptr()) {
+            0 => Ok(()),
+            e => Err(Error::from(e)),
+        }
+    }
+}
+
+/// Flag a frame as interlaced, with the top field first. Only used to build test frames, since
+/// decoders set these flags themselves.
+///
+/// # Arguments
+///
+/// * `frame` - Frame to flag.
+#[cfg(all(test, feature = "filter"))]
+pub fn set_frame_interlaced_top_field_first(frame: &mut Frame) {
+    unsafe {
+        (*frame.as_mut_ptr()).flags |=
+            (AV_FRAME_FLAG_INTERLACED | AV_FRAME_FLAG_TOP_FIELD_FIRST) as i32;
+    }
+}
+
+/// Borrow all planes of a frame mutably at once, with their strides. The frame is made writable
+/// first.
+///
+/// # Arguments
+///
+/// * `frame` - Frame to borrow planes of.
+pub fn frame_planes_mut(frame: &mut Frame) -> Result<Vec<(&mut [u8], usize)>, Error> {
+    frame_make_writable(frame)?;
+    let planes = frame.planes();
+    let plane_heights = (0..planes)
+        .map(|index| frame.plane_height(index) as usize)
+        .collect::<Vec<_>>();
+    unsafe {
+        let frame_ptr = frame.as_mut_ptr();
+        plane_heights
+            .into_iter()
+            .enumerate()
+            .map(|(index, plane_height)| {
+                let data = (*frame_ptr).data[index];
+                let stride = (*frame_ptr).linesize[index];
+                // Frames with negative strides (bottom-up images) are not supported.
+                if data.is_null() || stride < 0 {
+                    return Err(Error::InvalidData);
+                }
+                let stride = stride as usize;
+                // The planes do not overlap, so handing out a mutable slice for each is fine.
+                Ok((
+                    std::slice::from_raw_parts_mut(data, stride * plane_height),
+                    stride,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Remove all side data and metadata from a frame. This is required before reusing a frame, since
+/// `av_frame_copy_props` appends side data instead of replacing it.
+///
+/// # Arguments
+///
+/// * `frame` - Frame to remove side data and metadata from.
+pub fn frame_clear_side_data_and_metadata(frame: &mut Frame) {
+    unsafe {
+        let frame_ptr = frame.as_mut_ptr();
+        while (*frame_ptr).nb_side_data > 0 {
+            // This removes all side data entries of the same type at once.
+            av_frame_remove_side_data(frame_ptr, (**(*frame_ptr).side_data).type_);
+        }
+        av_dict_free(&mut (*frame_ptr).metadata);
+    }
+}
+
+/// Get the types of all side data entries attached to a frame.
+///
+/// # Arguments
+///
+/// * `frame` - Frame to get side data types of.
+pub fn frame_side_data_types(frame: &Frame) -> Vec<AVFrameSideDataType> {
+    unsafe {
+        let frame_ptr = frame.as_ptr();
+        (0..(*frame_ptr).nb_side_data.max(0) as usize)
+            .map(|i| (**(*frame_ptr).side_data.add(i)).type_)
+            .collect()
+    }
+}
+
+/// Compute the number of bytes in a single row of each plane (excluding padding) of an image with
+/// the given pixel format and width.
+///
+/// # Arguments
+///
+/// * `format` - Pixel format of the image.
+/// * `width` - Width of the image.
+pub fn image_plane_row_sizes(format: Pixel, width: u32) -> Result<[usize; 4], Error> {
+    unsafe {
+        let mut linesizes = [0 as std::ffi::c_int; 4];
+        match av_image_fill_linesizes(linesizes.as_mut_ptr(), format.into(), width as i32) {
+            e if e < 0 => Err(Error::from(e)),
+            _ => Ok(linesizes.map(|linesize| linesize.max(0) as usize)),
+        }
+    }
+}
+
+/// Whether or not the pixel format stores 8-bit luma samples in a plane of its own, as is the case
+/// for planar and semi-planar YUV formats (like YUV420P and NV12) and GRAY8.
+///
+/// # Arguments
+///
+/// * `format` - Pixel format to check.
+pub fn pixel_format_has_luma_plane(format: Pixel) -> bool {
+    const NON_LUMA_FLAGS: u64 = (AV_PIX_FMT_FLAG_RGB
+        | AV_PIX_FMT_FLAG_PAL
+        | AV_PIX_FMT_FLAG_BITSTREAM
+        | AV_PIX_FMT_FLAG_HWACCEL) as u64;
+
+    match format.descriptor() {
+        Some(descriptor) => unsafe {
+            let descriptor_ptr = descriptor.as_ptr();
+            ((*descriptor_ptr).flags & NON_LUMA_FLAGS) == 0
+                && (*descriptor_ptr).comp[0].plane == 0
+                && (*descriptor_ptr).comp[0].step == 1
+                && (*descriptor_ptr).comp[0].depth == 8
+        },
+        None => false,
+    }
+}
+
 /// A frame array is the `ndarray` version of `AVFrame`. It is 3-dimensional array with dims `(H, W,
 /// C)` and type byte.
 #[cfg(feature = "ndarray")]
@@ -356,6 +807,34 @@ pub fn convert_frame_to_ndarray_rgb24(frame: &mut Frame) -> Result<FrameArray, E
     }
 }
 
+/// Borrow an RGB24 video `AVFrame` produced by ffmpeg as an `ndarray` view without copying.
+///
+/// Note that rows in the frame may be padded, in which case the view is not in standard layout.
+///
+/// # Arguments
+///
+/// * `frame` - Video frame to borrow.
+///
+/// # Return value
+///
+/// A three-dimensional `ndarray` view with dimensions `(H, W, C)` and type byte.
+#[cfg(feature = "ndarray")]
+pub fn convert_frame_to_ndarray_view_rgb24(frame: &Frame) -> ArrayView3<'_, u8> {
+    unsafe {
+        let frame_ptr = frame.as_ptr();
+        let frame_width = (*frame_ptr).width as usize;
+        let frame_height = (*frame_ptr).height as usize;
+        let frame_linesize = (*frame_ptr).linesize[0];
+        assert_eq!(frame.format(), Pixel::RGB24);
+        assert!(frame_linesize >= 0 && frame_linesize as usize >= frame_width * 3);
+
+        ArrayView3::from_shape_ptr(
+            (frame_height, frame_width, 3_usize).strides((frame_linesize as usize, 3, 1)),
+            (*frame_ptr).data[0] as *const u8,
+        )
+    }
+}
+
 /// Retrieve a reference to the extradata bytes in codec parameters of an output stream.
 ///
 /// # Arguments
@@ -376,6 +855,46 @@ pub fn extradata(output: &Output, stream_index: usize) -> Result<&[u8], Error> {
     })
 }
 
+/// Retrieve a reference to the extradata bytes in codec parameters of an input stream.
+///
+/// # Arguments
+///
+/// * `input` - Input that contains stream to get extradata from.
+/// * `stream_index` - Index of stream.
+pub fn input_extradata(input: &Input, stream_index: usize) -> Result<&[u8], Error> {
+    let parameters = input
+        .stream(stream_index)
+        .map(|stream| stream.parameters())
+        .ok_or(Error::StreamNotFound)?;
+
+    unsafe {
+        if (*parameters.as_ptr()).extradata.is_null() {
+            return Ok(&[]);
+        }
+        Ok(std::slice::from_raw_parts(
+            (*parameters.as_ptr()).extradata,
+            (*parameters.as_ptr()).extradata_size as usize,
+        ))
+    }
+}
+
+/// Retrieve a reference to the extradata bytes in codec parameters.
+///
+/// # Arguments
+///
+/// * `parameters` - Codec parameters to get extradata from.
+pub fn codec_parameters_extradata(parameters: &Parameters) -> &[u8] {
+    unsafe {
+        if (*parameters.as_ptr()).extradata.is_null() {
+            return &[];
+        }
+        std::slice::from_raw_parts(
+            (*parameters.as_ptr()).extradata,
+            (*parameters.as_ptr()).extradata_size as usize,
+        )
+    }
+}
+
 /// Whether or not the output format context is configured to use H.264 packetization mode 0.
 ///
 /// # Arguments
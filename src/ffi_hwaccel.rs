@@ -8,14 +8,14 @@ pub struct HardwareDeviceContext {
 
 impl HardwareDeviceContext {
     pub fn new(
-        device_type: HardwareAccelerationDeviceType,
+        device_type: ffmpeg::ffi::AVHWDeviceType,
     ) -> Result<HardwareDeviceContext, ffmpeg::error::Error> {
         let mut ptr: *mut ffmpeg::ffi::AVBufferRef = std::ptr::null_mut();
 
         unsafe {
             match ffmpeg::ffi::av_hwdevice_ctx_create(
                 (&mut ptr) as *mut *mut ffmpeg::ffi::AVBufferRef,
-                device_type.into(),
+                device_type,
                 std::ptr::null(),
                 std::ptr::null_mut(),
                 0,
@@ -71,22 +71,53 @@ pub fn hwdevice_transfer_frame(
 
 pub fn codec_find_corresponding_hwaccel_pixfmt(
     codec: &ffmpeg::codec::codec::Codec,
-    hwaccel_type: HardwareAccelerationDeviceType,
+    hwaccel_type: ffmpeg::ffi::AVHWDeviceType,
 ) -> Option<ffmpeg::format::pixel::Pixel> {
-    let mut i = 0;
+    unsafe { codec_ptr_find_corresponding_hwaccel_pixfmt(codec.as_ptr(), hwaccel_type) }
+}
+
+pub fn codec_list_supporting_hwaccel(
+    hwaccel_type: ffmpeg::ffi::AVHWDeviceType,
+) -> Vec<ffmpeg::codec::Id> {
+    let mut codec_ids = Vec::new();
+    let mut opaque: *mut std::ffi::c_void = std::ptr::null_mut();
     loop {
         unsafe {
-            let hw_config = ffmpeg::ffi::avcodec_get_hw_config(codec.as_ptr(), i);
-            if !hw_config.is_null() {
-                let hw_config_supports_codec = (((*hw_config).methods) as i32
-                    & ffmpeg::ffi::AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX as i32)
-                    != 0;
-                if hw_config_supports_codec && (*hw_config).device_type == hwaccel_type.into() {
-                    break Some((*hw_config).pix_fmt.into());
-                }
-            } else {
-                break None;
+            let codec = ffmpeg::ffi::av_codec_iterate(&mut opaque);
+            if codec.is_null() {
+                break codec_ids;
+            }
+            // Only the default decoder for a codec is used for decoding, so other decoders for the
+            // same codec (such as the cuvid ones) are skipped.
+            if ffmpeg::ffi::av_codec_is_decoder(codec) == 0
+                || (*codec).type_ != ffmpeg::ffi::AVMediaType::AVMEDIA_TYPE_VIDEO
+                || ffmpeg::ffi::avcodec_find_decoder((*codec).id) != codec
+            {
+                continue;
+            }
+            if codec_ptr_find_corresponding_hwaccel_pixfmt(codec, hwaccel_type).is_some() {
+                codec_ids.push((*codec).id.into());
+            }
+        }
+    }
+}
+
+unsafe fn codec_ptr_find_corresponding_hwaccel_pixfmt(
+    codec: *const ffmpeg::ffi::AVCodec,
+    hwaccel_type: ffmpeg::ffi::AVHWDeviceType,
+) -> Option<ffmpeg::format::pixel::Pixel> {
+    let mut i = 0;
+    loop {
+        let hw_config = ffmpeg::ffi::avcodec_get_hw_config(codec, i);
+        if !hw_config.is_null() {
+            let hw_config_supports_codec = (((*hw_config).methods) as i32
+                & ffmpeg::ffi::AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX as i32)
+                != 0;
+            if hw_config_supports_codec && (*hw_config).device_type == hwaccel_type {
+                break Some((*hw_config).pix_fmt.into());
             }
+        } else {
+            break None;
         }
         i += 1;
     }
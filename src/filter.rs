@@ -0,0 +1,427 @@
+extern crate ffmpeg_next as ffmpeg;
+
+use ffmpeg::filter::Graph as AvGraph;
+use ffmpeg::util::error::EAGAIN;
+use ffmpeg::{Error as AvError, Rational as AvRational};
+
+use crate::error::Error;
+use crate::ffi;
+use crate::frame::{PixelFormat, RawFrame, VideoFrame};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Name of the buffer source in the filter graph.
+const SOURCE_NAME: &str = "in";
+
+/// Name of the buffer sink in the filter graph.
+const SINK_NAME: &str = "out";
+
+/// Properties of the frames that go into a [`FilterGraph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSource {
+    width: u32,
+    height: u32,
+    pixel_format: PixelFormat,
+    time_base: AvRational,
+    sample_aspect_ratio: AvRational,
+    frame_rate: Option<AvRational>,
+}
+
+impl BufferSource {
+    /// Create buffer source properties.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of input frames.
+    /// * `height` - Height of input frames.
+    /// * `pixel_format` - Pixel format of input frames.
+    /// * `time_base` - Time base of input frame timestamps.
+    pub fn new(width: u32, height: u32, pixel_format: PixelFormat, time_base: AvRational) -> Self {
+        Self {
+            width,
+            height,
+            pixel_format,
+            time_base,
+            sample_aspect_ratio: AvRational::new(1, 1),
+            frame_rate: None,
+        }
+    }
+
+    /// Set the sample (pixel) aspect ratio of input frames. Defaults to square pixels.
+    pub fn with_sample_aspect_ratio(mut self, sample_aspect_ratio: AvRational) -> Self {
+        self.sample_aspect_ratio = sample_aspect_ratio;
+        self
+    }
+
+    /// Set the frame rate of the input. Some filters, such as `fps`, need it.
+    pub fn with_frame_rate(mut self, frame_rate: AvRational) -> Self {
+        self.frame_rate = Some(frame_rate);
+        self
+    }
+
+    /// Arguments for the `buffer` filter.
+    fn args(&self) -> String {
+        let mut args = format!(
+            "video_size={}x{}:pix_fmt={}:time_base={}/{}:pixel_aspect={}/{}",
+            self.width,
+            self.height,
+            ffmpeg::ffi::AVPixelFormat::from(self.pixel_format) as i32,
+            self.time_base.numerator(),
+            self.time_base.denominator(),
+            self.sample_aspect_ratio.numerator(),
+            self.sample_aspect_ratio.denominator(),
+        );
+        if let Some(frame_rate) = self.frame_rate {
+            args.push_str(&format!(
+                ":frame_rate={}/{}",
+                frame_rate.numerator(),
+                frame_rate.denominator()
+            ));
+        }
+        args
+    }
+}
+
+/// Constraints on the frames that come out of a [`FilterGraph`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferSink {
+    pixel_format: Option<PixelFormat>,
+}
+
+impl BufferSink {
+    /// Create buffer sink without constraints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Convert output frames to the specified pixel format.
+    pub fn with_pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+        self.pixel_format = Some(pixel_format);
+        self
+    }
+}
+
+/// Deinterlacing algorithm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeinterlaceAlgorithm {
+    /// Yet Another DeInterlacing Filter (`yadif`).
+    #[default]
+    Yadif,
+    /// Bob Weaver Deinterlacing Filter (`bwdif`). Slower than `yadif`, but better quality.
+    Bwdif,
+}
+
+/// Deinterlacing output mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeinterlaceMode {
+    /// Output one frame for each field, which doubles the frame rate and keeps all motion.
+    Bob,
+    /// Output one deinterlaced frame for each interlaced frame. The frame rate stays the same.
+    #[default]
+    Frame,
+}
+
+/// Deinterlacing settings. Only frames that the decoder flags as interlaced are deinterlaced, so
+/// progressive frames (or progressive parts of a stream) pass through untouched. The field order
+/// is taken from the frame flags as well.
+///
+/// # Example
+///
+/// ```ignore
+/// let decoder = DecoderBuilder::new(Path::new("broadcast.ts"))
+///     .with_deinterlace(Deinterlace::new().with_mode(DeinterlaceMode::Bob))
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Deinterlace {
+    algorithm: DeinterlaceAlgorithm,
+    mode: DeinterlaceMode,
+}
+
+impl Deinterlace {
+    /// Create deinterlacing settings with the default algorithm (`yadif`) and mode
+    /// ([`DeinterlaceMode::Frame`]).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the deinterlacing algorithm.
+    pub fn with_algorithm(mut self, algorithm: DeinterlaceAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Set the output mode.
+    pub fn with_mode(mut self, mode: DeinterlaceMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Filter description that implements the deinterlacing settings.
+    pub(crate) fn description(&self) -> String {
+        let filter = match self.algorithm {
+            DeinterlaceAlgorithm::Yadif => "yadif",
+            DeinterlaceAlgorithm::Bwdif => "bwdif",
+        };
+        let mode = match self.mode {
+            DeinterlaceMode::Bob => "send_field",
+            DeinterlaceMode::Frame => "send_frame",
+        };
+        // Automatic parity uses the field order flags of each frame.
+        format!("{filter}=mode={mode}:parity=auto:deint=interlaced")
+    }
+}
+
+/// Applies an ffmpeg filter graph, such as `yadif,hqdn3d` or `fps=30`, to video frames.
+///
+/// Filters may hold on to frames, and may produce more or fewer frames than they get, so frames
+/// are sent and received separately. Call [`FilterGraph::drain`] after the last frame to get the
+/// frames that are still in the graph.
+///
+/// # Example
+///
+/// ```ignore
+/// let (mut decoder, mut reader, stream_index) = Decoder::new(Path::new("input.ts"))?.into_parts();
+/// let (width, height) = decoder.size_out();
+/// let source = BufferSource::new(width, height, PixelFormat::RGB24, decoder.time_base());
+/// let mut filter = FilterGraph::new("yadif,hqdn3d", source)?;
+/// while let Ok(packet) = reader.read(stream_index) {
+///     if let Some(frame) = decoder.decode_frame(packet)? {
+///         for frame in filter.filter(frame)? {
+///             encoder.encode_frame(frame)?;
+///         }
+///     }
+/// }
+/// for frame in filter.drain()? {
+///     encoder.encode_frame(frame)?;
+/// }
+/// ```
+pub struct FilterGraph {
+    description: String,
+    source: BufferSource,
+    sink: BufferSink,
+    graph: Option<AvGraph>,
+    eof: bool,
+    time_base: AvRational,
+    output_format: PixelFormat,
+    output_size: (u32, u32),
+}
+
+impl FilterGraph {
+    /// Create a filter graph from a filter description, in the same syntax as the `-vf` argument
+    /// of the ffmpeg command line tool.
+    ///
+    /// # Arguments
+    ///
+    /// * `description` - Filter description, for example `yadif,hqdn3d`.
+    /// * `source` - Properties of input frames.
+    pub fn new(description: &str, source: BufferSource) -> Result<Self> {
+        Self::with_sink(description, source, BufferSink::default())
+    }
+
+    /// Create a filter graph from a filter description with constraints on the output frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `description` - Filter description, for example `yadif,hqdn3d`.
+    /// * `source` - Properties of input frames.
+    /// * `sink` - Constraints on output frames.
+    pub fn with_sink(description: &str, source: BufferSource, sink: BufferSink) -> Result<Self> {
+        let mut filter_graph = Self {
+            description: description.to_string(),
+            source,
+            sink,
+            graph: None,
+            eof: false,
+            time_base: source.time_base,
+            output_format: source.pixel_format,
+            output_size: (source.width, source.height),
+        };
+        filter_graph.build()?;
+        Ok(filter_graph)
+    }
+
+    /// Send a raw frame into the filter graph. The frame must match the [`BufferSource`]
+    /// properties, and its timestamp must be in the time base of the source.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to filter.
+    pub fn send(&mut self, frame: RawFrame) -> Result<()> {
+        self.graph_source_add(Some(&frame))
+    }
+
+    /// Send a [`VideoFrame`] into the filter graph. The timestamp of the frame is converted to the
+    /// time base of the source.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to filter.
+    pub fn send_frame(&mut self, frame: VideoFrame) -> Result<()> {
+        let timestamp = frame.pts();
+        let mut frame = frame.into_inner();
+        frame.set_pts(
+            timestamp
+                .aligned_with_rational(self.source.time_base)
+                .into_value(),
+        );
+        self.send(frame)
+    }
+
+    /// Signal to the filter graph that the last frame has been sent. Signaling this more than
+    /// once has no effect.
+    pub fn send_eof(&mut self) -> Result<()> {
+        if self.eof {
+            return Ok(());
+        }
+        self.graph_source_add(None)?;
+        self.eof = true;
+        Ok(())
+    }
+
+    /// Receive a filtered raw frame, if there is one available. Its timestamp is in
+    /// [`FilterGraph::time_base`].
+    pub fn receive(&mut self) -> Result<Option<RawFrame>> {
+        let Some(graph) = self.graph.as_mut() else {
+            return Ok(None);
+        };
+
+        let mut frame = RawFrame::empty();
+        let receive_result = graph
+            .get(SINK_NAME)
+            .ok_or(AvError::FilterNotFound)?
+            .sink()
+            .frame(&mut frame);
+        match receive_result {
+            Ok(()) => Ok(Some(frame)),
+            Err(AvError::Eof) => Ok(None),
+            Err(AvError::Other { errno }) if errno == EAGAIN => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Receive a filtered [`VideoFrame`], if there is one available.
+    pub fn receive_frame(&mut self) -> Result<Option<VideoFrame>> {
+        Ok(self
+            .receive()?
+            .map(|frame| VideoFrame::new(frame, self.time_base)))
+    }
+
+    /// Filter a single [`VideoFrame`] and return the frames that came out of the filter graph.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to filter.
+    pub fn filter(&mut self, frame: VideoFrame) -> Result<Vec<VideoFrame>> {
+        self.send_frame(frame)?;
+        self.receive_all()
+    }
+
+    /// Signal to the filter graph that the last frame has been sent, and return the frames that
+    /// were still in the filter graph.
+    pub fn drain(&mut self) -> Result<Vec<VideoFrame>> {
+        self.send_eof()?;
+        self.receive_all()
+    }
+
+    /// Reset the filter graph, dropping any frames it holds on to. This is necessary after
+    /// draining or seeking. The graph is rebuilt when the next frame is sent.
+    pub fn reset(&mut self) {
+        self.graph = None;
+        self.eof = false;
+    }
+
+    /// Get the time base of filtered frames.
+    #[inline]
+    pub fn time_base(&self) -> AvRational {
+        self.time_base
+    }
+
+    /// Get the pixel format of filtered frames.
+    #[inline]
+    pub fn output_format(&self) -> PixelFormat {
+        self.output_format
+    }
+
+    /// Get the size of filtered frames as width and height.
+    #[inline]
+    pub fn output_size(&self) -> (u32, u32) {
+        self.output_size
+    }
+
+    /// Build the filter graph, and determine the properties of the output frames.
+    fn build(&mut self) -> Result<()> {
+        let mut graph = AvGraph::new();
+        let buffer = ffmpeg::filter::find("buffer").ok_or(AvError::FilterNotFound)?;
+        let buffersink = ffmpeg::filter::find("buffersink").ok_or(AvError::FilterNotFound)?;
+        graph.add(&buffer, SOURCE_NAME, &self.source.args())?;
+        graph.add(&buffersink, SINK_NAME, "")?;
+
+        let mut description = if self.description.trim().is_empty() {
+            "null".to_string()
+        } else {
+            self.description.clone()
+        };
+        // Converting with a trailing format filter works on all ffmpeg versions, unlike setting
+        // the pixel formats on the buffer sink.
+        if let Some(pixel_format) = self.sink.pixel_format {
+            let name = pixel_format
+                .descriptor()
+                .ok_or(Error::InvalidFrameFormat)?
+                .name();
+            description.push_str(&format!(",format=pix_fmts={name}"));
+        }
+
+        graph
+            .output(SOURCE_NAME, 0)?
+            .input(SINK_NAME, 0)?
+            .parse(&description)?;
+        graph.validate()?;
+
+        let mut sink = graph.get(SINK_NAME).ok_or(AvError::FilterNotFound)?;
+        self.time_base = sink.sink().time_base();
+        let (output_format, output_width, output_height) = ffi::filter_sink_properties(&sink);
+        self.output_format = output_format;
+        self.output_size = (output_width, output_height);
+        self.graph = Some(graph);
+
+        Ok(())
+    }
+
+    /// Add a frame to the buffer source, or signal the end of the stream if there is no frame.
+    /// Builds the filter graph first if it was reset.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to add.
+    fn graph_source_add(&mut self, frame: Option<&RawFrame>) -> Result<()> {
+        if self.graph.is_none() {
+            self.build()?;
+        }
+        let Some(graph) = self.graph.as_mut() else {
+            return Err(Error::UninitializedCodec);
+        };
+
+        let mut source_context = graph.get(SOURCE_NAME).ok_or(AvError::FilterNotFound)?;
+        let mut source = source_context.source();
+        match frame {
+            Some(frame) => source.add(frame)?,
+            None => source.flush()?,
+        }
+
+        Ok(())
+    }
+
+    /// Receive all frames that are available.
+    fn receive_all(&mut self) -> Result<Vec<VideoFrame>> {
+        let mut frames = Vec::new();
+        while let Some(frame) = self.receive_frame()? {
+            frames.push(frame);
+        }
+
+        Ok(frames)
+    }
+}
+
+unsafe impl Send for FilterGraph {}
+unsafe impl Sync for FilterGraph {}
@@ -1,7 +1,24 @@
 extern crate ffmpeg_next as ffmpeg;
 
+#[cfg(feature = "ndarray")]
+use ndarray::{ArrayView2, ShapeBuilder};
+
+use ffmpeg::software::scaling::{context::Context as AvScaler, flag::Flags as AvScalerFlags};
+use ffmpeg::util::color::{
+    Primaries as AvColorPrimaries, Range as AvColorRange, Space as AvColorSpace,
+    TransferCharacteristic as AvColorTransferCharacteristic,
+};
 use ffmpeg::util::format::Pixel as AvPixel;
+use ffmpeg::util::frame::side_data::Type as AvSideDataType;
 use ffmpeg::util::frame::Video as AvFrame;
+use ffmpeg::util::picture::Type as AvPictureType;
+use ffmpeg::Rational as AvRational;
+
+use crate::error::Error;
+use crate::ffi;
+use crate::time::Time;
+
+type Result<T> = std::result::Result<T, Error>;
 
 /// Re-export internal `AvPixel` as `PixelFormat` for callers.
 pub type PixelFormat = AvPixel;
@@ -13,5 +30,399 @@ pub type RawFrame = AvFrame;
 #[cfg(feature = "ndarray")]
 pub type Frame = crate::ffi::FrameArray;
 
+/// Re-export internal `AvPictureType` as `PictureType` for callers.
+pub type PictureType = AvPictureType;
+
+/// Re-export internal `AvColorSpace` as `ColorSpace` for callers.
+pub type ColorSpace = AvColorSpace;
+
+/// Re-export internal `AvColorRange` as `ColorRange` for callers.
+pub type ColorRange = AvColorRange;
+
+/// Re-export internal `AvColorPrimaries` as `ColorPrimaries` for callers.
+pub type ColorPrimaries = AvColorPrimaries;
+
+/// Re-export internal `AvColorTransferCharacteristic` as `ColorTransferCharacteristic` for
+/// callers.
+pub type ColorTransferCharacteristic = AvColorTransferCharacteristic;
+
+/// Re-export internal `AvSideDataType` as `SideDataType` for callers.
+pub type SideDataType = AvSideDataType;
+
 /// Default frame pixel format.
 pub(crate) const FRAME_PIXEL_FORMAT: AvPixel = AvPixel::RGB24;
+
+/// Pool of frames with a fixed pixel format and size. Frames that are returned to the pool are
+/// handed out again later, so that frame buffers do not need to be allocated for every frame.
+pub(crate) struct FramePool {
+    frames: Vec<AvFrame>,
+    format: AvPixel,
+    width: u32,
+    height: u32,
+}
+
+impl FramePool {
+    /// Maximum number of frames kept around in the pool.
+    const MAX_FRAMES: usize = 4;
+
+    /// Create a new, empty pool for frames with the given pixel format and size.
+    pub(crate) fn new(format: AvPixel, width: u32, height: u32) -> Self {
+        Self {
+            frames: Vec::with_capacity(Self::MAX_FRAMES),
+            format,
+            width,
+            height,
+        }
+    }
+
+    /// Take a frame from the pool. If the pool is empty, an empty (unallocated) frame is returned.
+    ///
+    /// Frames that are still referenced elsewhere (for example, by an encoder that buffers frames)
+    /// are made writable first, so it is always safe to write to the returned frame.
+    pub(crate) fn take(&mut self) -> AvFrame {
+        while let Some(mut frame) = self.frames.pop() {
+            if ffi::frame_make_writable(&mut frame).is_ok() {
+                ffi::frame_clear_side_data_and_metadata(&mut frame);
+                return frame;
+            }
+        }
+        AvFrame::empty()
+    }
+
+    /// Take a frame from the pool. If the pool is empty, a new frame is allocated.
+    pub(crate) fn take_or_alloc(&mut self) -> AvFrame {
+        let frame = self.take();
+        // SAFETY: Only checks whether the frame data pointer is set.
+        if unsafe { frame.is_empty() } {
+            AvFrame::new(self.format, self.width, self.height)
+        } else {
+            frame
+        }
+    }
+
+    /// Return a frame to the pool. Frames that do not match the pixel format and size of the pool
+    /// are dropped.
+    pub(crate) fn put(&mut self, frame: AvFrame) {
+        if self.frames.len() < Self::MAX_FRAMES
+            && frame.format() == self.format
+            && frame.width() == self.width
+            && frame.height() == self.height
+        {
+            self.frames.push(frame);
+        }
+    }
+}
+
+/// Represents a video frame along with its metadata: timestamps, pixel format, dimensions, picture
+/// type, color properties and side data.
+///
+/// Unlike [`RawFrame`], all accessors are safe, and unlike the `ndarray`-based `Frame`, the frame
+/// carries its own timestamp and may have any pixel format.
+#[derive(Clone)]
+pub struct VideoFrame {
+    inner: AvFrame,
+    time_base: AvRational,
+}
+
+impl VideoFrame {
+    /// Create a new video frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Inner `AvFrame`.
+    /// * `time_base` - Time base of the frame timestamps.
+    pub fn new(inner: AvFrame, time_base: AvRational) -> Self {
+        Self { inner, time_base }
+    }
+
+    /// Create a new video frame from an RGB24 `ndarray` frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame in `HWC` format and standard layout.
+    /// * `timestamp` - Frame timestamp.
+    #[cfg(feature = "ndarray")]
+    pub fn from_ndarray(frame: &Frame, timestamp: Time) -> Result<Self> {
+        let (height, width, channels) = frame.dim();
+        if height == 0 || width == 0 || channels != 3 || !frame.is_standard_layout() {
+            return Err(Error::InvalidFrameFormat);
+        }
+
+        let (pts, time_base) = timestamp.into_parts();
+        let mut inner = ffi::convert_ndarray_to_frame_rgb24(frame).map_err(Error::BackendError)?;
+        inner.set_pts(pts);
+
+        Ok(Self::new(inner, time_base))
+    }
+
+    /// Get frame PTS (presentation timestamp).
+    #[inline]
+    pub fn pts(&self) -> Time {
+        Time::new(self.inner.pts(), self.time_base)
+    }
+
+    /// Set frame PTS (presentation timestamp).
+    #[inline]
+    pub fn set_pts(&mut self, timestamp: Time) {
+        self.inner
+            .set_pts(timestamp.aligned_with_rational(self.time_base).into_value());
+    }
+
+    /// Get frame duration.
+    #[inline]
+    pub fn duration(&self) -> Time {
+        Time::new(Some(self.inner.packet().duration), self.time_base)
+    }
+
+    /// Get the time base of the frame timestamps.
+    #[inline]
+    pub fn time_base(&self) -> AvRational {
+        self.time_base
+    }
+
+    /// Get frame pixel format.
+    #[inline]
+    pub fn format(&self) -> PixelFormat {
+        self.inner.format()
+    }
+
+    /// Get frame width.
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.inner.width()
+    }
+
+    /// Get frame height.
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.inner.height()
+    }
+
+    /// Get frame size (resolution dimensions): width and height.
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        (self.inner.width(), self.inner.height())
+    }
+
+    /// Whether or not the frame is a key frame.
+    #[inline]
+    pub fn is_key(&self) -> bool {
+        self.inner.is_key()
+    }
+
+    /// Get the picture type of the frame.
+    #[inline]
+    pub fn picture_type(&self) -> PictureType {
+        self.inner.kind()
+    }
+
+    /// Get the color space of the frame.
+    #[inline]
+    pub fn color_space(&self) -> ColorSpace {
+        self.inner.color_space()
+    }
+
+    /// Get the color range of the frame.
+    #[inline]
+    pub fn color_range(&self) -> ColorRange {
+        self.inner.color_range()
+    }
+
+    /// Get the color primaries of the frame.
+    #[inline]
+    pub fn color_primaries(&self) -> ColorPrimaries {
+        self.inner.color_primaries()
+    }
+
+    /// Get the color transfer characteristic of the frame.
+    #[inline]
+    pub fn color_transfer_characteristic(&self) -> ColorTransferCharacteristic {
+        self.inner.color_transfer_characteristic()
+    }
+
+    /// Get the types of side data attached to the frame.
+    pub fn side_data_types(&self) -> Vec<SideDataType> {
+        ffi::frame_side_data_types(&self.inner)
+            .into_iter()
+            .map(SideDataType::from)
+            .collect()
+    }
+
+    /// Get the contents of side data attached to the frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Type of side data to get.
+    pub fn side_data(&self, kind: SideDataType) -> Option<&[u8]> {
+        self.inner.side_data(kind).map(|side_data| {
+            let data = side_data.data();
+            // SAFETY: The side data is owned by the frame, not by the `SideData` wrapper, so it
+            // lives as long as the frame does.
+            unsafe { std::slice::from_raw_parts(data.as_ptr(), data.len()) }
+        })
+    }
+
+    /// Number of planes in the frame. Packed formats like RGB24 have a single plane, planar formats
+    /// like YUV420P have one plane per component.
+    #[inline]
+    pub fn planes(&self) -> usize {
+        self.inner.planes()
+    }
+
+    /// Get the number of bytes per row (including padding) of a plane.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Plane index.
+    pub fn stride(&self, index: usize) -> Option<usize> {
+        (index < self.inner.planes()).then(|| self.inner.stride(index))
+    }
+
+    /// Get the width and height of a plane in pixels. Chroma planes may be subsampled.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Plane index.
+    pub fn plane_size(&self, index: usize) -> Option<(u32, u32)> {
+        (index < self.inner.planes()).then(|| {
+            (
+                self.inner.plane_width(index),
+                self.inner.plane_height(index),
+            )
+        })
+    }
+
+    /// Get the data of a plane. Each row in the plane is [`stride`](VideoFrame::stride) bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Plane index.
+    pub fn plane(&self, index: usize) -> Option<&[u8]> {
+        (index < self.inner.planes()).then(|| self.inner.data(index))
+    }
+
+    /// Get the mutable data of a plane. Each row in the plane is [`stride`](VideoFrame::stride)
+    /// bytes.
+    ///
+    /// If the frame data is shared with another frame, it is copied first so that writing to it
+    /// does not affect the other frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Plane index.
+    pub fn plane_mut(&mut self, index: usize) -> Option<&mut [u8]> {
+        if index < self.inner.planes() && ffi::frame_make_writable(&mut self.inner).is_ok() {
+            Some(self.inner.data_mut(index))
+        } else {
+            None
+        }
+    }
+
+    /// Borrow a plane as a two-dimensional `ndarray` view without copying. The view has dims
+    /// `(H, W)` where `W` is the number of bytes per row (excluding padding).
+    ///
+    /// For 8-bit planar YUV formats, this gives direct access to the Y, U and V planes.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Plane index.
+    #[cfg(feature = "ndarray")]
+    pub fn plane_view(&self, index: usize) -> Option<ArrayView2<'_, u8>> {
+        let (_, plane_height) = self.plane_size(index)?;
+        let row_size = *ffi::image_plane_row_sizes(self.format(), self.width())
+            .ok()?
+            .get(index)?;
+        let stride = self.stride(index)?;
+        ArrayView2::from_shape(
+            (plane_height as usize, row_size).strides((stride, 1)),
+            self.plane(index)?,
+        )
+        .ok()
+    }
+
+    /// Borrow the luma (Y) plane as a two-dimensional `ndarray` view with dims `(H, W)` without
+    /// copying.
+    ///
+    /// Only available for pixel formats that store 8-bit luma in a plane of its own, like YUV420P,
+    /// NV12 and GRAY8. Returns `None` for other pixel formats.
+    #[cfg(feature = "ndarray")]
+    pub fn luma(&self) -> Option<ArrayView2<'_, u8>> {
+        if ffi::pixel_format_has_luma_plane(self.format()) {
+            self.plane_view(0)
+        } else {
+            None
+        }
+    }
+
+    /// Convert the frame to another pixel format.
+    ///
+    /// Note that this creates a new scaler for every invocation. Use the resizing and pixel format
+    /// options of the decoder and encoder to convert a stream of frames instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Pixel format to convert to.
+    pub fn convert(&self, format: PixelFormat) -> Result<VideoFrame> {
+        let (width, height) = self.size();
+        let mut scaler = AvScaler::get(
+            self.format(),
+            width,
+            height,
+            format,
+            width,
+            height,
+            AvScalerFlags::AREA,
+        )
+        .map_err(Error::BackendError)?;
+
+        let mut frame_converted = AvFrame::empty();
+        scaler
+            .run(&self.inner, &mut frame_converted)
+            .map_err(Error::BackendError)?;
+        ffi::copy_frame_props(&self.inner, &mut frame_converted);
+
+        Ok(Self::new(frame_converted, self.time_base))
+    }
+
+    /// Convert the frame to an RGB24 `ndarray` frame in `HWC` format. Frames in other pixel formats
+    /// are converted to RGB24 first.
+    #[cfg(feature = "ndarray")]
+    pub fn to_ndarray(&self) -> Result<Frame> {
+        if self.format() == FRAME_PIXEL_FORMAT {
+            Ok(ffi::convert_frame_to_ndarray_view_rgb24(&self.inner).to_owned())
+        } else {
+            self.convert(FRAME_PIXEL_FORMAT)?.to_ndarray()
+        }
+    }
+
+    /// Get a reference to the native inner type.
+    #[inline]
+    pub fn as_raw(&self) -> &AvFrame {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the native inner type.
+    #[inline]
+    pub fn as_raw_mut(&mut self) -> &mut AvFrame {
+        &mut self.inner
+    }
+
+    /// Downcast to native inner type.
+    pub fn into_inner(self) -> AvFrame {
+        self.inner
+    }
+
+    /// Downcast to native inner type and time base.
+    pub fn into_inner_parts(self) -> (AvFrame, AvRational) {
+        (self.inner, self.time_base)
+    }
+}
+
+impl From<VideoFrame> for AvFrame {
+    fn from(frame: VideoFrame) -> AvFrame {
+        frame.into_inner()
+    }
+}
+
+unsafe impl Send for VideoFrame {}
+unsafe impl Sync for VideoFrame {}
@@ -0,0 +1,362 @@
+extern crate ffmpeg_next as ffmpeg;
+
+use ffmpeg::Rational as AvRational;
+
+use crate::error::Error;
+use crate::frame::VideoFrame;
+use crate::time::Time;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Converts a stream of frames with variable frame rate into a stream with a constant frame rate,
+/// by duplicating and dropping frames as needed. It can be placed between a [`Decoder`] and an
+/// [`Encoder`].
+///
+/// Every output slot at `n / frame_rate` is filled with the latest input frame whose timestamp is
+/// strictly before the middle of the slot. Frames that do not end up in any slot are dropped, frames
+/// that end up in more than one slot are duplicated.
+///
+/// Note that each frame is only emitted once the next frame (or the end of the stream) is known,
+/// which adds a latency of one input frame.
+///
+/// [`Decoder`]: crate::decode::Decoder
+/// [`Encoder`]: crate::encode::Encoder
+///
+/// # Example
+///
+/// ```ignore
+/// let mut converter = FrameRateConverter::new((30, 1))?;
+/// for frame in decoder.decode_frame_iter() {
+///     for frame in converter.push_frame(frame?) {
+///         encoder.encode_frame(frame)?;
+///     }
+/// }
+/// for frame in converter.flush_frames() {
+///     encoder.encode_frame(frame)?;
+/// }
+/// println!("duplicated: {}", converter.stats().duplicated());
+/// ```
+pub struct FrameRateConverter<T> {
+    frame_rate: AvRational,
+    next_slot: Option<i64>,
+    pending: Option<PendingFrame<T>>,
+    stats: FrameRateStats,
+}
+
+impl<T: Clone> FrameRateConverter<T> {
+    /// Create a frame rate converter.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_rate` - Target frame rate in frames per second, for example `(30000, 1001)`. Must
+    ///   be positive, or [`Error::InvalidFrameRate`] is returned.
+    pub fn new(frame_rate: impl Into<AvRational>) -> Result<Self> {
+        let frame_rate = frame_rate.into();
+        if frame_rate.numerator() as i64 * frame_rate.denominator() as i64 <= 0 {
+            return Err(Error::InvalidFrameRate);
+        }
+        Ok(Self {
+            frame_rate,
+            next_slot: None,
+            pending: None,
+            stats: FrameRateStats::default(),
+        })
+    }
+
+    /// Push a frame into the converter.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Timestamp of frame. Frames without a timestamp are placed in the next
+    ///   output slot.
+    /// * `frame` - Frame.
+    ///
+    /// # Return value
+    ///
+    /// Zero or more frames with their timestamps on the constant frame rate grid. The timestamps
+    /// are in [`FrameRateConverter::time_base`].
+    pub fn push(&mut self, timestamp: Time, frame: T) -> Vec<(Time, T)> {
+        self.stats.input += 1;
+
+        // Position of the frame in output slots, as a fraction. Frames without timestamp are
+        // placed in the middle of the next slot, so that the previous frame fills that slot.
+        let (position, position_den) = match (self.slot_position(timestamp), self.next_slot) {
+            (Some(position), _) => position,
+            (None, Some(slot)) => (2 * slot as i128 + 1, 2),
+            (None, None) => (0, 1),
+        };
+
+        let mut frames = Vec::new();
+        match self.next_slot {
+            Some(mut slot) => {
+                // Fill every slot of which the middle lies at or before the new frame with the
+                // previous frame.
+                if let Some(pending) = self.pending.as_mut() {
+                    while (2 * slot as i128 + 1) * position_den <= 2 * position {
+                        frames.push((self.slot_time(slot), pending.frame.clone()));
+                        pending.outputs += 1;
+                        slot += 1;
+                    }
+                }
+                self.next_slot = Some(slot);
+            }
+            None => {
+                // Round to the nearest slot.
+                let slot = (2 * position + position_den).div_euclid(2 * position_den);
+                self.next_slot = Some(slot as i64);
+            }
+        }
+
+        self.stats.output += frames.len() as u64;
+        self.replace_pending(Some(PendingFrame { frame, outputs: 0 }));
+
+        frames
+    }
+
+    /// Flush the converter at the end of the stream. The last frame is emitted if it has not been
+    /// emitted yet.
+    pub fn flush(&mut self) -> Vec<(Time, T)> {
+        let mut frames = Vec::new();
+        if let (Some(pending), Some(slot)) = (self.pending.as_mut(), self.next_slot) {
+            if pending.outputs == 0 {
+                frames.push((self.slot_time(slot), pending.frame.clone()));
+                pending.outputs += 1;
+                self.next_slot = Some(slot + 1);
+            }
+        }
+
+        self.stats.output += frames.len() as u64;
+        self.replace_pending(None);
+
+        frames
+    }
+
+    /// Get the target frame rate.
+    #[inline]
+    pub fn frame_rate(&self) -> AvRational {
+        self.frame_rate
+    }
+
+    /// Get the time base of output timestamps, which is the inverse of the frame rate.
+    #[inline]
+    pub fn time_base(&self) -> AvRational {
+        self.frame_rate.invert()
+    }
+
+    /// Get the conversion statistics so far.
+    #[inline]
+    pub fn stats(&self) -> FrameRateStats {
+        self.stats
+    }
+
+    /// Replace the pending frame and account for the number of times the previous pending frame
+    /// was emitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `pending` - New pending frame.
+    fn replace_pending(&mut self, pending: Option<PendingFrame<T>>) {
+        if let Some(previous) = std::mem::replace(&mut self.pending, pending) {
+            match previous.outputs {
+                0 => self.stats.dropped += 1,
+                outputs => self.stats.duplicated += outputs - 1,
+            }
+        }
+    }
+
+    /// Position of a timestamp in output slots, as an exact fraction with a positive denominator.
+    /// Returns `None` if the timestamp has no value.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Timestamp.
+    fn slot_position(&self, timestamp: Time) -> Option<(i128, i128)> {
+        if timestamp.has_no_pts() {
+            return None;
+        }
+        let (time, time_base) = timestamp.into_parts();
+        let numerator =
+            time? as i128 * time_base.numerator() as i128 * self.frame_rate.numerator() as i128;
+        let denominator = time_base.denominator() as i128 * self.frame_rate.denominator() as i128;
+        if denominator < 0 {
+            Some((-numerator, -denominator))
+        } else {
+            Some((numerator, denominator))
+        }
+    }
+
+    /// Timestamp of output slot.
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` - Output slot.
+    fn slot_time(&self, slot: i64) -> Time {
+        Time::new(Some(slot), self.time_base())
+    }
+}
+
+impl FrameRateConverter<VideoFrame> {
+    /// Push a [`VideoFrame`] into the converter. The frame PTS is used as timestamp, and the PTS of
+    /// the returned frames is set to their timestamp on the constant frame rate grid.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame.
+    pub fn push_frame(&mut self, frame: VideoFrame) -> Vec<VideoFrame> {
+        let timestamp = frame.pts();
+        Self::with_timestamps(self.push(timestamp, frame))
+    }
+
+    /// Flush the converter at the end of the stream. See [`FrameRateConverter::flush`].
+    pub fn flush_frames(&mut self) -> Vec<VideoFrame> {
+        Self::with_timestamps(self.flush())
+    }
+
+    /// Set the PTS of frames to their output timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - Frames with their output timestamps.
+    fn with_timestamps(frames: Vec<(Time, VideoFrame)>) -> Vec<VideoFrame> {
+        frames
+            .into_iter()
+            .map(|(timestamp, mut frame)| {
+                frame.set_pts(timestamp);
+                frame
+            })
+            .collect()
+    }
+}
+
+/// Statistics of a [`FrameRateConverter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameRateStats {
+    input: u64,
+    output: u64,
+    duplicated: u64,
+    dropped: u64,
+}
+
+impl FrameRateStats {
+    /// Number of frames pushed into the converter.
+    #[inline]
+    pub fn input(&self) -> u64 {
+        self.input
+    }
+
+    /// Number of frames emitted by the converter.
+    #[inline]
+    pub fn output(&self) -> u64 {
+        self.output
+    }
+
+    /// Number of extra copies of frames that were emitted to fill gaps.
+    #[inline]
+    pub fn duplicated(&self) -> u64 {
+        self.duplicated
+    }
+
+    /// Number of frames that were not emitted at all.
+    #[inline]
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Frame that is waiting to be emitted, with the number of times it was emitted so far.
+struct PendingFrame<T> {
+    frame: T,
+    outputs: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(time: i64) -> Time {
+        Time::new(Some(time), AvRational::new(1, 1000))
+    }
+
+    fn slots(frames: &[(Time, u32)]) -> Vec<(i64, u32)> {
+        frames
+            .iter()
+            .map(|(time, frame)| (time.into_value().unwrap(), *frame))
+            .collect()
+    }
+
+    #[test]
+    fn passes_through_matching_frame_rate() {
+        let mut converter = FrameRateConverter::new((25, 1)).unwrap();
+        let mut out = Vec::new();
+        for i in 0..5 {
+            out.extend(converter.push(millis(i * 40), i as u32));
+        }
+        out.extend(converter.flush());
+        assert_eq!(slots(&out), vec![(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)]);
+        let stats = converter.stats();
+        assert_eq!(stats.input(), 5);
+        assert_eq!(stats.output(), 5);
+        assert_eq!(stats.duplicated(), 0);
+        assert_eq!(stats.dropped(), 0);
+    }
+
+    #[test]
+    fn duplicates_frames_when_upsampling() {
+        let mut converter = FrameRateConverter::new((30, 1)).unwrap();
+        let mut out = Vec::new();
+        for i in 0..6 {
+            out.extend(converter.push(millis(i * 40), i as u32));
+        }
+        out.extend(converter.flush());
+        // Six frames at 25 fps span 240 ms, which is 7.2 slots at 30 fps.
+        assert_eq!(
+            slots(&out),
+            vec![(0, 0), (1, 1), (2, 2), (3, 2), (4, 3), (5, 4), (6, 5)]
+        );
+        assert_eq!(converter.stats().duplicated(), 1);
+        assert_eq!(converter.stats().dropped(), 0);
+    }
+
+    #[test]
+    fn drops_frames_when_downsampling() {
+        let mut converter = FrameRateConverter::new((10, 1)).unwrap();
+        let mut out = Vec::new();
+        for i in 0..6 {
+            out.extend(converter.push(millis(i * 50), i as u32));
+        }
+        out.extend(converter.flush());
+        assert_eq!(slots(&out), vec![(0, 0), (1, 2), (2, 4), (3, 5)]);
+        assert_eq!(converter.stats().dropped(), 2);
+        assert_eq!(converter.stats().duplicated(), 0);
+    }
+
+    #[test]
+    fn fills_gaps_in_jittery_input() {
+        let mut converter = FrameRateConverter::new((10, 1)).unwrap();
+        let mut out = Vec::new();
+        for (time, frame) in [(0, 0), (110, 1), (390, 2), (400, 3)] {
+            out.extend(converter.push(millis(time), frame));
+        }
+        out.extend(converter.flush());
+        assert_eq!(slots(&out), vec![(0, 0), (1, 1), (2, 1), (3, 1), (4, 3)]);
+        assert_eq!(converter.stats().duplicated(), 2);
+        assert_eq!(converter.stats().dropped(), 1);
+    }
+
+    #[test]
+    fn starts_at_slot_of_first_frame() {
+        let mut converter = FrameRateConverter::new((30000, 1001)).unwrap();
+        assert!(converter.push(millis(10_010), 0).is_empty());
+        let out = converter.flush();
+        assert_eq!(slots(&out), vec![(300, 0)]);
+        assert_eq!(converter.time_base(), AvRational::new(1001, 30000));
+    }
+
+    #[test]
+    fn rejects_non_positive_frame_rate() {
+        assert!(FrameRateConverter::<u32>::new((0, 1)).is_err());
+        assert!(FrameRateConverter::<u32>::new((-30, 1)).is_err());
+        assert!(FrameRateConverter::<u32>::new((30, 0)).is_err());
+        assert!(FrameRateConverter::<u32>::new((-30, -1)).is_ok());
+    }
+}
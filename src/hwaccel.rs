@@ -6,6 +6,7 @@ use crate::ffi_hwaccel;
 type Result<T> = std::result::Result<T, Error>;
 
 pub(crate) struct HardwareAccelerationContext {
+    device_type: HardwareAccelerationDeviceType,
     pixel_format: ffmpeg::util::format::Pixel,
     _hardware_device_context: ffi_hwaccel::HardwareDeviceContext,
 }
@@ -15,22 +16,30 @@ impl HardwareAccelerationContext {
         decoder: &mut ffmpeg::codec::Context,
         device_type: HardwareAccelerationDeviceType,
     ) -> Result<Self> {
+        let av_device_type = ffmpeg::ffi::AVHWDeviceType::try_from(device_type)?;
         let codec = ffmpeg::codec::decoder::find(decoder.id()).ok_or(Error::UninitializedCodec)?;
         let pixel_format =
-            ffi_hwaccel::codec_find_corresponding_hwaccel_pixfmt(&codec, device_type)
+            ffi_hwaccel::codec_find_corresponding_hwaccel_pixfmt(&codec, av_device_type)
                 .ok_or(Error::UnsupportedCodecHardwareAccelerationDeviceType)?;
 
-        ffi_hwaccel::codec_context_hwaccel_set_get_format(decoder, pixel_format);
+        // Create the device before touching the decoder, so that the decoder can still fall back
+        // to software decoding if there is no such device on this machine.
+        let hardware_device_context = ffi_hwaccel::HardwareDeviceContext::new(av_device_type)?;
 
-        let hardware_device_context = ffi_hwaccel::HardwareDeviceContext::new(device_type)?;
+        ffi_hwaccel::codec_context_hwaccel_set_get_format(decoder, pixel_format);
         ffi_hwaccel::codec_context_hwaccel_set_hw_device_ctx(decoder, &hardware_device_context);
 
         Ok(HardwareAccelerationContext {
+            device_type,
             pixel_format,
             _hardware_device_context: hardware_device_context,
         })
     }
 
+    pub(crate) fn device_type(&self) -> HardwareAccelerationDeviceType {
+        self.device_type
+    }
+
     pub(crate) fn format(&self) -> ffmpeg::util::format::Pixel {
         self.pixel_format
     }
@@ -74,6 +83,33 @@ impl HardwareAccelerationDeviceType {
     pub fn list_available() -> Vec<HardwareAccelerationDeviceType> {
         ffi_hwaccel::hwdevice_list_available_device_types()
     }
+
+    /// Whether or not a device of this type can actually be opened on this system. Unlike
+    /// [`HardwareAccelerationDeviceType::is_available`], which only checks whether ffmpeg was built
+    /// with support for the device type, this checks for the presence of the hardware (and its
+    /// drivers).
+    pub fn is_usable(self) -> bool {
+        ffmpeg::ffi::AVHWDeviceType::try_from(self)
+            .ok()
+            .and_then(|device_type| ffi_hwaccel::HardwareDeviceContext::new(device_type).ok())
+            .is_some()
+    }
+
+    /// List the codecs that can be decoded with this device type. Returns an empty list if the
+    /// device type is not supported.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// for device_type in HardwareAccelerationDeviceType::list_available() {
+    ///     println!("{device_type:?}: {:?}", device_type.list_supported_codecs());
+    /// }
+    /// ```
+    pub fn list_supported_codecs(self) -> Vec<ffmpeg::codec::Id> {
+        ffmpeg::ffi::AVHWDeviceType::try_from(self)
+            .map(ffi_hwaccel::codec_list_supporting_hwaccel)
+            .unwrap_or_default()
+    }
 }
 
 impl HardwareAccelerationDeviceType {
@@ -98,9 +134,11 @@ impl HardwareAccelerationDeviceType {
     }
 }
 
-impl From<HardwareAccelerationDeviceType> for ffmpeg::ffi::AVHWDeviceType {
-    fn from(value: HardwareAccelerationDeviceType) -> Self {
-        match value {
+impl TryFrom<HardwareAccelerationDeviceType> for ffmpeg::ffi::AVHWDeviceType {
+    type Error = Error;
+
+    fn try_from(value: HardwareAccelerationDeviceType) -> Result<Self> {
+        Ok(match value {
             HardwareAccelerationDeviceType::Vdpau => {
                 ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VDPAU
             }
@@ -131,9 +169,10 @@ impl From<HardwareAccelerationDeviceType> for ffmpeg::ffi::AVHWDeviceType {
             HardwareAccelerationDeviceType::MediaCodec => {
                 ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_MEDIACODEC
             }
+            // FIXME: Not available in the ffmpeg versions before 7 that we still support.
             HardwareAccelerationDeviceType::D3D12Va => {
-                unimplemented!()
+                return Err(Error::UnsupportedHardwareAccelerationDeviceType)
             }
-        }
+        })
     }
 }
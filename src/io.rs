@@ -4,6 +4,7 @@ use ffmpeg::codec::packet::Packet as AvPacket;
 use ffmpeg::ffi::AV_TIME_BASE_Q;
 use ffmpeg::format::context::{Input as AvInput, Output as AvOutput};
 use ffmpeg::media::Type as AvMediaType;
+use ffmpeg::util::mathematics::rescale::TIME_BASE;
 use ffmpeg::Error as AvError;
 use ffmpeg_next::ffi::av_seek_frame;
 
@@ -13,6 +14,7 @@ use crate::location::Location;
 use crate::options::Options;
 use crate::packet::Packet;
 use crate::stream::StreamInfo;
+use crate::time::Time;
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -173,6 +175,22 @@ impl Reader {
         }
     }
 
+    /// Seek to the last keyframe at or before a timestamp, so that everything that is needed to
+    /// decode the frame at the timestamp is read from there on.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Timestamp to seek to.
+    pub fn seek_to_keyframe_before(&mut self, timestamp: Time) -> Result<()> {
+        let timestamp = timestamp
+            .aligned_with_rational(TIME_BASE)
+            .into_value()
+            .ok_or(Error::InvalidTimeRange)?;
+        self.input
+            .seek(timestamp, ..timestamp)
+            .map_err(Error::BackendError)
+    }
+
     /// Seek to start of reader. This function performs best effort seeking to the start of the
     /// file.
     pub fn seek_to_start(&mut self) -> Result<()> {
@@ -188,6 +206,25 @@ impl Reader {
             .ok_or(AvError::StreamNotFound)?
             .index())
     }
+
+    /// Find the best subtitle stream and return the index.
+    pub fn best_subtitle_stream_index(&self) -> Result<usize> {
+        Ok(self
+            .input
+            .streams()
+            .best(AvMediaType::Subtitle)
+            .ok_or(AvError::StreamNotFound)?
+            .index())
+    }
+
+    /// Get the indices of all subtitle streams, for example to pick one by language.
+    pub fn subtitle_stream_indices(&self) -> Vec<usize> {
+        self.input
+            .streams()
+            .filter(|stream| stream.parameters().medium() == AvMediaType::Subtitle)
+            .map(|stream| stream.index())
+            .collect()
+    }
 }
 
 unsafe impl Send for Reader {}
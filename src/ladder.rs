@@ -0,0 +1,331 @@
+use crate::encode::{EncodeFlags, Encoder, EncoderBuilder, Settings};
+use crate::error::Error;
+use crate::frame::{PixelFormat, RawFrame, VideoFrame, FRAME_PIXEL_FORMAT};
+use crate::location::Location;
+use crate::options::Options;
+use crate::resize::Resize;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A single rendition of a [`LadderEncoder`].
+pub struct Rendition<'a> {
+    destination: Location,
+    settings: Settings,
+    resize: Option<Resize>,
+    options: Option<&'a Options>,
+    format: Option<&'a str>,
+}
+
+impl<'a> Rendition<'a> {
+    /// Create a rendition with the specified destination and settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `destination` - Where to encode to.
+    /// * `settings` - Encoding settings.
+    pub fn new(destination: impl Into<Location>, settings: Settings) -> Self {
+        Self {
+            destination: destination.into(),
+            settings,
+            resize: None,
+            options: None,
+            format: None,
+        }
+    }
+
+    /// Derive the output size from the source size instead of using the size in the settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `resize` - Resizing to apply.
+    pub fn with_resize(mut self, resize: Resize) -> Self {
+        self.resize = Some(resize);
+        self
+    }
+
+    /// Set the output options for the rendition.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The output options.
+    pub fn with_options(mut self, options: &'a Options) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Set the container format for the rendition.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Container format to use.
+    pub fn with_format(mut self, format: &'a str) -> Self {
+        self.format = Some(format);
+        self
+    }
+}
+
+/// Builds a [`LadderEncoder`].
+pub struct LadderEncoderBuilder<'a> {
+    source_size: (u32, u32),
+    input_pixel_format: Option<PixelFormat>,
+    keyframe_interval: Option<u64>,
+    renditions: Vec<Rendition<'a>>,
+}
+
+impl<'a> LadderEncoderBuilder<'a> {
+    /// Create a ladder encoder builder for frames of the specified size.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of source frames.
+    /// * `height` - Height of source frames.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            source_size: (width, height),
+            input_pixel_format: None,
+            keyframe_interval: None,
+            renditions: Vec::new(),
+        }
+    }
+
+    /// Set the pixel format of source frames, so that the renditions can set up their converters
+    /// in advance. Defaults to RGB24.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixel_format` - Pixel format of source frames.
+    pub fn with_input_pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+        self.input_pixel_format = Some(pixel_format);
+        self
+    }
+
+    /// Set the keyframe interval that is shared by all renditions. Defaults to the keyframe
+    /// interval in the settings of the first rendition.
+    ///
+    /// # Arguments
+    ///
+    /// * `keyframe_interval` - Keyframe interval in frames.
+    pub fn with_keyframe_interval(mut self, keyframe_interval: u64) -> Self {
+        self.keyframe_interval = Some(keyframe_interval);
+        self
+    }
+
+    /// Add a rendition to the ladder.
+    ///
+    /// # Arguments
+    ///
+    /// * `rendition` - Rendition to add.
+    pub fn with_rendition(mut self, rendition: Rendition<'a>) -> Self {
+        self.renditions.push(rendition);
+        self
+    }
+
+    /// Build a [`LadderEncoder`].
+    pub fn build(self) -> Result<LadderEncoder> {
+        let keyframe_interval = self.keyframe_interval.or_else(|| {
+            self.renditions
+                .first()
+                .map(|rendition| rendition.settings.keyframe_interval())
+        });
+        let input_pixel_format = self.input_pixel_format.unwrap_or(FRAME_PIXEL_FORMAT);
+        let (source_width, source_height) = self.source_size;
+
+        let renditions = self
+            .renditions
+            .into_iter()
+            .map(|rendition| {
+                let mut settings = rendition.settings;
+                if let Some(resize) = rendition.resize {
+                    let (width, height) = resize
+                        .compute_for(self.source_size)
+                        .ok_or(Error::InvalidResizeParameters)?;
+                    settings.set_size(width, height);
+                }
+                if let Some(keyframe_interval) = keyframe_interval {
+                    settings.set_keyframe_interval(keyframe_interval);
+                }
+                // Keyframes are only placed by the frame counter, which is the same for every
+                // rendition. Scene cut detection would insert keyframes at different positions in
+                // each rendition, and open GOPs cannot be switched at.
+                let gop = settings
+                    .gop()
+                    .clone()
+                    .with_scene_cut(false)
+                    .with_closed(true);
+                settings.set_gop(gop);
+
+                let mut builder = EncoderBuilder::new(rendition.destination, settings)
+                    .with_input_pixel_format(input_pixel_format)
+                    .with_input_size(source_width, source_height);
+                if let Some(options) = rendition.options {
+                    builder = builder.with_options(options);
+                }
+                if let Some(format) = rendition.format {
+                    builder = builder.with_format(format);
+                }
+                builder.build()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(LadderEncoder { renditions })
+    }
+}
+
+/// Encodes one stream of frames into multiple renditions with different sizes and settings, such
+/// as the variants of an HLS or DASH adaptive bitrate ladder. The source only needs to be decoded
+/// once, and keyframes are placed at the same frames in every rendition so that players can
+/// switch between them at segment boundaries.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut decoder = DecoderBuilder::new(Path::new("input.mp4"))
+///     .with_pixel_format(PixelFormat::YUV420P)
+///     .build()?;
+/// let (width, height) = decoder.size_out();
+/// let mut ladder = LadderEncoderBuilder::new(width, height)
+///     .with_input_pixel_format(PixelFormat::YUV420P)
+///     .with_keyframe_interval(48)
+///     .with_rendition(
+///         Rendition::new(Path::new("1080p.mp4"), Settings::preset_h264_yuv420p(1920, 1080, false))
+///             .with_resize(Resize::FitEven(1920, 1080)),
+///     )
+///     .with_rendition(
+///         Rendition::new(Path::new("360p.mp4"), Settings::preset_h264_yuv420p(640, 360, false))
+///             .with_resize(Resize::FitEven(640, 360)),
+///     )
+///     .build()?;
+/// for frame in decoder.decode_frame_iter() {
+///     ladder.encode_frame(frame?)?;
+/// }
+/// ladder.finish()?;
+/// ```
+pub struct LadderEncoder {
+    renditions: Vec<Encoder>,
+}
+
+impl LadderEncoder {
+    /// Encode a single [`VideoFrame`] in every rendition. The frame PTS is used as timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    pub fn encode_frame(&mut self, frame: VideoFrame) -> Result<()> {
+        self.encode_with(frame, EncodeFlags::default())
+    }
+
+    /// Encode a single [`VideoFrame`] in every rendition with per-frame flags. Forcing a keyframe
+    /// forces it in every rendition, so they stay aligned.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    /// * `flags` - Flags for this frame.
+    pub fn encode_with(&mut self, frame: VideoFrame, flags: EncodeFlags) -> Result<()> {
+        self.for_each_rendition(frame, |encoder, frame| encoder.encode_with(frame, flags))
+    }
+
+    /// Encode a single raw frame in every rendition. See [`Encoder::encode_raw`].
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    pub fn encode_raw(&mut self, frame: RawFrame) -> Result<()> {
+        self.for_each_rendition(frame, |encoder, frame| encoder.encode_raw(frame))
+    }
+
+    /// Finish every rendition. See [`Encoder::finish`].
+    pub fn finish(&mut self) -> Result<()> {
+        for encoder in self.renditions.iter_mut() {
+            encoder.finish()?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the encoders of the renditions, in the order in which they were added. This can be
+    /// used to look at the [`Encoder::emitted_packets`] of each rendition.
+    pub fn renditions(&self) -> &[Encoder] {
+        &self.renditions
+    }
+
+    /// Pass a frame to every rendition. Every rendition but the last gets its own copy, since the
+    /// encoders take ownership of their frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to encode.
+    /// * `encode` - Function that encodes a frame with the encoder of a rendition.
+    fn for_each_rendition<F: Clone>(
+        &mut self,
+        frame: F,
+        mut encode: impl FnMut(&mut Encoder, F) -> Result<()>,
+    ) -> Result<()> {
+        if let Some((last, others)) = self.renditions.split_last_mut() {
+            for encoder in others {
+                encode(encoder, frame.clone())?;
+            }
+            encode(last, frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    #[test]
+    fn aligns_keyframes_of_renditions() {
+        let (width, height) = testing::SIZE;
+        let paths = [
+            testing::temp_path("ladder-full.mp4"),
+            testing::temp_path("ladder-half.mp4"),
+        ];
+        let ladder = LadderEncoderBuilder::new(width as u32, height as u32)
+            .with_input_pixel_format(PixelFormat::YUV420P)
+            .with_keyframe_interval(5)
+            .with_rendition(Rendition::new(
+                paths[0].as_path(),
+                Settings::preset_h264_yuv420p(width, height, false),
+            ))
+            .with_rendition(
+                Rendition::new(
+                    paths[1].as_path(),
+                    Settings::preset_h264_yuv420p(width / 2, height / 2, false),
+                )
+                .with_resize(Resize::Exact(width as u32 / 2, height as u32 / 2)),
+            )
+            .build();
+        let keyframes = ladder.and_then(|mut ladder| {
+            for index in 0..20 {
+                ladder.encode_frame(testing::generate_frame(index))?;
+            }
+            ladder.finish()?;
+            Ok(ladder
+                .renditions()
+                .iter()
+                .map(|encoder| {
+                    let mut packets = encoder
+                        .emitted_packets()
+                        .iter()
+                        .map(|packet| (packet.pts().into_value(), packet.is_key()))
+                        .collect::<Vec<_>>();
+                    packets.sort();
+                    packets
+                })
+                .collect::<Vec<_>>())
+        });
+        for path in &paths {
+            let _ = std::fs::remove_file(path);
+        }
+
+        let keyframes = keyframes.unwrap();
+        assert_eq!(keyframes[0].len(), 20);
+        assert_eq!(keyframes[0], keyframes[1]);
+        assert_eq!(keyframes[0].iter().filter(|(_, is_key)| *is_key).count(), 4);
+    }
+}
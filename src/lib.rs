@@ -1,28 +1,45 @@
+pub mod annotate;
+pub mod archive;
+pub mod canvas;
 pub mod decode;
+pub mod edit;
 pub mod encode;
 pub mod error;
 pub mod extradata;
+#[cfg(feature = "filter")]
+pub mod filter;
 pub mod frame;
+pub mod framerate;
 pub mod hwaccel;
 pub mod init;
 pub mod io;
+pub mod ladder;
 pub mod location;
+pub mod mask;
 pub mod mux;
 pub mod options;
+pub mod overlay;
 pub mod packet;
 pub mod resize;
 pub mod rtp;
 pub mod stream;
+pub mod subtitle;
+#[cfg(feature = "ndarray")]
+pub mod tensor;
 pub mod time;
 
 mod ffi;
 mod ffi_hwaccel;
+mod font;
+#[cfg(test)]
+mod testing;
 
 pub use decode::{Decoder, DecoderBuilder};
 pub use encode::{Encoder, EncoderBuilder};
 pub use error::Error;
 #[cfg(feature = "ndarray")]
 pub use frame::Frame;
+pub use frame::VideoFrame;
 pub use init::init;
 pub use io::{Reader, ReaderBuilder, Writer, WriterBuilder};
 pub use location::{Location, Url};
@@ -0,0 +1,757 @@
+use crate::canvas::{polygon_spans, Canvas, Color, Component};
+use crate::error::Error;
+#[cfg(feature = "ndarray")]
+use crate::frame::Frame;
+use crate::frame::{RawFrame, VideoFrame};
+use crate::time::Time;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Region of a frame to mask.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskRegion {
+    /// Rectangle with the position of the top left corner and the size.
+    Rect {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    /// Polygon with its corners as `(x, y)` coordinates.
+    Polygon(Vec<(i32, i32)>),
+}
+
+impl MaskRegion {
+    /// Create a rectangular region.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position of the left edge.
+    /// * `y` - Vertical position of the top edge.
+    /// * `width` - Width of rectangle.
+    /// * `height` - Height of rectangle.
+    pub fn rect(x: i32, y: i32, width: u32, height: u32) -> Self {
+        MaskRegion::Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Create a polygonal region.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - Corners as `(x, y)` coordinates.
+    pub fn polygon(points: impl IntoIterator<Item = (i32, i32)>) -> Self {
+        MaskRegion::Polygon(points.into_iter().collect())
+    }
+
+    /// Interpolate between this region and another one. Rectangles are interpolated with
+    /// rectangles and polygons with polygons with the same number of corners. Other regions cannot
+    /// be interpolated, in which case this region is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Region to interpolate towards.
+    /// * `factor` - Interpolation factor, from 0 (this region) to 1 (the other region).
+    pub fn interpolate(&self, other: &MaskRegion, factor: f64) -> MaskRegion {
+        let lerp = |from: i64, to: i64| (from as f64 + (to - from) as f64 * factor).round() as i64;
+        match (self, other) {
+            (
+                MaskRegion::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+                MaskRegion::Rect {
+                    x: other_x,
+                    y: other_y,
+                    width: other_width,
+                    height: other_height,
+                },
+            ) => MaskRegion::Rect {
+                x: lerp(*x as i64, *other_x as i64) as i32,
+                y: lerp(*y as i64, *other_y as i64) as i32,
+                width: lerp(*width as i64, *other_width as i64) as u32,
+                height: lerp(*height as i64, *other_height as i64) as u32,
+            },
+            (MaskRegion::Polygon(points), MaskRegion::Polygon(other_points))
+                if points.len() == other_points.len() =>
+            {
+                MaskRegion::Polygon(
+                    points
+                        .iter()
+                        .zip(other_points)
+                        .map(|((x, y), (other_x, other_y))| {
+                            (
+                                lerp(*x as i64, *other_x as i64) as i32,
+                                lerp(*y as i64, *other_y as i64) as i32,
+                            )
+                        })
+                        .collect(),
+                )
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// Compute the horizontal spans of pixels in the region, clipped to the canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of canvas.
+    /// * `height` - Height of canvas.
+    ///
+    /// # Return value
+    ///
+    /// Spans as row, start column (inclusive) and end column (exclusive).
+    fn spans(&self, width: u32, height: u32) -> Vec<(i32, i32, i32)> {
+        let spans = match self {
+            MaskRegion::Rect {
+                x,
+                y,
+                width: rect_width,
+                height: rect_height,
+            } => {
+                let bottom = (*y as i64 + *rect_height as i64).min(height as i64) as i32;
+                let right = (*x as i64 + *rect_width as i64).min(i32::MAX as i64) as i32;
+                ((*y).max(0)..bottom).map(|row| (row, *x, right)).collect()
+            }
+            MaskRegion::Polygon(points) => polygon_spans(points, height),
+        };
+        spans
+            .into_iter()
+            .map(|(row, start, end)| (row, start.max(0), end.min(width as i32)))
+            .filter(|(_, start, end)| start < end)
+            .collect()
+    }
+}
+
+/// How to hide the content of a masked region.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskStyle {
+    /// Replace the region with blocks of the average color, with the given size in pixels. Blocks
+    /// are aligned to the frame, not to the region, so that they do not shimmer when the region
+    /// moves.
+    Pixelate { block_size: u32 },
+    /// Gaussian blur with the given standard deviation in pixels. A standard deviation that is not
+    /// positive or not finite leaves the region as it is.
+    Blur { sigma: f32 },
+    /// Fill the region with a color.
+    Fill(Color),
+}
+
+impl MaskStyle {
+    /// Mask a region of a canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to mask.
+    /// * `region` - Region to mask.
+    pub fn draw(&self, canvas: &mut Canvas, region: &MaskRegion) {
+        let spans = region.spans(canvas.width(), canvas.height());
+        if let MaskStyle::Fill(color) = self {
+            canvas.fill_spans(spans, *color);
+            return;
+        }
+
+        let Some(region) = RegionBitmap::new(&spans) else {
+            return;
+        };
+        for component in canvas.components() {
+            let plane = ComponentPlane::new(canvas, component);
+            match *self {
+                MaskStyle::Pixelate { block_size } => {
+                    pixelate(canvas, &plane, &region, block_size.max(1))
+                }
+                MaskStyle::Blur { sigma } => blur(canvas, &plane, &region, sigma),
+                MaskStyle::Fill(_) => unreachable!(),
+            }
+        }
+    }
+}
+
+/// A region that moves over time, such as a tracked face or license plate. The region is given
+/// at keyframes, and interpolated between them.
+///
+/// The track is active from its first keyframe until its last keyframe, or until the end set with
+/// [`MaskTrack::with_end`].
+///
+/// # Example
+///
+/// ```ignore
+/// let track = MaskTrack::new()
+///     .with_keyframe(Time::from_secs(1.0), MaskRegion::rect(100, 100, 40, 40))
+///     .with_keyframe(Time::from_secs(2.0), MaskRegion::rect(200, 120, 50, 50));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MaskTrack {
+    keyframes: Vec<(Time, MaskRegion)>,
+    end: Option<Time>,
+    interpolate: bool,
+}
+
+impl MaskTrack {
+    /// Create an empty track.
+    pub fn new() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            end: None,
+            interpolate: true,
+        }
+    }
+
+    /// Add a keyframe. Keyframes may be added in any order. Keyframes with a timestamp without
+    /// value are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - Time of keyframe.
+    /// * `region` - Region at that time.
+    pub fn with_keyframe(mut self, time: Time, region: MaskRegion) -> Self {
+        self.push(time, region);
+        self
+    }
+
+    /// Add a keyframe. Keyframes may be added in any order. Keyframes with a timestamp without
+    /// value are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - Time of keyframe.
+    /// * `region` - Region at that time.
+    pub fn push(&mut self, time: Time, region: MaskRegion) {
+        if !time.has_value() {
+            return;
+        }
+        let index = self.keyframes.partition_point(|(keyframe_time, _)| {
+            keyframe_time.as_secs_f64() <= time.as_secs_f64()
+        });
+        self.keyframes.insert(index, (time, region));
+    }
+
+    /// Keep masking the region of the last keyframe until the given time (inclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `end` - End of track.
+    pub fn with_end(mut self, end: Time) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Set whether to interpolate regions between keyframes. If not, the region of a keyframe is
+    /// used until the next keyframe. Defaults to `true`.
+    ///
+    /// # Arguments
+    ///
+    /// * `interpolate` - Whether to interpolate.
+    pub fn with_interpolation(mut self, interpolate: bool) -> Self {
+        self.interpolate = interpolate;
+        self
+    }
+
+    /// Get the region at a given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - Time.
+    ///
+    /// # Return value
+    ///
+    /// The region, or `None` if the track is not active at that time.
+    pub fn region_at(&self, time: Time) -> Option<MaskRegion> {
+        let (first_time, _) = self.keyframes.first()?;
+        let (last_time, last_region) = self.keyframes.last()?;
+        let time = time.has_value().then(|| time.as_secs_f64())?;
+        let end = self.end.unwrap_or(*last_time).as_secs_f64();
+        if time < first_time.as_secs_f64() || time > end.max(last_time.as_secs_f64()) {
+            return None;
+        }
+
+        let next = self
+            .keyframes
+            .partition_point(|(keyframe_time, _)| keyframe_time.as_secs_f64() <= time);
+        if next == self.keyframes.len() {
+            return Some(last_region.clone());
+        }
+        let (previous_time, previous_region) = &self.keyframes[next - 1];
+        let (next_time, next_region) = &self.keyframes[next];
+        if !self.interpolate {
+            return Some(previous_region.clone());
+        }
+        let (previous_time, next_time) = (previous_time.as_secs_f64(), next_time.as_secs_f64());
+        let factor = (time - previous_time) / (next_time - previous_time);
+        Some(previous_region.interpolate(next_region, factor))
+    }
+}
+
+impl Default for MaskTrack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Masks regions of frames before they are encoded, for example to hide faces and license plates.
+///
+/// # Example
+///
+/// ```ignore
+/// let mask = Mask::new(MaskStyle::Blur { sigma: 8.0 }).with_track(face_track);
+/// for frame in decoder.decode_frame_iter() {
+///     let mut frame = frame?;
+///     mask.apply(&mut frame)?;
+///     encoder.encode_frame(frame)?;
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mask {
+    style: MaskStyle,
+    tracks: Vec<MaskTrack>,
+}
+
+impl Mask {
+    /// Create a mask without tracks.
+    ///
+    /// # Arguments
+    ///
+    /// * `style` - How to hide masked regions.
+    pub fn new(style: MaskStyle) -> Self {
+        Self {
+            style,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Add a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - Track to add.
+    pub fn with_track(mut self, track: MaskTrack) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    /// Add a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - Track to add.
+    pub fn push_track(&mut self, track: MaskTrack) {
+        self.tracks.push(track);
+    }
+
+    /// Get the regions that are masked at a given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` - Time.
+    pub fn regions_at(&self, time: Time) -> Vec<MaskRegion> {
+        self.tracks
+            .iter()
+            .filter_map(|track| track.region_at(time))
+            .collect()
+    }
+
+    /// Mask the regions that are active at a given time on a canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to mask.
+    /// * `timestamp` - Timestamp of the frame behind the canvas.
+    pub fn draw(&self, canvas: &mut Canvas, timestamp: Time) {
+        for region in self.regions_at(timestamp) {
+            self.style.draw(canvas, &region);
+        }
+    }
+
+    /// Mask a [`VideoFrame`]. The frame PTS is used as timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to mask. See [`Canvas`] for the supported pixel formats.
+    pub fn apply(&self, frame: &mut VideoFrame) -> Result<()> {
+        let timestamp = frame.pts();
+        self.draw(&mut Canvas::from_frame(frame)?, timestamp);
+        Ok(())
+    }
+
+    /// Mask a raw frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to mask. See [`Canvas`] for the supported pixel formats.
+    /// * `timestamp` - Frame timestamp.
+    pub fn apply_raw(&self, frame: &mut RawFrame, timestamp: Time) -> Result<()> {
+        self.draw(&mut Canvas::from_raw_frame(frame)?, timestamp);
+        Ok(())
+    }
+
+    /// Mask an RGB24 `ndarray` frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame in `HWC` format and standard layout.
+    /// * `timestamp` - Frame timestamp.
+    #[cfg(feature = "ndarray")]
+    pub fn apply_ndarray(&self, frame: &mut Frame, timestamp: Time) -> Result<()> {
+        self.draw(&mut Canvas::from_ndarray(frame)?, timestamp);
+        Ok(())
+    }
+}
+
+/// Pixels of a region in full resolution, within its bounding box.
+struct RegionBitmap {
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+    bits: Vec<bool>,
+}
+
+impl RegionBitmap {
+    /// Create a bitmap from the spans of a region.
+    ///
+    /// # Arguments
+    ///
+    /// * `spans` - Spans as row, start column and end column.
+    ///
+    /// # Return value
+    ///
+    /// The bitmap, or `None` if the region is empty.
+    fn new(spans: &[(i32, i32, i32)]) -> Option<Self> {
+        let left = spans.iter().map(|(_, start, _)| *start).min()?;
+        let right = spans.iter().map(|(_, _, end)| *end).max()?;
+        let top = spans.iter().map(|(row, _, _)| *row).min()?;
+        let bottom = spans.iter().map(|(row, _, _)| *row).max()? + 1;
+        let width = (right - left) as usize;
+        let mut bits = vec![false; width * (bottom - top) as usize];
+        for (row, start, end) in spans {
+            let offset = (row - top) as usize * width;
+            bits[offset + (start - left) as usize..offset + (end - left) as usize].fill(true);
+        }
+        Some(Self {
+            left,
+            top,
+            right,
+            bottom,
+            bits,
+        })
+    }
+
+    /// Whether a component pixel lies in the region. A subsampled pixel lies in the region if any
+    /// of the full resolution pixels that share it does, so that the region is covered completely
+    /// in every component.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position in the component.
+    /// * `y` - Vertical position in the component.
+    /// * `shift` - Subsampling of the component.
+    fn contains(&self, x: i32, y: i32, shift: (u32, u32)) -> bool {
+        let (left, right) = (
+            (x << shift.0).max(self.left),
+            ((x + 1) << shift.0).min(self.right),
+        );
+        let (top, bottom) = (
+            (y << shift.1).max(self.top),
+            ((y + 1) << shift.1).min(self.bottom),
+        );
+        let width = (self.right - self.left) as usize;
+        (top..bottom).any(|y| {
+            let offset = (y - self.top) as usize * width;
+            (left..right).any(|x| self.bits[offset + (x - self.left) as usize])
+        })
+    }
+
+    /// Bounding box in component coordinates: left, top, right and bottom (exclusive).
+    ///
+    /// # Arguments
+    ///
+    /// * `shift` - Subsampling of the component.
+    fn bounds(&self, shift: (u32, u32)) -> (i32, i32, i32, i32) {
+        (
+            self.left >> shift.0,
+            self.top >> shift.1,
+            ((self.right - 1) >> shift.0) + 1,
+            ((self.bottom - 1) >> shift.1) + 1,
+        )
+    }
+}
+
+/// A color component with the size of its plane.
+struct ComponentPlane {
+    component: Component,
+    width: i32,
+    height: i32,
+}
+
+impl ComponentPlane {
+    /// Get the component plane of a canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas.
+    /// * `component` - Component.
+    fn new(canvas: &Canvas, component: Component) -> Self {
+        let (shift_x, shift_y) = component.shift;
+        Self {
+            component,
+            width: canvas.width().div_ceil(1 << shift_x) as i32,
+            height: canvas.height().div_ceil(1 << shift_y) as i32,
+        }
+    }
+
+    /// Byte index of a pixel in the plane data.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Horizontal position.
+    /// * `y` - Vertical position.
+    /// * `stride` - Stride of the plane.
+    #[inline]
+    fn index(&self, x: i32, y: i32, stride: usize) -> usize {
+        y as usize * stride + x as usize * self.component.step + self.component.offset
+    }
+}
+
+/// Pixelate a region of a component.
+///
+/// # Arguments
+///
+/// * `canvas` - Canvas.
+/// * `plane` - Component to pixelate.
+/// * `region` - Region to pixelate.
+/// * `block_size` - Block size in full resolution pixels.
+fn pixelate(canvas: &mut Canvas, plane: &ComponentPlane, region: &RegionBitmap, block_size: u32) {
+    let shift = plane.component.shift;
+    // A block larger than the frame covers the whole frame, just like a block of the frame size.
+    let block_width = (block_size.min(canvas.width()) >> shift.0).max(1) as i32;
+    let block_height = (block_size.min(canvas.height()) >> shift.1).max(1) as i32;
+    let (left, top, right, bottom) = region.bounds(shift);
+    let (data, stride) = canvas.plane_mut(plane.component.plane);
+
+    let mut block_top = top - top.rem_euclid(block_height);
+    while block_top < bottom {
+        let rows = block_top..(block_top + block_height).min(plane.height);
+        let mut block_left = left - left.rem_euclid(block_width);
+        while block_left < right {
+            let columns = block_left..(block_left + block_width).min(plane.width);
+            let mut sum = 0u64;
+            for y in rows.clone() {
+                for x in columns.clone() {
+                    sum += data[plane.index(x, y, stride)] as u64;
+                }
+            }
+            let count = (rows.len() * columns.len()) as u64;
+            let average = ((sum + count / 2) / count) as u8;
+            for y in rows.clone() {
+                for x in columns.clone() {
+                    if region.contains(x, y, shift) {
+                        data[plane.index(x, y, stride)] = average;
+                    }
+                }
+            }
+            block_left += block_width;
+        }
+        block_top += block_height;
+    }
+}
+
+/// Blur a region of a component with a separable Gaussian kernel. Pixels outside the region are
+/// read but not changed, and the edges of the plane are extended.
+///
+/// # Arguments
+///
+/// * `canvas` - Canvas.
+/// * `plane` - Component to blur.
+/// * `region` - Region to blur.
+/// * `sigma` - Standard deviation of the kernel in full resolution pixels.
+fn blur(canvas: &mut Canvas, plane: &ComponentPlane, region: &RegionBitmap, sigma: f32) {
+    let shift = plane.component.shift;
+    // Subsampled components are blurred less along each subsampled axis, so that the blur covers
+    // the same area of the frame.
+    let horizontal_kernel = gaussian_kernel(sigma / (1 << shift.0) as f32);
+    let vertical_kernel = gaussian_kernel(sigma / (1 << shift.1) as f32);
+    let horizontal_radius = (horizontal_kernel.len() / 2) as i32;
+    let vertical_radius = (vertical_kernel.len() / 2) as i32;
+    let (left, top, right, bottom) = region.bounds(shift);
+    let (data, stride) = canvas.plane_mut(plane.component.plane);
+
+    // Horizontal pass over all rows that the vertical pass reads.
+    let rows = (top - vertical_radius).max(0)..(bottom + vertical_radius).min(plane.height);
+    let width = (right - left) as usize;
+    let mut horizontal = vec![0.0f32; rows.len() * width];
+    for (row_index, y) in rows.clone().enumerate() {
+        for x in left..right {
+            horizontal[row_index * width + (x - left) as usize] = horizontal_kernel
+                .iter()
+                .enumerate()
+                .map(|(tap, weight)| {
+                    let source_x = (x + tap as i32 - horizontal_radius).clamp(0, plane.width - 1);
+                    weight * data[plane.index(source_x, y, stride)] as f32
+                })
+                .sum();
+        }
+    }
+
+    for y in top..bottom {
+        for x in left..right {
+            if !region.contains(x, y, shift) {
+                continue;
+            }
+            let value: f32 = vertical_kernel
+                .iter()
+                .enumerate()
+                .map(|(tap, weight)| {
+                    let source_y =
+                        (y + tap as i32 - vertical_radius).clamp(rows.start, rows.end - 1);
+                    weight
+                        * horizontal[(source_y - rows.start) as usize * width + (x - left) as usize]
+                })
+                .sum();
+            data[plane.index(x, y, stride)] = value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Compute a normalized Gaussian kernel that covers three standard deviations on each side.
+///
+/// # Arguments
+///
+/// * `sigma` - Standard deviation. If it is not positive or not finite, the kernel leaves pixels
+///   as they are.
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    if !sigma.is_finite() || sigma <= 0.0 {
+        return vec![1.0];
+    }
+    let radius = (3.0 * sigma).ceil() as i32;
+    let weights = (-radius..=radius)
+        .map(|offset| (-(offset * offset) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f32) -> Time {
+        Time::from_secs(secs)
+    }
+
+    #[test]
+    fn interpolates_regions() {
+        let from = MaskRegion::rect(0, 0, 10, 10);
+        let to = MaskRegion::rect(100, -50, 20, 30);
+        assert_eq!(
+            from.interpolate(&to, 0.5),
+            MaskRegion::rect(50, -25, 15, 20)
+        );
+        assert_eq!(from.interpolate(&to, 0.0), from);
+
+        let triangle = MaskRegion::polygon([(0, 0), (10, 0), (0, 10)]);
+        let moved = MaskRegion::polygon([(10, 10), (20, 10), (10, 20)]);
+        assert_eq!(
+            triangle.interpolate(&moved, 0.3),
+            MaskRegion::polygon([(3, 3), (13, 3), (3, 13)])
+        );
+        assert_eq!(triangle.interpolate(&from, 0.5), triangle);
+    }
+
+    #[test]
+    fn tracks_regions_between_keyframes() {
+        let track = MaskTrack::new()
+            .with_keyframe(secs(2.0), MaskRegion::rect(100, 0, 10, 10))
+            .with_keyframe(secs(1.0), MaskRegion::rect(0, 0, 10, 10));
+        assert_eq!(track.region_at(secs(0.5)), None);
+        assert_eq!(
+            track.region_at(secs(1.0)),
+            Some(MaskRegion::rect(0, 0, 10, 10))
+        );
+        assert_eq!(
+            track.region_at(secs(1.25)),
+            Some(MaskRegion::rect(25, 0, 10, 10))
+        );
+        assert_eq!(
+            track.region_at(secs(2.0)),
+            Some(MaskRegion::rect(100, 0, 10, 10))
+        );
+        assert_eq!(track.region_at(secs(2.5)), None);
+
+        let held = track.with_interpolation(false).with_end(secs(3.0));
+        assert_eq!(
+            held.region_at(secs(1.75)),
+            Some(MaskRegion::rect(0, 0, 10, 10))
+        );
+        assert_eq!(
+            held.region_at(secs(2.5)),
+            Some(MaskRegion::rect(100, 0, 10, 10))
+        );
+        assert_eq!(held.region_at(secs(3.5)), None);
+        assert_eq!(MaskTrack::new().region_at(secs(1.0)), None);
+    }
+
+    #[test]
+    fn clips_region_spans() {
+        assert_eq!(
+            MaskRegion::rect(-2, 3, 5, 10).spans(8, 5),
+            vec![(3, 0, 3), (4, 0, 3)]
+        );
+        assert!(MaskRegion::rect(10, 0, 5, 5).spans(8, 5).is_empty());
+    }
+
+    #[test]
+    fn maps_regions_to_subsampled_components() {
+        let region = RegionBitmap::new(&[(1, 1, 4), (2, 1, 4)]).unwrap();
+        assert_eq!(region.bounds((0, 0)), (1, 1, 4, 3));
+        assert_eq!(region.bounds((1, 1)), (0, 0, 2, 2));
+        assert!(region.contains(1, 1, (0, 0)));
+        assert!(region.contains(0, 0, (1, 1)));
+        assert!(region.contains(1, 1, (1, 1)));
+        assert!(!region.contains(2, 0, (1, 1)));
+        assert!(!region.contains(0, 2, (1, 1)));
+    }
+
+    #[test]
+    fn normalizes_gaussian_kernel() {
+        let kernel = gaussian_kernel(2.0);
+        assert_eq!(kernel.len(), 13);
+        assert!((kernel.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(kernel[6] > kernel[5] && kernel[5] == kernel[7]);
+        assert_eq!(gaussian_kernel(0.0), vec![1.0]);
+        assert_eq!(gaussian_kernel(f32::NAN), vec![1.0]);
+        assert_eq!(gaussian_kernel(f32::INFINITY), vec![1.0]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn pixelates_and_blurs_ndarray_frames() {
+        let mut frame = Frame::from_shape_fn((4, 4, 3), |(y, x, _)| (y * 4 + x) as u8 * 10);
+        let mut canvas = Canvas::from_ndarray(&mut frame).unwrap();
+        MaskStyle::Pixelate { block_size: 2 }.draw(&mut canvas, &MaskRegion::rect(0, 0, 2, 4));
+        assert_eq!(frame[[0, 0, 0]], 25);
+        assert_eq!(frame[[1, 1, 1]], 25);
+        assert_eq!(frame[[2, 0, 2]], 105);
+        assert_eq!(frame[[0, 2, 0]], 20);
+
+        // A block larger than the frame averages the whole region at once.
+        let mut frame = Frame::from_shape_fn((4, 4, 3), |(y, x, _)| (y * 4 + x) as u8 * 10);
+        let mut canvas = Canvas::from_ndarray(&mut frame).unwrap();
+        MaskStyle::Pixelate {
+            block_size: u32::MAX,
+        }
+        .draw(&mut canvas, &MaskRegion::rect(0, 0, 4, 4));
+        assert!(frame.iter().all(|value| *value == 75));
+
+        let mut frame = Frame::from_elem((5, 5, 3), 77);
+        let mut canvas = Canvas::from_ndarray(&mut frame).unwrap();
+        MaskStyle::Blur { sigma: 1.5 }.draw(&mut canvas, &MaskRegion::rect(1, 1, 3, 3));
+        assert!(frame.iter().all(|value| *value == 77));
+    }
+}
@@ -74,6 +74,62 @@ impl Options {
         Self(opts)
     }
 
+    /// Default options for a H265 encoder.
+    pub fn preset_h265() -> Self {
+        let mut opts = AvDictionary::new();
+        // Set H265 encoder to the medium preset.
+        opts.set("preset", "medium");
+
+        Self(opts)
+    }
+
+    /// Options for a H265 encoder that are tuned for low-latency encoding such as for real-time
+    /// streaming.
+    pub fn preset_h265_realtime() -> Self {
+        let mut opts = AvDictionary::new();
+        // Set H265 encoder to the medium preset.
+        opts.set("preset", "medium");
+        // Tune for low latency
+        opts.set("tune", "zerolatency");
+
+        Self(opts)
+    }
+
+    /// Default options for a VP9 encoder.
+    pub fn preset_vp9() -> Self {
+        let mut opts = AvDictionary::new();
+        // Use the good quality deadline and enable row based multithreading.
+        opts.set("deadline", "good");
+        opts.set("row-mt", "1");
+
+        Self(opts)
+    }
+
+    /// Options for a VP9 encoder that are tuned for low-latency encoding such as for real-time
+    /// streaming.
+    pub fn preset_vp9_realtime() -> Self {
+        let mut opts = AvDictionary::new();
+        // Use the realtime deadline with the fastest speed setting.
+        opts.set("deadline", "realtime");
+        opts.set("cpu-used", "8");
+        opts.set("row-mt", "1");
+        // Do not buffer frames for alternate reference frames.
+        opts.set("lag-in-frames", "0");
+
+        Self(opts)
+    }
+
+    /// Options for a FFV1 encoder that are suitable for archival.
+    pub fn preset_ffv1_archival() -> Self {
+        let mut opts = AvDictionary::new();
+        // Use FFV1 version 3, which supports slices and error detection.
+        opts.set("level", "3");
+        // Add CRCs to every slice so that damage can be detected.
+        opts.set("slicecrc", "1");
+
+        Self(opts)
+    }
+
     /// Convert back to ffmpeg native dictionary, which can be used with `ffmpeg_next` functions.
     pub(super) fn to_dict(&self) -> AvDictionary<'_> {
         self.0.clone()
@@ -0,0 +1,733 @@
+extern crate ffmpeg_next as ffmpeg;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ffmpeg::Rational as AvRational;
+
+use crate::canvas::{Canvas, Color};
+use crate::error::Error;
+#[cfg(feature = "ndarray")]
+use crate::frame::Frame;
+use crate::frame::{RawFrame, VideoFrame};
+use crate::subtitle::{SubtitleContent, SubtitleCue};
+use crate::time::Time;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Number of milliseconds in a day.
+const MILLISECONDS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// Where to place an overlay element on the frame. The named positions keep the margin of the
+/// [`TextStyle`] from the edges of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+    /// Place the top left corner of the element at the given coordinates.
+    At(i32, i32),
+}
+
+impl Position {
+    /// Compute the top left corner of an element.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Width and height of element.
+    /// * `canvas_size` - Width and height of canvas.
+    /// * `margin` - Distance from the edges of the canvas.
+    fn resolve(self, size: (u32, u32), canvas_size: (u32, u32), margin: u32) -> (i32, i32) {
+        let (width, height) = (size.0 as i64, size.1 as i64);
+        let (canvas_width, canvas_height) = (canvas_size.0 as i64, canvas_size.1 as i64);
+        let margin = margin as i64;
+
+        let left = margin;
+        let center_x = (canvas_width - width) / 2;
+        let right = canvas_width - width - margin;
+        let top = margin;
+        let center_y = (canvas_height - height) / 2;
+        let bottom = canvas_height - height - margin;
+
+        let (x, y) = match self {
+            Position::TopLeft => (left, top),
+            Position::TopCenter => (center_x, top),
+            Position::TopRight => (right, top),
+            Position::Center => (center_x, center_y),
+            Position::BottomLeft => (left, bottom),
+            Position::BottomCenter => (center_x, bottom),
+            Position::BottomRight => (right, bottom),
+            Position::At(x, y) => (x as i64, y as i64),
+        };
+        (x as i32, y as i32)
+    }
+
+    /// Horizontal offset of a line within a block of text, so that lines are aligned to the same
+    /// side of the frame as the element itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `line_width` - Width of line.
+    /// * `text_width` - Width of the widest line.
+    fn line_offset(self, line_width: u32, text_width: u32) -> u32 {
+        match self {
+            Position::TopCenter | Position::Center | Position::BottomCenter => {
+                (text_width - line_width) / 2
+            }
+            Position::TopRight | Position::BottomRight => text_width - line_width,
+            Position::TopLeft | Position::BottomLeft | Position::At(..) => 0,
+        }
+    }
+}
+
+/// Style of text drawn by an overlay element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextStyle {
+    font_size: u32,
+    color: Color,
+    background: Option<Color>,
+    padding: u32,
+    margin: u32,
+}
+
+impl TextStyle {
+    /// Set the font size in pixels. The built-in font is 8 pixels high and is scaled by whole
+    /// numbers, so the font size is rounded down to a multiple of 8. Defaults to 16.
+    ///
+    /// # Arguments
+    ///
+    /// * `font_size` - Font size in pixels.
+    pub fn with_font_size(mut self, font_size: u32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// Set the text color. Defaults to white.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - Text color.
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the color of the box behind the text, or `None` to draw no box. Defaults to
+    /// semi-transparent black.
+    ///
+    /// # Arguments
+    ///
+    /// * `background` - Box color.
+    pub fn with_background(mut self, background: Option<Color>) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Set the space between the text and the edges of the box. Defaults to 4 pixels.
+    ///
+    /// # Arguments
+    ///
+    /// * `padding` - Padding in pixels.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Set the space between the box and the edges of the frame. Defaults to 8 pixels.
+    ///
+    /// # Arguments
+    ///
+    /// * `margin` - Margin in pixels.
+    pub fn with_margin(mut self, margin: u32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Scale of the built-in font for the font size.
+    fn scale(&self) -> u32 {
+        (self.font_size / 8).max(1)
+    }
+
+    /// Draw a block of text with this style, with a box behind it if the style has a background.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to draw onto.
+    /// * `text` - Text to draw. Lines are separated by `\n`.
+    /// * `position` - Where to place the box.
+    fn draw(&self, canvas: &mut Canvas, text: &str, position: Position) {
+        let scale = self.scale();
+        let (text_width, text_height) = Canvas::text_size(text, scale);
+        let box_size = (
+            text_width + 2 * self.padding,
+            text_height + 2 * self.padding,
+        );
+        let (x, y) = position.resolve(box_size, canvas.size(), self.margin);
+
+        if let Some(background) = self.background {
+            canvas.fill_rect(x, y, box_size.0, box_size.1, background);
+        }
+
+        let (_, line_height) = Canvas::text_size("", scale);
+        for (row, line) in text.split('\n').enumerate() {
+            let (line_width, _) = Canvas::text_size(line, scale);
+            canvas.draw_text(
+                x + (self.padding + position.line_offset(line_width, text_width)) as i32,
+                y + (self.padding + row as u32 * line_height) as i32,
+                line,
+                scale,
+                self.color,
+            );
+        }
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            font_size: 16,
+            color: Color::WHITE,
+            background: Some(Color::BLACK.with_alpha(160)),
+            padding: 4,
+            margin: 8,
+        }
+    }
+}
+
+/// Fixed text, such as a camera name.
+#[derive(Debug, Clone)]
+pub struct TextOverlay {
+    text: String,
+    position: Position,
+    style: TextStyle,
+}
+
+impl TextOverlay {
+    /// Create a text overlay element. It is placed in the top left corner by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text to draw. Lines are separated by `\n`.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            position: Position::TopLeft,
+            style: TextStyle::default(),
+        }
+    }
+
+    /// Set the position of the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - Where to place the text.
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Set the style of the text.
+    ///
+    /// # Arguments
+    ///
+    /// * `style` - Text style.
+    pub fn with_style(mut self, style: TextStyle) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+/// The wall-clock time of each frame, formatted with a template. The wall-clock time is the time
+/// at which the stream started plus the frame timestamp.
+///
+/// The template may contain the following fields:
+///
+/// * `%Y` - Year, such as `2024`.
+/// * `%m` - Month, `01` to `12`.
+/// * `%d` - Day of the month, `01` to `31`.
+/// * `%H` - Hour, `00` to `23`.
+/// * `%M` - Minute, `00` to `59`.
+/// * `%S` - Second, `00` to `59`.
+/// * `%L` - Millisecond, `000` to `999`.
+/// * `%F` - Date, same as `%Y-%m-%d`.
+/// * `%T` - Time, same as `%H:%M:%S`.
+/// * `%%` - A literal `%`.
+#[derive(Debug, Clone)]
+pub struct TimestampOverlay {
+    template: String,
+    origin_milliseconds: i64,
+    utc_offset_seconds: i32,
+    position: Position,
+    style: TextStyle,
+}
+
+impl TimestampOverlay {
+    /// Create a timestamp overlay element. It is placed in the top right corner by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - Template for the text, for example `"%F %T.%L"`.
+    /// * `origin` - Wall-clock time of timestamp zero.
+    pub fn new(template: impl Into<String>, origin: SystemTime) -> Self {
+        let origin_milliseconds = match origin.duration_since(UNIX_EPOCH) {
+            Ok(since_epoch) => since_epoch.as_millis() as i64,
+            Err(before_epoch) => -(before_epoch.duration().as_millis() as i64),
+        };
+        Self {
+            template: template.into(),
+            origin_milliseconds,
+            utc_offset_seconds: 0,
+            position: Position::TopRight,
+            style: TextStyle::default(),
+        }
+    }
+
+    /// Set the offset from UTC of the time zone to show the time in. Defaults to UTC.
+    ///
+    /// # Arguments
+    ///
+    /// * `utc_offset_seconds` - Offset from UTC in seconds, such as `3600` for UTC+1.
+    pub fn with_utc_offset(mut self, utc_offset_seconds: i32) -> Self {
+        self.utc_offset_seconds = utc_offset_seconds;
+        self
+    }
+
+    /// Set the position of the timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - Where to place the timestamp.
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Set the style of the timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `style` - Text style.
+    pub fn with_style(mut self, style: TextStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Format the wall-clock time of a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Frame timestamp.
+    ///
+    /// # Return value
+    ///
+    /// The formatted time, or `None` if the timestamp has no value.
+    pub fn format(&self, timestamp: Time) -> Option<String> {
+        let milliseconds = timestamp
+            .with_time_base(AvRational::new(1, 1000))
+            .into_value()?;
+        Some(format_wall_clock(
+            &self.template,
+            self.origin_milliseconds + milliseconds + self.utc_offset_seconds as i64 * 1000,
+        ))
+    }
+}
+
+/// Subtitle cues, shown while they are active. Text cues are drawn with the text style, bitmap
+/// cues are drawn at their own position.
+#[derive(Debug, Clone)]
+pub struct SubtitleOverlay {
+    cues: Vec<SubtitleCue>,
+    canvas_size: Option<(u32, u32)>,
+    position: Position,
+    style: TextStyle,
+}
+
+impl SubtitleOverlay {
+    /// Create a subtitle overlay element. Text cues are placed at the bottom center by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `cues` - Cues to show. Cue times must be relative to the same start as the frame
+    ///   timestamps.
+    pub fn new(cues: impl IntoIterator<Item = SubtitleCue>) -> Self {
+        Self {
+            cues: cues.into_iter().collect(),
+            canvas_size: None,
+            position: Position::BottomCenter,
+            style: TextStyle::default(),
+        }
+    }
+
+    /// Set the size of the canvas that bitmap cues are positioned on, see
+    /// [`SubtitleDecoder::size`](crate::subtitle::SubtitleDecoder::size). Bitmap cues are scaled
+    /// from this size to the frame size. By default, bitmap cues are not scaled.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Canvas width.
+    /// * `height` - Canvas height.
+    pub fn with_canvas_size(mut self, width: u32, height: u32) -> Self {
+        self.canvas_size = Some((width, height));
+        self
+    }
+
+    /// Set the position of text cues.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - Where to place text cues.
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Set the style of text cues.
+    ///
+    /// # Arguments
+    ///
+    /// * `style` - Text style.
+    pub fn with_style(mut self, style: TextStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Get the cues that are shown at a timestamp. Cues without an end time stay on screen.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Frame timestamp.
+    pub fn active_cues(&self, timestamp: Time) -> impl Iterator<Item = &SubtitleCue> + '_ {
+        self.cues.iter().filter(move |cue| {
+            cue.start().has_value()
+                && cue.start().as_secs_f64() <= timestamp.as_secs_f64()
+                && (!cue.end().has_value() || timestamp.as_secs_f64() < cue.end().as_secs_f64())
+        })
+    }
+
+    /// Draw the cues that are shown at a timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to draw onto.
+    /// * `timestamp` - Frame timestamp.
+    fn draw(&self, canvas: &mut Canvas, timestamp: Time) -> Result<()> {
+        let mut lines = Vec::new();
+        for cue in self.active_cues(timestamp) {
+            match cue.content() {
+                SubtitleContent::Bitmap(bitmap) => {
+                    let (canvas_width, canvas_height) = canvas.size();
+                    let (source_width, source_height) =
+                        self.canvas_size.unwrap_or((canvas_width, canvas_height));
+                    let scale_x = |value: u32| {
+                        (value as u64 * canvas_width as u64 / source_width.max(1) as u64) as u32
+                    };
+                    let scale_y = |value: u32| {
+                        (value as u64 * canvas_height as u64 / source_height.max(1) as u64) as u32
+                    };
+                    canvas.draw_rgba(
+                        scale_x(bitmap.x) as i32,
+                        scale_y(bitmap.y) as i32,
+                        (bitmap.width, bitmap.height),
+                        &bitmap.data,
+                        (scale_x(bitmap.width), scale_y(bitmap.height)),
+                    )?;
+                }
+                content => lines.extend(content.text()),
+            }
+        }
+        if !lines.is_empty() {
+            self.style.draw(canvas, &lines.join("\n"), self.position);
+        }
+
+        Ok(())
+    }
+}
+
+/// Element of an [`Overlay`].
+#[derive(Debug, Clone)]
+enum OverlayElement {
+    Text(TextOverlay),
+    Timestamp(TimestampOverlay),
+    Subtitles(SubtitleOverlay),
+}
+
+/// Burns text, timestamps and subtitles into frames before they are encoded. Elements are drawn in
+/// the order in which they were added.
+///
+/// # Example
+///
+/// ```ignore
+/// let overlay = Overlay::new()
+///     .with_text(TextOverlay::new("Camera 1"))
+///     .with_timestamp(
+///         TimestampOverlay::new("%F %T", recording_started_at)
+///             .with_position(Position::BottomRight)
+///             .with_style(TextStyle::default().with_font_size(24)),
+///     );
+/// for frame in decoder.decode_frame_iter() {
+///     let mut frame = frame?;
+///     overlay.apply(&mut frame)?;
+///     encoder.encode_frame(frame)?;
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    elements: Vec<OverlayElement>,
+}
+
+impl Overlay {
+    /// Create an empty overlay.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add fixed text.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Text element.
+    pub fn with_text(mut self, text: TextOverlay) -> Self {
+        self.elements.push(OverlayElement::Text(text));
+        self
+    }
+
+    /// Add a wall-clock timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Timestamp element.
+    pub fn with_timestamp(mut self, timestamp: TimestampOverlay) -> Self {
+        self.elements.push(OverlayElement::Timestamp(timestamp));
+        self
+    }
+
+    /// Add subtitles.
+    ///
+    /// # Arguments
+    ///
+    /// * `subtitles` - Subtitle element.
+    pub fn with_subtitles(mut self, subtitles: SubtitleOverlay) -> Self {
+        self.elements.push(OverlayElement::Subtitles(subtitles));
+        self
+    }
+
+    /// Draw the overlay onto a canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `canvas` - Canvas to draw onto.
+    /// * `timestamp` - Timestamp of the frame behind the canvas.
+    pub fn draw(&self, canvas: &mut Canvas, timestamp: Time) -> Result<()> {
+        for element in &self.elements {
+            match element {
+                OverlayElement::Text(text) => text.style.draw(canvas, &text.text, text.position),
+                OverlayElement::Timestamp(timestamp_overlay) => {
+                    if let Some(text) = timestamp_overlay.format(timestamp) {
+                        timestamp_overlay
+                            .style
+                            .draw(canvas, &text, timestamp_overlay.position);
+                    }
+                }
+                OverlayElement::Subtitles(subtitles) => subtitles.draw(canvas, timestamp)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draw the overlay onto a [`VideoFrame`]. The frame PTS is used as timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to draw onto. See [`Canvas`] for the supported pixel formats.
+    pub fn apply(&self, frame: &mut VideoFrame) -> Result<()> {
+        let timestamp = frame.pts();
+        self.draw(&mut Canvas::from_frame(frame)?, timestamp)
+    }
+
+    /// Draw the overlay onto a raw frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame to draw onto. See [`Canvas`] for the supported pixel formats.
+    /// * `timestamp` - Frame timestamp.
+    pub fn apply_raw(&self, frame: &mut RawFrame, timestamp: Time) -> Result<()> {
+        self.draw(&mut Canvas::from_raw_frame(frame)?, timestamp)
+    }
+
+    /// Draw the overlay onto an RGB24 `ndarray` frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame in `HWC` format and standard layout.
+    /// * `timestamp` - Frame timestamp.
+    #[cfg(feature = "ndarray")]
+    pub fn apply_ndarray(&self, frame: &mut Frame, timestamp: Time) -> Result<()> {
+        self.draw(&mut Canvas::from_ndarray(frame)?, timestamp)
+    }
+}
+
+/// Format a wall-clock time in UTC with a template. See [`TimestampOverlay`] for the fields.
+///
+/// # Arguments
+///
+/// * `template` - Template.
+/// * `milliseconds` - Number of milliseconds since the Unix epoch.
+fn format_wall_clock(template: &str, milliseconds: i64) -> String {
+    let (year, month, day) = civil_from_days(milliseconds.div_euclid(MILLISECONDS_PER_DAY));
+    let time_of_day = milliseconds.rem_euclid(MILLISECONDS_PER_DAY);
+    let hour = time_of_day / 3_600_000;
+    let minute = time_of_day / 60_000 % 60;
+    let second = time_of_day / 1000 % 60;
+    let millisecond = time_of_day % 1000;
+
+    let mut formatted = String::with_capacity(template.len() + 16);
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => formatted.push_str(&format!("{year:04}")),
+            Some('m') => formatted.push_str(&format!("{month:02}")),
+            Some('d') => formatted.push_str(&format!("{day:02}")),
+            Some('H') => formatted.push_str(&format!("{hour:02}")),
+            Some('M') => formatted.push_str(&format!("{minute:02}")),
+            Some('S') => formatted.push_str(&format!("{second:02}")),
+            Some('L') => formatted.push_str(&format!("{millisecond:03}")),
+            Some('F') => formatted.push_str(&format!("{year:04}-{month:02}-{day:02}")),
+            Some('T') => formatted.push_str(&format!("{hour:02}:{minute:02}:{second:02}")),
+            Some('%') => formatted.push('%'),
+            Some(other) => {
+                formatted.push('%');
+                formatted.push(other);
+            }
+            None => formatted.push('%'),
+        }
+    }
+    formatted
+}
+
+/// Convert a number of days since the Unix epoch to a date in the proleptic Gregorian calendar.
+///
+/// # Arguments
+///
+/// * `days` - Number of days since 1970-01-01.
+///
+/// # Return value
+///
+/// Year, month (1 to 12) and day of the month (1 to 31).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // See Howard Hinnant's `civil_from_days` algorithm. Eras are 400 year periods starting at
+    // 0000-03-01, so that leap days are at the end of each year.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(20_454), (2026, 1, 1));
+    }
+
+    #[test]
+    fn formats_wall_clock_templates() {
+        // 2024-02-29 13:45:07.089 UTC.
+        let milliseconds = 19_782 * MILLISECONDS_PER_DAY + 49_507_089;
+        assert_eq!(
+            format_wall_clock("%F %T.%L", milliseconds),
+            "2024-02-29 13:45:07.089"
+        );
+        assert_eq!(
+            format_wall_clock("%d/%m/%Y %H:%M:%S 100%% %q", milliseconds),
+            "29/02/2024 13:45:07 100% %q"
+        );
+        assert_eq!(format_wall_clock("%", 0), "%");
+    }
+
+    #[test]
+    fn timestamp_adds_frame_time_to_origin() {
+        let origin = UNIX_EPOCH + Duration::from_secs(19_782 * 86_400 + 23 * 3600 + 59 * 60 + 59);
+        let overlay = TimestampOverlay::new("%F %T.%L", origin);
+        assert_eq!(
+            overlay.format(Time::new(Some(45_000), AvRational::new(1, 90_000))),
+            Some("2024-02-29 23:59:59.500".to_string())
+        );
+        assert_eq!(
+            overlay
+                .clone()
+                .with_utc_offset(3600)
+                .format(Time::from_secs(1.0)),
+            Some("2024-03-01 01:00:00.000".to_string())
+        );
+        assert_eq!(
+            overlay.format(Time::new(None, AvRational::new(1, 90_000))),
+            None
+        );
+    }
+
+    #[test]
+    fn resolves_positions() {
+        let canvas = (640, 480);
+        assert_eq!(Position::TopLeft.resolve((100, 20), canvas, 8), (8, 8));
+        assert_eq!(Position::TopRight.resolve((100, 20), canvas, 8), (532, 8));
+        assert_eq!(
+            Position::BottomCenter.resolve((100, 20), canvas, 8),
+            (270, 452)
+        );
+        assert_eq!(Position::Center.resolve((100, 20), canvas, 8), (270, 230));
+        assert_eq!(Position::At(-5, 7).resolve((100, 20), canvas, 8), (-5, 7));
+        assert_eq!(Position::BottomCenter.line_offset(40, 100), 30);
+        assert_eq!(Position::BottomRight.line_offset(40, 100), 60);
+    }
+
+    #[test]
+    fn selects_active_subtitle_cues() {
+        let millis = |time| Time::new(Some(time), AvRational::new(1, 1000));
+        let overlay = SubtitleOverlay::new([
+            SubtitleCue::text(millis(0), millis(1000), "first"),
+            SubtitleCue::text(millis(1000), millis(2000), "second"),
+            SubtitleCue::text(
+                millis(1500),
+                Time::new(None, AvRational::new(1, 1000)),
+                "open",
+            ),
+        ]);
+        let active = |time| {
+            overlay
+                .active_cues(millis(time))
+                .filter_map(|cue| cue.content().text())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(active(999), vec!["first"]);
+        assert_eq!(active(1000), vec!["second"]);
+        assert_eq!(active(1800), vec!["second", "open"]);
+        assert_eq!(active(5000), vec!["open"]);
+    }
+}
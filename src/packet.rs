@@ -31,6 +31,18 @@ impl Packet {
         Time::new(Some(self.inner.duration()), self.time_base)
     }
 
+    /// Get the index of the stream the packet belongs to.
+    #[inline]
+    pub fn stream_index(&self) -> usize {
+        self.inner.stream()
+    }
+
+    /// Get the time base of the packet timestamps.
+    #[inline]
+    pub fn time_base(&self) -> AvRational {
+        self.time_base
+    }
+
     // Check whether packet is key.
     #[inline]
     pub fn is_key(&self) -> bool {
@@ -1,9 +1,11 @@
 extern crate ffmpeg_next as ffmpeg;
 
-use ffmpeg::codec::Parameters as AvCodecParameters;
+use ffmpeg::codec::{Context as AvContext, Parameters as AvCodecParameters};
+use ffmpeg::media::Type as AvMediaType;
 use ffmpeg::{Error as AvError, Rational as AvRational};
 
 use crate::error::Error;
+use crate::ffi;
 use crate::io::Reader;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -45,6 +47,94 @@ impl StreamInfo {
         })
     }
 
+    /// Check whether the packets of another stream can continue this stream without decoding and
+    /// encoding them. This requires the same codec, the same size and pixel format for video, the
+    /// same sample rate, channel count and sample format for audio, and the same extradata, which
+    /// holds the parameter sets of codecs such as H.264.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Information of the other stream.
+    ///
+    /// # Return value
+    ///
+    /// `None` if the streams are compatible, or a description of the first difference.
+    pub(crate) fn incompatibility_with(&self, other: &StreamInfo) -> Result<Option<String>> {
+        let (expected, actual) = (&self.codec_parameters, &other.codec_parameters);
+        if actual.medium() != expected.medium() {
+            return Ok(Some(format!(
+                "is {:?} instead of {:?}",
+                actual.medium(),
+                expected.medium()
+            )));
+        }
+        if actual.id() != expected.id() {
+            return Ok(Some(format!(
+                "has codec {} instead of {}",
+                actual.id().name(),
+                expected.id().name()
+            )));
+        }
+
+        let context =
+            |parameters: &AvCodecParameters| AvContext::from_parameters(parameters.clone());
+        match expected.medium() {
+            AvMediaType::Video => {
+                let expected = context(expected)?.decoder().video()?;
+                let actual = context(actual)?.decoder().video()?;
+                if (actual.width(), actual.height()) != (expected.width(), expected.height()) {
+                    return Ok(Some(format!(
+                        "has size {}x{} instead of {}x{}",
+                        actual.width(),
+                        actual.height(),
+                        expected.width(),
+                        expected.height()
+                    )));
+                }
+                if actual.format() != expected.format() {
+                    return Ok(Some(format!(
+                        "has pixel format {:?} instead of {:?}",
+                        actual.format(),
+                        expected.format()
+                    )));
+                }
+            }
+            AvMediaType::Audio => {
+                let expected = context(expected)?.decoder().audio()?;
+                let actual = context(actual)?.decoder().audio()?;
+                if actual.rate() != expected.rate() {
+                    return Ok(Some(format!(
+                        "has sample rate {} instead of {}",
+                        actual.rate(),
+                        expected.rate()
+                    )));
+                }
+                if actual.channels() != expected.channels() {
+                    return Ok(Some(format!(
+                        "has {} channels instead of {}",
+                        actual.channels(),
+                        expected.channels()
+                    )));
+                }
+                if actual.format() != expected.format() {
+                    return Ok(Some(format!(
+                        "has sample format {:?} instead of {:?}",
+                        actual.format(),
+                        expected.format()
+                    )));
+                }
+            }
+            _ => {}
+        }
+
+        if ffi::codec_parameters_extradata(actual) != ffi::codec_parameters_extradata(expected) {
+            return Ok(Some(
+                "has different codec extradata, such as other parameter sets".to_string(),
+            ));
+        }
+        Ok(None)
+    }
+
     /// Turn information back into parts for usage.
     ///
     /// Note: Consumes stream information object.
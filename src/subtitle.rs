@@ -0,0 +1,570 @@
+extern crate ffmpeg_next as ffmpeg;
+
+use ffmpeg::codec::decoder::Subtitle as AvSubtitleDecoder;
+use ffmpeg::codec::encoder::subtitle::Encoder as AvSubtitleEncoder;
+use ffmpeg::codec::packet::Packet as AvPacket;
+use ffmpeg::codec::subtitle::{
+    Rect as AvRect, RectMut as AvRectMut, Subtitle as AvSubtitle, Type as AvRectType,
+};
+use ffmpeg::codec::{Context as AvContext, Id as AvCodecId, Parameters as AvCodecParameters};
+use ffmpeg::util::mathematics::rescale::TIME_BASE;
+use ffmpeg::{Error as AvError, Rational as AvRational};
+
+use crate::error::Error;
+use crate::ffi;
+use crate::io::Reader;
+use crate::packet::Packet;
+use crate::stream::StreamInfo;
+use crate::time::Time;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Subtitle display times are expressed in milliseconds.
+const MILLISECONDS: AvRational = AvRational(1, 1000);
+
+/// Size of the buffer that subtitles are encoded into. This is the same size the ffmpeg command
+/// line tool uses.
+const ENCODE_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// ASS script header for text subtitle encoders, with a single `Default` style. This is the header
+/// that ffmpeg generates for text subtitles without styling.
+const DEFAULT_ASS_HEADER: &str = "[Script Info]\r\n\
+    ScriptType: v4.00+\r\n\
+    PlayResX: 384\r\n\
+    PlayResY: 288\r\n\
+    ScaledBorderAndShadow: yes\r\n\
+    YCbCr Matrix: None\r\n\
+    \r\n\
+    [V4+ Styles]\r\n\
+    Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, \
+    Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, \
+    Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\r\n\
+    Style: Default,Arial,16,&Hffffff,&Hffffff,&H0,&H0,0,0,0,0,100,100,0,0,1,1,0,2,10,10,10,1\r\n\
+    \r\n\
+    [Events]\r\n\
+    Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\r\n";
+
+/// Number of fields in an ASS dialogue event as ffmpeg passes it around: `ReadOrder, Layer, Style,
+/// Name, MarginL, MarginR, MarginV, Effect, Text`.
+const ASS_EVENT_FIELDS: usize = 9;
+
+/// A single subtitle cue: content that is shown from `start` until `end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleCue {
+    start: Time,
+    end: Time,
+    content: SubtitleContent,
+}
+
+impl SubtitleCue {
+    /// Create a subtitle cue.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Time at which the cue is shown.
+    /// * `end` - Time at which the cue is hidden.
+    /// * `content` - Content of the cue.
+    pub fn new(start: Time, end: Time, content: SubtitleContent) -> Self {
+        Self {
+            start,
+            end,
+            content,
+        }
+    }
+
+    /// Create a plain text subtitle cue.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Time at which the cue is shown.
+    /// * `end` - Time at which the cue is hidden.
+    /// * `text` - Text of the cue. Lines are separated by `\n`.
+    pub fn text(start: Time, end: Time, text: impl Into<String>) -> Self {
+        Self::new(start, end, SubtitleContent::Text(text.into()))
+    }
+
+    /// Get the time at which the cue is shown.
+    #[inline]
+    pub fn start(&self) -> Time {
+        self.start
+    }
+
+    /// Get the time at which the cue is hidden. This has no value if the stream ended before the
+    /// end of the cue was known.
+    #[inline]
+    pub fn end(&self) -> Time {
+        self.end
+    }
+
+    /// Get the content of the cue.
+    #[inline]
+    pub fn content(&self) -> &SubtitleContent {
+        &self.content
+    }
+
+    /// Turn the cue into its content.
+    pub fn into_content(self) -> SubtitleContent {
+        self.content
+    }
+}
+
+/// Content of a [`SubtitleCue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubtitleContent {
+    /// Plain text. Lines are separated by `\n`.
+    Text(String),
+    /// ASS dialogue event in the form ffmpeg uses for all text subtitle formats, including SRT,
+    /// WebVTT and mov_text: `ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text`.
+    /// Formatting is kept as ASS override tags in the text field.
+    Ass(String),
+    /// Bitmap, as used by DVB, DVD and PGS subtitles.
+    Bitmap(SubtitleBitmap),
+}
+
+impl SubtitleContent {
+    /// Get the plain text of the content, without any formatting. Returns `None` for bitmaps.
+    pub fn text(&self) -> Option<String> {
+        match self {
+            SubtitleContent::Text(text) => Some(text.clone()),
+            SubtitleContent::Ass(event) => Some(ass_event_plain_text(event)),
+            SubtitleContent::Bitmap(_) => None,
+        }
+    }
+}
+
+/// Bitmap subtitle, positioned on the canvas of the subtitle stream (see
+/// [`SubtitleDecoder::size`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleBitmap {
+    /// Horizontal position of the left edge of the bitmap on the canvas.
+    pub x: u32,
+    /// Vertical position of the top edge of the bitmap on the canvas.
+    pub y: u32,
+    /// Width of the bitmap.
+    pub width: u32,
+    /// Height of the bitmap.
+    pub height: u32,
+    /// Pixels in RGBA order, row by row without padding.
+    pub data: Vec<u8>,
+}
+
+/// Decodes packets of a subtitle stream into [`SubtitleCue`]s. Text subtitles (SRT, ASS, WebVTT,
+/// mov_text) produce text cues and bitmap subtitles (DVB, DVD, PGS) produce bitmap cues.
+///
+/// Some formats, such as PGS, do not say how long a cue is shown. Such cues are held back until
+/// the next subtitle of the stream clears them, so their end time is always known. Use
+/// [`SubtitleDecoder::drain`] at the end of the stream to get cues that were never cleared.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut reader = Reader::new(Path::new("movie.mkv"))?;
+/// let stream_index = reader.best_subtitle_stream_index()?;
+/// let mut decoder = SubtitleDecoder::new(&reader, stream_index)?;
+/// while let Ok(packet) = reader.read(stream_index) {
+///     for cue in decoder.decode(packet)? {
+///         println!("{} - {}: {:?}", cue.start(), cue.end(), cue.content().text());
+///     }
+/// }
+/// for cue in decoder.drain() {
+///     println!("{}: {:?}", cue.start(), cue.content().text());
+/// }
+/// ```
+pub struct SubtitleDecoder {
+    decoder: AvSubtitleDecoder,
+    decoder_time_base: AvRational,
+    size: (u32, u32),
+    pending: Vec<SubtitleCue>,
+}
+
+impl SubtitleDecoder {
+    /// Create a subtitle decoder for a stream of a reader.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - [`Reader`] to initialize decoder from.
+    /// * `reader_stream_index` - Index of subtitle stream in reader.
+    pub fn new(reader: &Reader, reader_stream_index: usize) -> Result<Self> {
+        let reader_stream = reader
+            .input
+            .stream(reader_stream_index)
+            .ok_or(AvError::StreamNotFound)?;
+
+        let mut decoder_context = AvContext::new();
+        ffi::set_decoder_context_time_base(&mut decoder_context, reader_stream.time_base());
+        decoder_context.set_parameters(reader_stream.parameters())?;
+
+        let mut decoder = decoder_context.decoder();
+        // Decoders need the packet time base to derive display times from packet durations.
+        decoder.set_packet_time_base(reader_stream.time_base());
+        let decoder = decoder.subtitle()?;
+        let size = ffi::codec_context_size(&decoder);
+
+        Ok(Self {
+            decoder,
+            decoder_time_base: reader_stream.time_base(),
+            size,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Get decoder time base. Cue times are expressed in this time base.
+    #[inline]
+    pub fn time_base(&self) -> AvRational {
+        self.decoder_time_base
+    }
+
+    /// Get the size of the canvas that bitmap cues are positioned on. This is usually the size of
+    /// the video. Text subtitle streams may not have a size.
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Decode a [`Packet`].
+    ///
+    /// # Arguments
+    ///
+    /// * `packet` - Packet of the subtitle stream.
+    ///
+    /// # Return value
+    ///
+    /// Cues of which the end time is known. This can be empty, or contain cues of earlier packets
+    /// that were cleared by this one.
+    pub fn decode(&mut self, packet: Packet) -> Result<Vec<SubtitleCue>> {
+        let packet_pts = packet.pts();
+        let packet = packet.into_inner();
+
+        let mut subtitle = AvSubtitle::new();
+        if !self.decoder.decode(&packet, &mut subtitle)? {
+            return Ok(Vec::new());
+        }
+        let cues = self.cues_from_subtitle(&subtitle, packet_pts);
+        ffi::free_subtitle(&mut subtitle);
+
+        Ok(cues)
+    }
+
+    /// Get the cues that are still waiting for their end time, at the end of the stream. The end
+    /// time of these cues has no value.
+    pub fn drain(&mut self) -> Vec<SubtitleCue> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Reset the decoder, for example after seeking. Cues that are waiting for their end time are
+    /// discarded.
+    pub fn reset(&mut self) {
+        self.decoder.flush();
+        self.pending.clear();
+    }
+
+    /// Turn a decoded subtitle into cues, and end any pending cues at the start of the subtitle.
+    ///
+    /// # Arguments
+    ///
+    /// * `subtitle` - Decoded subtitle.
+    /// * `packet_pts` - PTS of the packet the subtitle was decoded from.
+    fn cues_from_subtitle(&mut self, subtitle: &AvSubtitle, packet_pts: Time) -> Vec<SubtitleCue> {
+        // The packet PTS is in the stream time base. The subtitle PTS is the same time in the
+        // ffmpeg internal time base, which is only used if the packet did not have one.
+        let base = if packet_pts.has_value() && !packet_pts.has_no_pts() {
+            packet_pts.with_time_base(self.decoder_time_base)
+        } else {
+            Time::new(subtitle.pts(), TIME_BASE).with_time_base(self.decoder_time_base)
+        };
+        let start = milliseconds_after(base, subtitle.start());
+        // Display times of zero or the maximum value mean that the subtitle stays on screen until
+        // the next one.
+        let end = match subtitle.end() {
+            0 | u32::MAX => None,
+            end => Some(milliseconds_after(base, end)),
+        };
+
+        let mut cues = self
+            .pending
+            .drain(..)
+            .map(|mut cue| {
+                cue.end = start;
+                cue
+            })
+            .collect::<Vec<_>>();
+
+        for rect in subtitle.rects() {
+            let content = match rect {
+                AvRect::Text(text) => SubtitleContent::Text(text.get().to_string()),
+                AvRect::Ass(ass) => SubtitleContent::Ass(ass.get().to_string()),
+                AvRect::Bitmap(bitmap) => SubtitleContent::Bitmap(SubtitleBitmap {
+                    x: bitmap.x() as u32,
+                    y: bitmap.y() as u32,
+                    width: bitmap.width(),
+                    height: bitmap.height(),
+                    data: ffi::subtitle_bitmap_to_rgba(&bitmap),
+                }),
+                AvRect::None(_) => continue,
+            };
+            match end {
+                Some(end) => cues.push(SubtitleCue::new(start, end, content)),
+                None => self.pending.push(SubtitleCue::new(
+                    start,
+                    Time::new(None, self.decoder_time_base),
+                    content,
+                )),
+            }
+        }
+
+        cues
+    }
+}
+
+unsafe impl Send for SubtitleDecoder {}
+unsafe impl Sync for SubtitleDecoder {}
+
+/// Subtitle codecs that the [`SubtitleEncoder`] can produce. Only text subtitles are supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleCodec {
+    /// SubRip (SRT), for MKV.
+    Srt,
+    /// Advanced SubStation Alpha (ASS), for MKV.
+    Ass,
+    /// WebVTT, for WebM and HLS.
+    WebVtt,
+    /// MPEG-4 Timed Text (mov_text), for MP4 and MOV.
+    MovText,
+}
+
+impl SubtitleCodec {
+    /// Get the ffmpeg codec ID.
+    fn id(&self) -> AvCodecId {
+        match self {
+            SubtitleCodec::Srt => AvCodecId::SUBRIP,
+            SubtitleCodec::Ass => AvCodecId::ASS,
+            SubtitleCodec::WebVtt => AvCodecId::WEBVTT,
+            SubtitleCodec::MovText => AvCodecId::MOV_TEXT,
+        }
+    }
+}
+
+/// Encodes [`SubtitleCue`]s into [`Packet`]s of a subtitle stream, which can be fed to a
+/// [`Muxer`](crate::mux::Muxer) next to the other streams. To add subtitles to the output of an
+/// [`Encoder`](crate::encode::Encoder), use
+/// [`EncoderBuilder::with_subtitle_stream`](crate::encode::EncoderBuilder::with_subtitle_stream)
+/// instead.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut reader = Reader::new(Path::new("input.mp4"))?;
+/// let video_stream_index = reader.best_video_stream_index()?;
+/// let mut subtitles = SubtitleEncoder::new(SubtitleCodec::MovText)?.with_stream_index(1000);
+/// let mut muxer = MuxerBuilder::new(Writer::new(Path::new("output.mp4"))?)
+///     .with_stream(reader.stream_info(video_stream_index)?)?
+///     .with_stream(subtitles.stream_info()?)?
+///     .interleaved()
+///     .build();
+/// muxer.mux(subtitles.encode(&SubtitleCue::text(
+///     Time::from_secs(1.0),
+///     Time::from_secs(3.0),
+///     "Hello!",
+/// ))?)?;
+/// while let Ok(packet) = reader.read(video_stream_index) {
+///     muxer.mux(packet)?;
+/// }
+/// muxer.finish()?;
+/// ```
+pub struct SubtitleEncoder {
+    encoder: AvSubtitleEncoder,
+    stream_index: usize,
+    read_order: u64,
+    buf: Vec<u8>,
+}
+
+impl SubtitleEncoder {
+    /// Create a subtitle encoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `subtitle_codec` - Subtitle codec to encode to.
+    pub fn new(subtitle_codec: SubtitleCodec) -> Result<Self> {
+        let codec = ffmpeg::encoder::find(subtitle_codec.id())
+            .ok_or_else(|| Error::UnsupportedCodec(format!("{subtitle_codec:?}")))?;
+        let mut encoder_context = ffi::codec_context_as(&codec)?;
+        ffi::set_encoder_context_time_base(&mut encoder_context, MILLISECONDS);
+        // Text subtitle encoders parse the styles from the ASS header, and refuse to open without
+        // one.
+        ffi::set_codec_context_subtitle_header(&mut encoder_context, DEFAULT_ASS_HEADER)?;
+        let encoder = encoder_context.encoder().subtitle()?.open_as(codec)?;
+
+        Ok(Self {
+            encoder,
+            stream_index: 0,
+            read_order: 0,
+            buf: vec![0; ENCODE_BUFFER_SIZE],
+        })
+    }
+
+    /// Set the stream index of the encoded packets and stream information. Defaults to zero.
+    pub fn with_stream_index(mut self, stream_index: usize) -> Self {
+        self.stream_index = stream_index;
+        self
+    }
+
+    /// Get stream information of the encoded stream. This can be used to add the stream to a
+    /// [`Muxer`](crate::mux::Muxer).
+    pub fn stream_info(&self) -> Result<StreamInfo> {
+        StreamInfo::from_params(
+            AvCodecParameters::from(&self.encoder),
+            self.time_base(),
+            self.stream_index,
+        )
+    }
+
+    /// Get encoder time base.
+    #[inline]
+    pub fn time_base(&self) -> AvRational {
+        MILLISECONDS
+    }
+
+    /// Encode a single cue.
+    ///
+    /// # Arguments
+    ///
+    /// * `cue` - Cue to encode. Bitmap cues are not supported.
+    pub fn encode(&mut self, cue: &SubtitleCue) -> Result<Packet> {
+        let event = match cue.content() {
+            SubtitleContent::Text(text) => ass_event(self.read_order, text),
+            SubtitleContent::Ass(event) => event.clone(),
+            SubtitleContent::Bitmap(_) => return Err(Error::UnsupportedSubtitleContent),
+        };
+        self.read_order += 1;
+
+        let start = cue.start().with_time_base(MILLISECONDS).into_value();
+        let duration = cue
+            .end()
+            .aligned_with(cue.start())
+            .subtract()
+            .with_time_base(MILLISECONDS)
+            .into_value()
+            .map(|duration| duration.max(0));
+
+        let mut subtitle = AvSubtitle::new();
+        // The start display time must be zero, the cue start is carried by the packet PTS instead.
+        subtitle.set_pts(start.map(|start| start * 1000));
+        subtitle.set_end(duration.unwrap_or(0) as u32);
+        if let AvRectMut::Ass(mut rect) = subtitle.add_rect(AvRectType::Ass) {
+            rect.set(&event);
+        }
+        let size = ffi::encode_subtitle(&mut self.encoder, &subtitle, &mut self.buf);
+        ffi::free_subtitle(&mut subtitle);
+
+        let mut packet = AvPacket::copy(&self.buf[..size?]);
+        packet.set_stream(self.stream_index);
+        packet.set_pts(start);
+        packet.set_dts(start);
+        if let Some(duration) = duration {
+            packet.set_duration(duration);
+        }
+
+        Ok(Packet::new(packet, MILLISECONDS))
+    }
+}
+
+unsafe impl Send for SubtitleEncoder {}
+unsafe impl Sync for SubtitleEncoder {}
+
+/// Offset a time by a number of milliseconds.
+///
+/// # Arguments
+///
+/// * `time` - Time to offset.
+/// * `milliseconds` - Number of milliseconds.
+fn milliseconds_after(time: Time, milliseconds: u32) -> Time {
+    time.aligned_with(Time::new(Some(milliseconds as i64), MILLISECONDS))
+        .add()
+}
+
+/// Build an ASS dialogue event with the default style for plain text.
+///
+/// # Arguments
+///
+/// * `read_order` - Position of the event in the stream.
+/// * `text` - Plain text. Lines are separated by `\n`.
+fn ass_event(read_order: u64, text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\n', "\\N");
+    format!("{read_order},0,Default,,0,0,0,,{text}")
+}
+
+/// Extract the plain text from an ASS dialogue event, without override tags.
+///
+/// # Arguments
+///
+/// * `event` - ASS dialogue event.
+fn ass_event_plain_text(event: &str) -> String {
+    let text = event
+        .splitn(ASS_EVENT_FIELDS, ',')
+        .nth(ASS_EVENT_FIELDS - 1)
+        .unwrap_or(event);
+
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // Override blocks such as `{\i1}` only change formatting.
+            '{' => {
+                if !chars.by_ref().any(|c| c == '}') {
+                    break;
+                }
+            }
+            '\\' => match chars.next() {
+                Some('N') | Some('n') => plain.push('\n'),
+                Some('h') => plain.push(' '),
+                Some(other) => {
+                    plain.push('\\');
+                    plain.push(other);
+                }
+                None => plain.push('\\'),
+            },
+            c => plain.push(c),
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_strips_override_tags() {
+        let event = "3,0,Default,,0,0,0,,{\\i1}Hello{\\i0},\\Nworld\\hagain";
+        assert_eq!(ass_event_plain_text(event), "Hello,\nworld again");
+    }
+
+    #[test]
+    fn plain_text_round_trips_through_event() {
+        let event = ass_event(7, "first line\r\nsecond, line");
+        assert_eq!(event, "7,0,Default,,0,0,0,,first line\\Nsecond, line");
+        assert_eq!(ass_event_plain_text(&event), "first line\nsecond, line");
+    }
+
+    #[test]
+    fn bitmap_content_has_no_text() {
+        let content = SubtitleContent::Bitmap(SubtitleBitmap {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+            data: vec![255, 255, 255, 255],
+        });
+        assert_eq!(content.text(), None);
+        assert_eq!(
+            SubtitleContent::Text("hi".to_string()).text(),
+            Some("hi".to_string())
+        );
+    }
+
+    #[test]
+    fn milliseconds_are_added_in_time_base_of_time() {
+        let start = milliseconds_after(Time::new(Some(900), AvRational::new(1, 90000)), 1500);
+        assert_eq!(start, Time::new(Some(135_900), AvRational::new(1, 90000)));
+    }
+}
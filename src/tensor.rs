@@ -0,0 +1,412 @@
+use ndarray::{s, Array3, Array4, ArrayView3, ArrayView4, ArrayViewMut3, Axis};
+
+use crate::error::Error;
+use crate::time::Time;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A tensor frame is a normalized floating-point version of a frame. It is a 3-dimensional array
+/// with either dims `(C, H, W)` or `(H, W, C)` depending on the [`TensorLayout`].
+pub type TensorFrame = Array3<f32>;
+
+/// Memory layout of a tensor frame.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TensorLayout {
+    /// Channels first: `(C, H, W)`. This is the layout most models expect.
+    #[default]
+    Chw,
+    /// Channels last: `(H, W, C)`. This is the same layout as [`Frame`](crate::frame::Frame).
+    Hwc,
+}
+
+/// Per-channel normalization to apply when converting byte frames to tensor frames.
+///
+/// Each channel value `x` is first scaled to `[0, 1]` and then normalized as follows:
+/// `(x / 255 - mean) / std`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Normalization {
+    mean: [f32; 3],
+    std: [f32; 3],
+}
+
+impl Normalization {
+    /// Create a normalization with a custom mean and standard deviation per channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `mean` - Mean per channel (in RGB order) in the `[0, 1]` range.
+    /// * `std` - Standard deviation per channel (in RGB order) in the `[0, 1]` range.
+    pub fn new(mean: [f32; 3], std: [f32; 3]) -> Self {
+        Self { mean, std }
+    }
+
+    /// Normalization that only scales values to the `[0, 1]` range.
+    pub fn unit() -> Self {
+        Self::new([0.0, 0.0, 0.0], [1.0, 1.0, 1.0])
+    }
+
+    /// Normalization using the mean and standard deviation of the ImageNet dataset. Most
+    /// pretrained vision models expect this normalization.
+    pub fn imagenet() -> Self {
+        Self::new([0.485, 0.456, 0.406], [0.229, 0.224, 0.225])
+    }
+
+    /// Compute the scale and offset per channel such that `x * scale + offset` is equal to
+    /// `(x / 255 - mean) / std`.
+    fn scale_and_offset(&self) -> ([f32; 3], [f32; 3]) {
+        let scale = [0, 1, 2].map(|c| 1.0 / (255.0 * self.std[c]));
+        let offset = [0, 1, 2].map(|c| -self.mean[c] / self.std[c]);
+        (scale, offset)
+    }
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self::unit()
+    }
+}
+
+/// Holds settings for converting byte frames to tensor frames.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct TensorSettings {
+    layout: TensorLayout,
+    normalization: Normalization,
+}
+
+impl TensorSettings {
+    /// Create tensor settings with the default layout (`CHW`) and normalization (`[0, 1]` range).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the memory layout of the tensor.
+    ///
+    /// * `layout` - Layout to use.
+    pub fn with_layout(mut self, layout: TensorLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set the normalization to apply.
+    ///
+    /// * `normalization` - Normalization to apply.
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Get the memory layout of the tensor.
+    #[inline]
+    pub fn layout(&self) -> TensorLayout {
+        self.layout
+    }
+
+    /// Get the normalization.
+    #[inline]
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Compute the tensor dimensions for a frame with the given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Frame width and height.
+    pub fn dims_for(&self, size: (u32, u32)) -> (usize, usize, usize) {
+        let (width, height) = (size.0 as usize, size.1 as usize);
+        match self.layout {
+            TensorLayout::Chw => (3, height, width),
+            TensorLayout::Hwc => (height, width, 3),
+        }
+    }
+}
+
+/// Convert a byte frame with dims `(H, W, C)` to a tensor frame.
+///
+/// # Arguments
+///
+/// * `frame` - Frame to convert. The frame format must be `(H, W, C)` with 3 channels.
+/// * `settings` - Layout and normalization to use.
+///
+/// # Return value
+///
+/// A newly allocated tensor frame.
+pub fn convert_frame_to_tensor(
+    frame: ArrayView3<u8>,
+    settings: &TensorSettings,
+) -> Result<TensorFrame> {
+    let (height, width, _) = frame.dim();
+    let mut tensor = TensorFrame::zeros(settings.dims_for((width as u32, height as u32)));
+    convert_frame_to_tensor_into(frame, settings, tensor.view_mut())?;
+    Ok(tensor)
+}
+
+/// Convert a byte frame with dims `(H, W, C)` to a tensor frame, writing into an existing buffer.
+///
+/// # Arguments
+///
+/// * `frame` - Frame to convert. The frame format must be `(H, W, C)` with 3 channels.
+/// * `settings` - Layout and normalization to use.
+/// * `tensor` - Buffer to write to. Its dims must correspond to the frame size and layout.
+pub fn convert_frame_to_tensor_into(
+    frame: ArrayView3<u8>,
+    settings: &TensorSettings,
+    mut tensor: ArrayViewMut3<f32>,
+) -> Result<()> {
+    let (height, width, channels) = frame.dim();
+    if channels != 3 || tensor.dim() != settings.dims_for((width as u32, height as u32)) {
+        return Err(Error::InvalidFrameFormat);
+    }
+
+    // Permute the output view so that we can always iterate in `(H, W, C)` order.
+    if settings.layout == TensorLayout::Chw {
+        tensor = tensor.permuted_axes([1, 2, 0]);
+    }
+
+    let (scale, offset) = settings.normalization.scale_and_offset();
+    let channels = tensor.axis_iter_mut(Axis(2)).zip(frame.axis_iter(Axis(2)));
+    for (c, (mut tensor_channel, frame_channel)) in channels.enumerate() {
+        tensor_channel.zip_mut_with(&frame_channel, |out, &value| {
+            *out = value as f32 * scale[c] + offset[c];
+        });
+    }
+
+    Ok(())
+}
+
+/// A batch of tensor frames with their timestamps, collected into a single 4-dimensional array
+/// with dims `(N, C, H, W)` or `(N, H, W, C)` depending on the [`TensorLayout`].
+///
+/// The backing buffer is allocated once and reused for every batch, so the caller should
+/// [`clear`](FrameBatch::clear) the batch instead of creating a new one.
+///
+/// # Example
+///
+/// ```ignore
+/// let settings = TensorSettings::new().with_normalization(Normalization::imagenet());
+/// let mut batch = FrameBatch::new(8, decoder.size_out(), settings);
+/// while decoder.decode_batch(&mut batch).is_ok() {
+///     let tensor = batch.tensor();
+///     // Run inference on tensor...
+/// }
+/// ```
+pub struct FrameBatch {
+    data: Array4<f32>,
+    timestamps: Vec<Time>,
+    size: (u32, u32),
+    settings: TensorSettings,
+}
+
+impl FrameBatch {
+    /// Create a new, empty batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Maximum number of frames in the batch. A batch holds at least one frame, so
+    ///   a capacity of zero is raised to one.
+    /// * `size` - Width and height of each frame.
+    /// * `settings` - Layout and normalization to use.
+    pub fn new(capacity: usize, size: (u32, u32), settings: TensorSettings) -> Self {
+        let capacity = capacity.max(1);
+        let (d0, d1, d2) = settings.dims_for(size);
+        Self {
+            data: Array4::zeros((capacity, d0, d1, d2)),
+            timestamps: Vec::with_capacity(capacity),
+            size,
+            settings,
+        }
+    }
+
+    /// Convert a frame and add it to the batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - Frame timestamp.
+    /// * `frame` - Frame to add. The frame format must be `(H, W, C)` with 3 channels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the batch is full.
+    pub fn push(&mut self, timestamp: Time, frame: ArrayView3<u8>) -> Result<()> {
+        assert!(!self.is_full(), "frame batch is full");
+        let (height, width, _) = frame.dim();
+        if (width as u32, height as u32) != self.size {
+            return Err(Error::InvalidFrameFormat);
+        }
+
+        let index = self.timestamps.len();
+        convert_frame_to_tensor_into(
+            frame,
+            &self.settings,
+            self.data.index_axis_mut(Axis(0), index),
+        )?;
+        self.timestamps.push(timestamp);
+
+        Ok(())
+    }
+
+    /// Remove all frames from the batch. This does not deallocate the backing buffer.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.timestamps.clear();
+    }
+
+    /// Number of frames in the batch.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Whether or not the batch is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Whether or not the batch is full.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.timestamps.len() == self.capacity()
+    }
+
+    /// Maximum number of frames in the batch.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.data.len_of(Axis(0))
+    }
+
+    /// Width and height of each frame in the batch.
+    #[inline]
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Get the batch tensor. Only includes frames that were added since the batch was last
+    /// cleared, so the first dimension is equal to [`len`](FrameBatch::len).
+    pub fn tensor(&self) -> ArrayView4<'_, f32> {
+        self.data.slice(s![..self.len(), .., .., ..])
+    }
+
+    /// Get the timestamps of the frames in the batch, in order.
+    #[inline]
+    pub fn timestamps(&self) -> &[Time] {
+        &self.timestamps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Decoder;
+    use crate::encode::Settings;
+    use crate::frame::PixelFormat;
+    use crate::testing;
+
+    fn testing_frame() -> Array3<u8> {
+        Array3::from_shape_fn((2, 4, 3), |(y, x, c)| (y * 100 + x * 10 + c) as u8)
+    }
+
+    #[test]
+    fn convert_hwc_unit() {
+        let frame = testing_frame();
+        let settings = TensorSettings::new().with_layout(TensorLayout::Hwc);
+        let tensor = convert_frame_to_tensor(frame.view(), &settings).unwrap();
+        assert_eq!(tensor.dim(), (2, 4, 3));
+        for ((y, x, c), value) in tensor.indexed_iter() {
+            assert!((value - frame[[y, x, c]] as f32 / 255.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn convert_chw_unit() {
+        let frame = testing_frame();
+        let tensor = convert_frame_to_tensor(frame.view(), &TensorSettings::new()).unwrap();
+        assert_eq!(tensor.dim(), (3, 2, 4));
+        for ((c, y, x), value) in tensor.indexed_iter() {
+            assert!((value - frame[[y, x, c]] as f32 / 255.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn convert_chw_imagenet() {
+        let frame = testing_frame();
+        let normalization = Normalization::imagenet();
+        let settings = TensorSettings::new().with_normalization(normalization);
+        let tensor = convert_frame_to_tensor(frame.view(), &settings).unwrap();
+        for ((c, y, x), value) in tensor.indexed_iter() {
+            let expected =
+                (frame[[y, x, c]] as f32 / 255.0 - normalization.mean[c]) / normalization.std[c];
+            assert!((value - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn convert_into_rejects_wrong_dims() {
+        let frame = testing_frame();
+        let mut tensor = TensorFrame::zeros((2, 4, 3));
+        assert!(convert_frame_to_tensor_into(
+            frame.view(),
+            &TensorSettings::new(),
+            tensor.view_mut()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn batch_push_and_clear() {
+        let frame = testing_frame();
+        let mut batch = FrameBatch::new(2, (4, 2), TensorSettings::new());
+        assert!(batch.is_empty());
+        batch.push(Time::from_secs(0.0), frame.view()).unwrap();
+        batch.push(Time::from_secs(0.5), frame.view()).unwrap();
+        assert!(batch.is_full());
+        assert_eq!(batch.tensor().dim(), (2, 3, 2, 4));
+        assert_eq!(
+            batch.timestamps(),
+            &[Time::from_secs(0.0), Time::from_secs(0.5)]
+        );
+        batch.clear();
+        assert!(batch.is_empty());
+        assert_eq!(batch.tensor().dim(), (0, 3, 2, 4));
+        assert_eq!(batch.capacity(), 2);
+    }
+
+    #[test]
+    fn batch_holds_at_least_one_frame() {
+        let batch = FrameBatch::new(0, (4, 2), TensorSettings::new());
+        assert_eq!(batch.capacity(), 1);
+        assert!(!batch.is_full());
+    }
+
+    #[test]
+    fn decodes_trailing_partial_batch() {
+        let (width, height) = testing::SIZE;
+        let path = testing::temp_path("decode-batch.mkv");
+        testing::write_video(
+            &path,
+            Settings::preset_ffv1(width, height, PixelFormat::YUV420P),
+            5,
+        );
+
+        let mut decoder = Decoder::new(path.as_path()).unwrap();
+        let mut batch = FrameBatch::new(2, decoder.size_out(), TensorSettings::new());
+        let mut lengths = Vec::new();
+        let result = loop {
+            match decoder.decode_batch(&mut batch) {
+                Ok(length) => lengths.push(length),
+                Err(err) => break err,
+            }
+        };
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Error::DecodeExhausted), "{result}");
+        assert_eq!(lengths, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn batch_rejects_wrong_size() {
+        let frame = testing_frame();
+        let mut batch = FrameBatch::new(1, (2, 4), TensorSettings::new());
+        assert!(batch.push(Time::zero(), frame.view()).is_err());
+        assert!(batch.is_empty());
+    }
+}
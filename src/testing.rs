@@ -0,0 +1,58 @@
+extern crate ffmpeg_next as ffmpeg;
+
+use std::path::{Path, PathBuf};
+
+use ffmpeg::Rational as AvRational;
+
+use crate::encode::{Encoder, Settings};
+use crate::frame::{PixelFormat, RawFrame, VideoFrame};
+
+/// Time base of generated videos, with one tick per frame at 25 fps.
+pub const TIME_BASE: AvRational = AvRational(1, 25);
+
+/// Width and height of the frames of generated videos.
+pub const SIZE: (usize, usize) = (64, 48);
+
+/// Get a path in the temporary directory that is unique to the test process.
+///
+/// # Arguments
+///
+/// * `name` - File name, including the extension that decides the container format.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("video-rs-{}-{name}", std::process::id()))
+}
+
+/// Generate a YUV420P frame with a pattern that moves from frame to frame.
+///
+/// # Arguments
+///
+/// * `index` - Index of the frame, which is also its timestamp in [`TIME_BASE`].
+pub fn generate_frame(index: i64) -> VideoFrame {
+    let (width, height) = SIZE;
+    let mut frame = RawFrame::new(PixelFormat::YUV420P, width as u32, height as u32);
+    let stride = frame.stride(0);
+    for (y, row) in frame.data_mut(0).chunks_mut(stride).enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = (x + 2 * y + 3 * index as usize) as u8;
+        }
+    }
+    frame.data_mut(1).fill(128);
+    frame.data_mut(2).fill(128);
+    frame.set_pts(Some(index));
+    VideoFrame::new(frame, TIME_BASE)
+}
+
+/// Encode generated frames to a file.
+///
+/// # Arguments
+///
+/// * `path` - Where to encode to.
+/// * `settings` - Encoding settings, for frames of [`SIZE`].
+/// * `count` - Number of frames.
+pub fn write_video(path: &Path, settings: Settings, count: i64) {
+    let mut encoder = Encoder::new(path, settings).unwrap();
+    for index in 0..count {
+        encoder.encode_frame(generate_frame(index)).unwrap();
+    }
+    encoder.finish().unwrap();
+}